@@ -5,6 +5,7 @@ use axum_login::{
 	AuthManagerLayerBuilder,
 };
 use sqlx::PgPool;
+use std::sync::Arc;
 use tower_http::cors;
 use tokio::{signal, task::AbortHandle};
 use tower_sessions::cookie::Key;
@@ -14,13 +15,38 @@ use time::Duration;
 use crate::handler::{
 	book::create_book_app,
 	memo::create_memo_app,
+	search::create_search_app,
+	tag::create_tag_app,
+	shelf::create_shelf_app,
+	job::create_job_app,
+	admin::create_admin_app,
+	import::create_import_app,
+	export::create_export_app,
 	auth::create_auth_app
 };
+use crate::modules::job_worker::run_job_worker;
+use crate::provider::{
+	cache::CachedProvider,
+	chain::ProviderChain,
+	fixture::FixtureProvider,
+	google_books::GoogleBooksProvider,
+	ndl::NdlProvider,
+	openbd::OpenBdProvider,
+	PayloadProvider,
+	SharedProvider,
+};
 use crate::repos::{
 	book::{BookRepositoryForPg, BookRepository},
 	memo::{MemoRepositoryForPg, MemoRepository},
+	reading::{ReadingRepositoryForPg, ReadingRepository},
+	search::{SearchRepositoryForPg, SearchRepository},
+	tag::{TagRepositoryForPg, TagRepository},
+	shelf::{ShelfRepositoryForPg, ShelfRepository},
+	job::{JobRepositoryForPg, JobRepository},
+	metadata_cache::{MetadataCacheRepositoryForPg, MetadataCacheRepository},
 	auth::AuthRepositoryForPg,
 };
+use crate::storage::{local::LocalBlobStore, SharedBlobStore};
 
 pub struct App {
 	db: PgPool,
@@ -59,11 +85,38 @@ impl App {
 
 		let book_repos = BookRepositoryForPg::new(self.db.clone());
 		let memo_repos = MemoRepositoryForPg::new(self.db.clone());
+		let reading_repos = ReadingRepositoryForPg::new(self.db.clone());
+		let search_repos = SearchRepositoryForPg::new(self.db.clone());
+		let tag_repos = TagRepositoryForPg::new(self.db.clone());
+		let shelf_repos = ShelfRepositoryForPg::new(self.db.clone());
+		let job_repos = JobRepositoryForPg::new(self.db.clone());
+		let cache_repos = MetadataCacheRepositoryForPg::new(self.db.clone());
+		let provider = create_provider(&cache_repos)?;
+		// 表紙の画像はCOVER_STORAGE_DIRに保存する
+		let blob_store: SharedBlobStore = Arc::new(LocalBlobStore::new(
+			std::env::var("COVER_STORAGE_DIR").unwrap_or("./storage".to_string()),
+		));
+
+		// 受け付けた登録の書誌情報を後から取得するワーカー
+		let worker_count = std::env::var("JOB_WORKERS")
+			.ok()
+			.and_then(|count| count.parse::<usize>().ok())
+			.unwrap_or(2);
+		let worker_tasks: Vec<_> = (0..worker_count)
+			.map(|_| {
+				tokio::task::spawn(run_job_worker(
+					job_repos.clone(),
+					book_repos.clone(),
+					provider.clone(),
+					blob_store.clone(),
+				))
+			})
+			.collect();
 
 		let host = std::env::var("APP_HOST").expect("APP_HOST is not defined");
 		let port = std::env::var("APP_PORT").expect("APP_PORT is not defined");
 
-		let app = create_app(book_repos, memo_repos)
+		let app = create_app(book_repos, memo_repos, reading_repos, search_repos, tag_repos, shelf_repos, job_repos, cache_repos, provider, blob_store)
 			.route_layer(login_required!(AuthRepositoryForPg))
 			.merge(create_auth_app())
 			.layer(auth_layer)
@@ -78,6 +131,8 @@ impl App {
 					.allow_methods([
 						http::method::Method::GET,
 						http::method::Method::POST,
+						http::method::Method::PUT,
+						http::method::Method::PATCH,
 						http::method::Method::DELETE,
 					])
 					.allow_credentials(true),
@@ -87,34 +142,133 @@ impl App {
 			.await
 			.expect("failed to listen");
 
-		// Ensure we use a shutdown signal to abort the deletion task.
+		// Ensure we use a shutdown signal to abort the deletion task and the job workers.
+		let mut abort_handles = vec![deletion_task.abort_handle()];
+		abort_handles.extend(worker_tasks.iter().map(|task| task.abort_handle()));
 		axum::serve(listener, app.into_make_service())
-			.with_graceful_shutdown(shutdown_signal(deletion_task.abort_handle()))
+			.with_graceful_shutdown(shutdown_signal(abort_handles))
 			.await?;
 
 		deletion_task.await.ok();
+		for task in worker_tasks {
+			task.await.ok();
+		}
 
 		Ok(())
 	}
 }
 
-fn create_app<BookRepos, MemoRepos>(book_repos: BookRepos, memo_repos: MemoRepos) -> axum::Router
+#[allow(clippy::too_many_arguments)]
+fn create_app<BookRepos, MemoRepos, ReadingRepos, SearchRepos, TagRepos, ShelfRepos, JobRepos, CacheRepos>(
+	book_repos: BookRepos,
+	memo_repos: MemoRepos,
+	reading_repos: ReadingRepos,
+	search_repos: SearchRepos,
+	tag_repos: TagRepos,
+	shelf_repos: ShelfRepos,
+	job_repos: JobRepos,
+	cache_repos: CacheRepos,
+	provider: SharedProvider,
+	blob_store: SharedBlobStore,
+) -> axum::Router
 where
 	BookRepos: BookRepository,
 	MemoRepos: MemoRepository,
+	ReadingRepos: ReadingRepository,
+	SearchRepos: SearchRepository,
+	TagRepos: TagRepository,
+	ShelfRepos: ShelfRepository,
+	JobRepos: JobRepository,
+	CacheRepos: MetadataCacheRepository,
 {
 	axum::Router::new()
 		.nest(
 			"/book",
-			create_book_app(&book_repos, &memo_repos)
+			create_book_app(&book_repos, &memo_repos, &reading_repos, &tag_repos, &shelf_repos, &job_repos, &provider, &blob_store)
 		)
 		.nest(
 			"/memo",
-			create_memo_app(&memo_repos)
+			create_memo_app(&memo_repos, &tag_repos)
+		)
+		.nest(
+			"/search",
+			create_search_app(&search_repos)
+		)
+		.nest(
+			"/tag",
+			create_tag_app(&tag_repos)
+		)
+		.nest(
+			"/shelf",
+			create_shelf_app(&shelf_repos, &book_repos)
+		)
+		.nest(
+			"/job",
+			create_job_app(&job_repos, &book_repos)
+		)
+		.nest(
+			"/import",
+			create_import_app(&book_repos, &memo_repos, &reading_repos, &tag_repos, &job_repos, &provider)
+		)
+		.nest(
+			"/export",
+			create_export_app(&book_repos, &memo_repos)
+		)
+		.nest(
+			"/admin",
+			create_admin_app(&cache_repos)
 		)
 }
 
-async fn shutdown_signal(deletion_task_abort_handle: AbortHandle) {
+// BOOK_PROVIDERSにカンマ区切りで並べた順に書誌情報の取得元へ問い合わせる
+// 取得元の応答はMETADATA_CACHE_TTL_HOURSの間DBに保存して使い回す
+fn create_provider<CacheRepos: MetadataCacheRepository>(
+	cache_repos: &CacheRepos,
+) -> Result<SharedProvider, Box<dyn std::error::Error>> {
+	let names = std::env::var("BOOK_PROVIDERS").unwrap_or("google_books".to_string());
+	let ttl_hours = std::env::var("METADATA_CACHE_TTL_HOURS")
+		.ok()
+		.and_then(|hours| hours.parse::<i64>().ok())
+		.unwrap_or(24 * 30);
+	let cached = |provider: Arc<dyn PayloadProvider>| -> SharedProvider {
+		Arc::new(CachedProvider::new(
+			provider,
+			cache_repos.clone(),
+			chrono::Duration::hours(ttl_hours),
+		))
+	};
+
+	let mut providers: Vec<SharedProvider> = Vec::new();
+	for name in names.split(',').map(str::trim) {
+		let provider: SharedProvider = match name {
+			"google_books" => cached(Arc::new(
+				std::env::var("GOOGLE_BOOKS_URL")
+					.map(|url| GoogleBooksProvider::with_base_url(&url))
+					.unwrap_or_default(),
+			)),
+			"openbd" => cached(Arc::new(
+				std::env::var("OPENBD_URL")
+					.map(|url| OpenBdProvider::with_base_url(&url))
+					.unwrap_or_default(),
+			)),
+			"ndl" => cached(Arc::new(
+				std::env::var("NDL_URL")
+					.map(|url| NdlProvider::with_base_url(&url))
+					.unwrap_or_default(),
+			)),
+			"fixture" => {
+				let path = std::env::var("BOOK_FIXTURE_PATH").expect("BOOK_FIXTURE_PATH is not defined");
+				Arc::new(FixtureProvider::from_file(&path)?)
+			}
+			_ => return Err(format!("unknown book provider: {}", name).into()),
+		};
+		providers.push(provider);
+	}
+
+	Ok(Arc::new(ProviderChain::new(providers)))
+}
+
+async fn shutdown_signal(abort_handles: Vec<AbortHandle>) {
 	let ctrl_c = async {
 		signal::ctrl_c()
 			.await
@@ -133,7 +287,10 @@ async fn shutdown_signal(deletion_task_abort_handle: AbortHandle) {
 	let terminate = std::future::pending::<()>();
 
 	tokio::select! {
-        _ = ctrl_c => { deletion_task_abort_handle.abort() },
-        _ = terminate => { deletion_task_abort_handle.abort() },
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
+	for handle in abort_handles {
+		handle.abort();
+	}
 }
@@ -0,0 +1,256 @@
+use axum::{
+	async_trait,
+	extract::{FromRequestParts, Path},
+	http::{request::Parts, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::repos::book::INTERNAL_ID_PREFIX;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum IsbnError {
+	#[error("ISBN must have 10 or 13 digits, got {0}")]
+	InvalidLength(usize),
+	#[error("ISBN contains an invalid character '{0}'")]
+	InvalidCharacter(char),
+	#[error("ISBN-13 must start with 978 or 979")]
+	InvalidPrefix,
+	#[error("ISBN check digit does not match, expected {0}")]
+	InvalidChecksum(char),
+}
+
+// ハイフンを除いて正規化した13桁のISBN
+// 手入力した本の内部の識別子もそのまま受け付ける
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Isbn13(String);
+
+impl Isbn13 {
+	pub fn parse(input: &str) -> Result<Self, IsbnError> {
+		let cleaned: String = input
+			.chars()
+			.filter(|c| *c != '-' && !c.is_whitespace())
+			.collect::<String>()
+			.to_uppercase();
+
+		if is_internal_id(&cleaned) {
+			return Ok(Isbn13(cleaned));
+		}
+		// 以降は1文字1バイトとして切り出すので、ASCII以外の文字はここで弾く
+		if let Some(c) = cleaned.chars().find(|c| !c.is_ascii()) {
+			return Err(IsbnError::InvalidCharacter(c));
+		}
+
+		match cleaned.chars().count() {
+			10 => from_isbn_10(&cleaned),
+			13 => {
+				let digits = parse_digits(&cleaned)?;
+				if !cleaned.starts_with("978") && !cleaned.starts_with("979") {
+					return Err(IsbnError::InvalidPrefix);
+				}
+				let expected = isbn_13_check_digit(&digits[..12]);
+				if digits[12] != expected {
+					return Err(IsbnError::InvalidChecksum(digit_char(expected)));
+				}
+				Ok(Isbn13(cleaned))
+			}
+			len => Err(IsbnError::InvalidLength(len)),
+		}
+	}
+
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+
+	pub fn is_internal(&self) -> bool {
+		is_internal_id(&self.0)
+	}
+
+	// 978で始まるISBNのみISBN-10に戻せる
+	pub fn to_isbn_10(&self) -> Option<String> {
+		if self.is_internal() || !self.0.starts_with("978") {
+			return None;
+		}
+		let digits = parse_digits(&self.0[3..12]).ok()?;
+		let check = isbn_10_check_digit(&digits);
+		Some(format!("{}{}", &self.0[3..12], check))
+	}
+}
+
+fn is_internal_id(value: &str) -> bool {
+	value.len() == 13
+		&& value.starts_with(INTERNAL_ID_PREFIX)
+		&& value[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn parse_digits(value: &str) -> Result<Vec<u32>, IsbnError> {
+	value
+		.chars()
+		.map(|c| c.to_digit(10).ok_or(IsbnError::InvalidCharacter(c)))
+		.collect()
+}
+
+fn digit_char(digit: u32) -> char {
+	char::from_digit(digit, 10).unwrap_or('0')
+}
+
+fn isbn_13_check_digit(digits: &[u32]) -> u32 {
+	let sum: u32 = digits
+		.iter()
+		.enumerate()
+		.map(|(i, digit)| if i % 2 == 0 { *digit } else { digit * 3 })
+		.sum();
+	(10 - sum % 10) % 10
+}
+
+fn isbn_10_check_digit(digits: &[u32]) -> char {
+	let sum: u32 = digits
+		.iter()
+		.enumerate()
+		.map(|(i, digit)| digit * (10 - i as u32))
+		.sum();
+	match (11 - sum % 11) % 11 {
+		10 => 'X',
+		check => digit_char(check),
+	}
+}
+
+fn from_isbn_10(value: &str) -> Result<Isbn13, IsbnError> {
+	let digits = parse_digits(&value[..9])?;
+	let check = value.chars().last().unwrap_or_default();
+	if !check.is_ascii_digit() && check != 'X' {
+		return Err(IsbnError::InvalidCharacter(check));
+	}
+	let expected = isbn_10_check_digit(&digits);
+	if check != expected {
+		return Err(IsbnError::InvalidChecksum(expected));
+	}
+
+	let mut digits_13 = vec![9, 7, 8];
+	digits_13.extend(digits);
+	let check_13 = isbn_13_check_digit(&digits_13);
+	Ok(Isbn13(format!("978{}{}", &value[..9], check_13)))
+}
+
+impl std::fmt::Display for Isbn13 {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl std::ops::Deref for Isbn13 {
+	type Target = str;
+
+	fn deref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl TryFrom<String> for Isbn13 {
+	type Error = IsbnError;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		Isbn13::parse(&value)
+	}
+}
+
+impl From<Isbn13> for String {
+	fn from(value: Isbn13) -> Self {
+		value.0
+	}
+}
+
+// パスの:isbn_13を取り出し、DBやネットワークに問い合わせる前に検証する
+#[async_trait]
+impl<S> FromRequestParts<S> for Isbn13
+where
+	S: Send + Sync,
+{
+	type Rejection = (StatusCode, String);
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+		let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+			.await
+			.map_err(|rejection| (StatusCode::BAD_REQUEST, rejection.to_string()))?;
+		let value = params.get("isbn_13").ok_or((
+			StatusCode::INTERNAL_SERVER_ERROR,
+			"route has no :isbn_13 parameter".to_string(),
+		))?;
+
+		Isbn13::parse(value).map_err(|err| (StatusCode::BAD_REQUEST, format!("Invalid isbn_13 '{}': {}", value, err)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_converts_isbn_10() {
+		let isbn_13 = Isbn13::parse("9784101010014").unwrap();
+		let isbn_10 = isbn_13.to_isbn_10().unwrap();
+		assert_eq!(Isbn13::parse(&isbn_10).unwrap(), isbn_13);
+	}
+
+	#[test]
+	fn parse_rejects_multibyte_characters() {
+		assert_eq!(Isbn13::parse("12345678あ9"), Err(IsbnError::InvalidCharacter('あ')));
+		assert_eq!(Isbn13::parse("978410101001あ"), Err(IsbnError::InvalidCharacter('あ')));
+	}
+
+	#[test]
+	fn parse_rejects_bad_checksum_and_prefix() {
+		assert_eq!(Isbn13::parse("9784101010015"), Err(IsbnError::InvalidChecksum('4')));
+		assert_eq!(Isbn13::parse("9774101010014"), Err(IsbnError::InvalidPrefix));
+		assert_eq!(Isbn13::parse("4101010014"), Err(IsbnError::InvalidChecksum('3')));
+	}
+
+	#[test]
+	fn parse_strips_hyphens_and_spaces() {
+		let expected = Isbn13::parse("9784101010014").unwrap();
+		assert_eq!(Isbn13::parse("978-4-10-101001-4").unwrap(), expected);
+		assert_eq!(Isbn13::parse(" 978 4101 010014 ").unwrap(), expected);
+		assert_eq!(Isbn13::parse("4-10-101001-3").unwrap(), expected);
+	}
+
+	#[test]
+	fn parse_accepts_isbn_10_with_x_check_digit() {
+		let expected = Isbn13::parse("9780804429573").unwrap();
+		assert_eq!(Isbn13::parse("0-8044-2957-X").unwrap(), expected);
+		assert_eq!(Isbn13::parse("080442957x").unwrap(), expected);
+		assert_eq!(expected.to_isbn_10().as_deref(), Some("080442957X"));
+	}
+
+	#[tokio::test]
+	async fn extractor_rejects_before_the_handler_runs() {
+		use axum::{body::Body, http::Request, routing::get, Router};
+		use http_body_util::BodyExt;
+		use std::sync::{
+			atomic::{AtomicBool, Ordering},
+			Arc,
+		};
+		use tower::ServiceExt;
+
+		let called = Arc::new(AtomicBool::new(false));
+		let handler_called = called.clone();
+		let app = Router::new().route(
+			"/book/:isbn_13",
+			get(move |isbn_13: Isbn13| async move {
+				handler_called.store(true, Ordering::SeqCst);
+				isbn_13.to_string()
+			}),
+		);
+
+		let req = Request::get("/book/9784101010015").body(Body::empty()).unwrap();
+		let res = app.oneshot(req).await.unwrap();
+		assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+		let body = res.into_body().collect().await.unwrap().to_bytes();
+		assert_eq!(
+			String::from_utf8(body.to_vec()).unwrap(),
+			"Invalid isbn_13 '9784101010015': ISBN check digit does not match, expected 4"
+		);
+		assert!(!called.load(Ordering::SeqCst));
+	}
+}
@@ -1 +1,2 @@
+pub mod isbn;
 pub mod user;
\ No newline at end of file
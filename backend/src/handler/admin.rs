@@ -0,0 +1,37 @@
+use axum::{
+	extract::{Extension, Json, Query},
+	http::StatusCode,
+	response::IntoResponse,
+};
+use serde::Serialize;
+
+use crate::handler::require_admin;
+use crate::repos::auth::AuthSession;
+use crate::repos::handle_repository_error;
+use crate::repos::metadata_cache::{MetadataCacheRepository, PurgeCache};
+
+#[derive(Serialize, Debug)]
+pub struct PurgeResult {
+	pub deleted: u64,
+}
+
+pub fn create_admin_app<CacheRepos: MetadataCacheRepository>(cache_repos: &CacheRepos) -> axum::Router {
+	axum::Router::new()
+		.route("/metadata_cache", axum::routing::delete(purge_metadata_cache::<CacheRepos>))
+		.layer(Extension(cache_repos.clone()))
+}
+
+// 保存した取得元の応答を消すハンドラ
+async fn purge_metadata_cache<T: MetadataCacheRepository>(
+	auth_session: AuthSession,
+	Query(query): Query<PurgeCache>,
+	Extension(cache_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	require_admin(&auth_session)?;
+	let deleted = cache_repos
+		.purge(&query)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(PurgeResult { deleted })))
+}
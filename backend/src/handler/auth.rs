@@ -1,7 +1,7 @@
 use axum::{
 	body::Body,
 	http::StatusCode,
-	response::IntoResponse,
+	response::{IntoResponse, Redirect},
 	routing::{get, post},
 	Form, Json, Router,
 };
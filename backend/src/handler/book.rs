@@ -1,124 +1,198 @@
 use axum::{
-	extract::{Json, Path, Extension},
+	extract::{DefaultBodyLimit, Json, Extension, Multipart, Query},
 	http::StatusCode,
-	response::IntoResponse,
+	response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 
+use crate::entity::isbn::Isbn13;
+use crate::handler::bulk::register_books;
+use crate::handler::cover::{find_cover, upload_cover};
+use crate::handler::current_user_id;
 use crate::handler::memo::{create_memo, find_all_memo};
-use crate::repos::book::{BookInfo, BookRepository};
-use crate::repos::RepositoryError;
+use crate::handler::tag::{tag_book, untag_book};
+use crate::handler::reading::{
+	add_reading_progress, change_reading_status, find_reading_history, find_reading_progress,
+};
+use crate::provider::{handle_provider_error, LookupMode, SharedProvider};
+use crate::repos::auth::AuthSession;
+use crate::modules::barcode::{decode_ean_13, BarcodeError};
+use crate::modules::cover::delete_cover;
+use crate::modules::job_worker::enqueue_cover;
+use crate::modules::validate_json::ValidatedJson;
+use crate::repos::book::{internal_book_id, BookFilter, BookInfo, BookRepository, BookSort, BookSource, CreateManualBook, UpdateBook};
+use crate::repos::{PageRequest, RepositoryError, SortOrder};
 use crate::repos::memo::MemoRepository;
+use crate::repos::reading::{ReadingRepository, ReadingStatus};
+use crate::repos::job::{Job, JobKind, JobRepository};
+use crate::repos::shelf::ShelfRepository;
+use crate::repos::tag::TagRepository;
+use crate::storage::SharedBlobStore;
+
+// スマートフォンで撮った写真を受け付けられる大きさ
+const SCAN_UPLOAD_LIMIT: usize = 16 * 1024 * 1024;
 
-pub fn create_book_app<BookRepos: BookRepository, MemoRepos: MemoRepository>(book_repos: &BookRepos, memo_repos: &MemoRepos) -> axum::Router {
+#[allow(clippy::too_many_arguments)]
+pub fn create_book_app<BookRepos, MemoRepos, ReadingRepos, TagRepos, ShelfRepos, JobRepos>(
+	book_repos: &BookRepos,
+	memo_repos: &MemoRepos,
+	reading_repos: &ReadingRepos,
+	tag_repos: &TagRepos,
+	shelf_repos: &ShelfRepos,
+	job_repos: &JobRepos,
+	provider: &SharedProvider,
+	blob_store: &SharedBlobStore,
+) -> axum::Router
+where
+	BookRepos: BookRepository,
+	MemoRepos: MemoRepository,
+	ReadingRepos: ReadingRepository,
+	TagRepos: TagRepository,
+	ShelfRepos: ShelfRepository,
+	JobRepos: JobRepository,
+{
 	axum::Router::new()
-		.route("/", axum::routing::get(find_all_book::<BookRepos>).post(create_book::<BookRepos>))
+		.route("/", axum::routing::get(find_all_book::<BookRepos>).post(create_book::<BookRepos, JobRepos>))
+		.route("/manual", axum::routing::post(create_manual_book::<BookRepos>))
+		.route("/bulk", axum::routing::post(register_books::<BookRepos, JobRepos>))
+		.route(
+			"/scan",
+			axum::routing::post(scan_book::<BookRepos, JobRepos>).layer(DefaultBodyLimit::max(SCAN_UPLOAD_LIMIT)),
+		)
 		.nest(
 			"/:isbn_13",
 			axum::Router::new()
 				.nest(
 					"/",
-					axum::Router::new().route("/", axum::routing::get(find_book::<BookRepos>).delete(delete_book::<BookRepos>)),
+					axum::Router::new().route(
+						"/",
+						axum::routing::get(find_book::<BookRepos, ShelfRepos>)
+							.patch(update_book::<BookRepos>)
+							.delete(delete_book::<BookRepos>),
+					),
 				)
 				.nest(
 					"/memo",
 					axum::Router::new().route("/", axum::routing::get(find_all_memo::<MemoRepos>).post(create_memo::<MemoRepos>)),
 				)
+				.route(
+					"/status",
+					axum::routing::get(find_reading_history::<ReadingRepos>).post(change_reading_status::<ReadingRepos>),
+				)
+				.route(
+					"/progress",
+					axum::routing::get(find_reading_progress::<ReadingRepos>).post(add_reading_progress::<ReadingRepos>),
+				)
+				.route("/refresh", axum::routing::post(refresh_book::<BookRepos>))
+				.route(
+					"/cover",
+					axum::routing::get(find_cover::<BookRepos>)
+						.put(upload_cover::<BookRepos>)
+						.layer(DefaultBodyLimit::max(SCAN_UPLOAD_LIMIT)),
+				)
+				.route(
+					"/tag/:tag_id",
+					axum::routing::put(tag_book::<TagRepos>).delete(untag_book::<TagRepos>),
+				)
 		)
 		.layer(Extension(book_repos.clone()))
 		.layer(Extension(memo_repos.clone()))
+		.layer(Extension(reading_repos.clone()))
+		.layer(Extension(tag_repos.clone()))
+		.layer(Extension(shelf_repos.clone()))
+		.layer(Extension(job_repos.clone()))
+		.layer(Extension(provider.clone()))
+		.layer(Extension(blob_store.clone()))
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-#[serde(rename_all = "camelCase")]
-struct Identifier {
-	#[serde(rename = "type")]
-	identifier_type: String,
-	identifier: String,
-}
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-#[serde(rename_all = "camelCase")]
-struct ImageLinks {
-	thumbnail: String,
-}
-
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct VolumeInfoResult {
-	title: String,
-	description: String,
-	authors: Vec<String>,
-	publisher: String,
-	published_date: String,
-	image_links: ImageLinks,
-	industry_identifiers: Vec<Identifier>,
+#[derive(Deserialize)]
+pub struct BookListQuery {
+	status: Option<ReadingStatus>,
+	tag: Option<String>,
+	#[serde(default)]
+	pub sort: BookSort,
+	#[serde(default)]
+	pub order: SortOrder,
+	limit: Option<i64>,
+	cursor: Option<String>,
+	#[serde(default)]
+	with_total: bool,
 }
 
-impl VolumeInfoResult {
-	fn to_book_info(&self) -> BookInfo {
-		BookInfo {
-			isbn_13: self
-				.industry_identifiers
-				.iter()
-				.find(|identifier| identifier.identifier_type == "ISBN_13")
-				.unwrap()
-				.identifier
-				.clone(),
-			title: self.title.clone(),
-			description: self.description.clone(),
-			authors: self.authors.clone(),
-			publisher: self.publisher.clone(),
-			published_date: self.published_date.clone(),
-			image_url: self.image_links.thumbnail.clone(),
+impl BookListQuery {
+	pub fn filter(&self) -> BookFilter {
+		BookFilter {
+			status: self.status,
+			tag: self.tag.clone(),
+			..Default::default()
 		}
 	}
-}
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct BookInfoResult {
-	volume_info: VolumeInfoResult,
+	pub fn page_request(&self) -> PageRequest {
+		PageRequest {
+			limit: self.limit,
+			cursor: self.cursor.clone(),
+			with_total: self.with_total,
+		}
+	}
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct SearchBooksResult {
-	items: Vec<BookInfoResult>,
+#[derive(Deserialize, Validate)]
+struct CreateBook {
+	isbn_13: Isbn13,
+	// trueなら書誌情報の取得を待たずにジョブとして受け付ける
+	#[serde(default)]
+	background: bool,
 }
 
-#[derive(Deserialize)]
-struct CreateBook {
-	isbn_13: String,
+#[derive(Deserialize, Debug, Default)]
+struct RefreshQuery {
+	// trueなら取得元に問い合わせず、保存済みの応答から取り直す
+	#[serde(default)]
+	offline: bool,
 }
 
 fn handle_repository_error(err: RepositoryError) -> StatusCode {
 	match err {
 		RepositoryError::NotFound(_) => StatusCode::NOT_FOUND,
 		RepositoryError::Registered(_) => StatusCode::BAD_REQUEST,
+		RepositoryError::InvalidCursor(_) => StatusCode::BAD_REQUEST,
+		RepositoryError::InvalidTransition(_) => StatusCode::CONFLICT,
+		RepositoryError::InvalidInput(_) => StatusCode::BAD_REQUEST,
 		_ => StatusCode::INTERNAL_SERVER_ERROR,
 	}
 }
 
 // 登録済みの本を全て返すハンドラ
 async fn find_all_book<T: BookRepository>(
+	auth_session: AuthSession,
+	Query(query): Query<BookListQuery>,
 	Extension(book_repos): Extension<T>,
 ) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
 	let book_info_list = book_repos
-		.find_all()
+		.find_all(&user_id, &query.filter(), query.sort, query.order, &query.page_request())
 		.await
-		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+		.map_err(handle_repository_error)?;
 
 	Ok((StatusCode::OK, Json(book_info_list)))
 }
 
 // 本を検索するハンドラ
-async fn find_book<T: BookRepository>(
-	Path(isbn_13): Path<String>,
+async fn find_book<T: BookRepository, U: ShelfRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
 	Extension(book_repos): Extension<T>,
+	Extension(shelf_repos): Extension<U>,
 ) -> Result<impl IntoResponse, StatusCode> {
-	let book_info = book_repos
-		.find(&isbn_13)
+	let user_id = current_user_id(&auth_session)?;
+	let mut book_info = book_repos
+		.find(&user_id, &isbn_13)
+		.await
+		.map_err(handle_repository_error)?;
+	book_info.shelves = shelf_repos
+		.find_membership(&user_id, &isbn_13)
 		.await
 		.map_err(handle_repository_error)?;
 
@@ -126,53 +200,575 @@ async fn find_book<T: BookRepository>(
 }
 
 // 本を登録するハンドラ
-async fn create_book<T: BookRepository>(
+async fn create_book<T: BookRepository, U: JobRepository>(
+	auth_session: AuthSession,
 	Extension(book_repos): Extension<T>,
-	Json(payload): Json<CreateBook>,
-) -> Result<impl IntoResponse, StatusCode> {
-	if book_repos.find(&payload.isbn_13).await.is_ok() {
+	Extension(job_repos): Extension<U>,
+	Extension(provider): Extension<SharedProvider>,
+	ValidatedJson(payload): ValidatedJson<CreateBook>,
+) -> Result<Response, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	if payload.background {
+		let job = enqueue_book(&book_repos, &job_repos, &user_id, &payload.isbn_13).await?;
+		return Ok((StatusCode::ACCEPTED, Json(job)).into_response());
+	}
+	let book_info = register_book(&book_repos, &job_repos, &provider, &user_id, &payload.isbn_13).await?;
+
+	Ok((StatusCode::CREATED, Json(book_info)).into_response())
+}
+
+// 書誌情報の取得をジョブに積む。状態はGET /job/:idで確かめる
+async fn enqueue_book<T: BookRepository, U: JobRepository>(
+	book_repos: &T,
+	job_repos: &U,
+	user_id: &str,
+	isbn_13: &Isbn13,
+) -> Result<Job, StatusCode> {
+	if isbn_13.is_internal() {
+		return Err(StatusCode::BAD_REQUEST);
+	}
+	if book_repos.find(user_id, isbn_13).await.is_ok() {
+		return Err(StatusCode::BAD_REQUEST);
+	}
+
+	job_repos
+		.enqueue(user_id, JobKind::FetchMetadata, isbn_13)
+		.await
+		.map_err(handle_repository_error)
+}
+
+// 書誌情報を取得元から取り寄せて本を登録する。表紙は後からジョブで取り寄せる
+pub async fn register_book<T: BookRepository, U: JobRepository>(
+	book_repos: &T,
+	job_repos: &U,
+	provider: &SharedProvider,
+	user_id: &str,
+	isbn_13: &Isbn13,
+) -> Result<BookInfo, StatusCode> {
+	// 内部の識別子は取得元に問い合わせられない
+	if isbn_13.is_internal() {
 		return Err(StatusCode::BAD_REQUEST);
 	}
-	const URL: &str = "https://www.googleapis.com/books/v1/volumes?q=isbn:";
-	let res = reqwest::get(format!("{}{}", URL, &payload.isbn_13))
+	if book_repos.find(user_id, isbn_13).await.is_ok() {
+		return Err(StatusCode::BAD_REQUEST);
+	}
+	let books = provider
+		.lookup(isbn_13)
 		.await
-		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-		.text()
+		.map_err(handle_provider_error)?;
+
+	let book_info = book_repos
+		.create(user_id, books)
 		.await
-		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+		.map_err(handle_repository_error)?;
+	enqueue_cover(job_repos, user_id, &book_info).await;
+
+	Ok(book_info)
+}
 
-	let search_books_result = serde_json::from_str::<SearchBooksResult>(&res)
-		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+// バーコードの誤りは理由を付けて返す
+pub enum ScanError {
+	Status(StatusCode),
+	Barcode(BarcodeError),
+}
 
-	if search_books_result.items.is_empty() {
-		return Err(StatusCode::NOT_FOUND);
+impl From<StatusCode> for ScanError {
+	fn from(status: StatusCode) -> Self {
+		ScanError::Status(status)
 	}
+}
 
-	// isbnで一意に検索しているためインデックス0で指定
-	let books = search_books_result.items[0].volume_info.to_book_info();
+impl From<BarcodeError> for ScanError {
+	fn from(err: BarcodeError) -> Self {
+		ScanError::Barcode(err)
+	}
+}
 
-	// Googleがisbn不一致でも良しなに変換してくれるが、ここでははじく
-	if payload.isbn_13 != books.isbn_13 {
-		return Err(StatusCode::NOT_FOUND);
+impl IntoResponse for ScanError {
+	fn into_response(self) -> Response {
+		match self {
+			ScanError::Status(status) => status.into_response(),
+			ScanError::Barcode(err) => err.into_response(),
+		}
 	}
+}
+
+#[derive(Serialize)]
+struct ScannedBook {
+	isbn_13: Isbn13,
+	book: BookInfo,
+}
+
+// バーコードの写真から本を登録するハンドラ
+async fn scan_book<T: BookRepository, U: JobRepository>(
+	auth_session: AuthSession,
+	Extension(book_repos): Extension<T>,
+	Extension(job_repos): Extension<U>,
+	Extension(provider): Extension<SharedProvider>,
+	mut multipart: Multipart,
+) -> Result<impl IntoResponse, ScanError> {
+	let user_id = current_user_id(&auth_session)?;
+	let field = multipart
+		.next_field()
+		.await
+		.map_err(|_| StatusCode::BAD_REQUEST)?
+		.ok_or(StatusCode::BAD_REQUEST)?;
+	let bytes = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+
+	// 画像の展開と走査は重いのでブロッキング用のスレッドで行う
+	let codes = tokio::task::spawn_blocking(move || decode_ean_13(&bytes))
+		.await
+		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+	// 日本の書籍は価格などの2段目のバーコードも写るので、ISBNとして読めるものを選ぶ
+	let isbn_13 = codes
+		.iter()
+		.filter_map(|code| Isbn13::parse(code).ok())
+		.find(|isbn_13| !isbn_13.is_internal())
+		.ok_or_else(|| BarcodeError::NotIsbn(codes.join(", ")))?;
+
+	let book_info = register_book(&book_repos, &job_repos, &provider, &user_id, &isbn_13).await?;
+
+	Ok((
+		StatusCode::CREATED,
+		Json(ScannedBook {
+			isbn_13,
+			book: book_info,
+		}),
+	))
+}
+
+// 書誌情報を手入力して本を登録するハンドラ
+async fn create_manual_book<T: BookRepository>(
+	auth_session: AuthSession,
+	Extension(book_repos): Extension<T>,
+	ValidatedJson(payload): ValidatedJson<CreateManualBook>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let mut payload = BookInfo::from(payload);
+	payload.isbn_13 = match payload.isbn_13.is_empty() {
+		true => internal_book_id(),
+		false => Isbn13::parse(&payload.isbn_13)
+			.map_err(|_| StatusCode::BAD_REQUEST)?
+			.into(),
+	};
 
 	let book_info = book_repos
-		.create(books)
+		.create(&user_id, payload)
 		.await
 		.map_err(handle_repository_error)?;
 
 	Ok((StatusCode::CREATED, Json(book_info)))
 }
 
+// 書誌情報の項目を書き換えるハンドラ
+async fn update_book<T: BookRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Extension(book_repos): Extension<T>,
+	ValidatedJson(payload): ValidatedJson<UpdateBook>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let book_info = book_repos
+		.update(&user_id, &isbn_13, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(book_info)))
+}
+
+// 書誌情報を取得し直し、書き換えていない項目だけを更新するハンドラ
+async fn refresh_book<T: BookRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Query(query): Query<RefreshQuery>,
+	Extension(book_repos): Extension<T>,
+	Extension(provider): Extension<SharedProvider>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let book_info = book_repos
+		.find(&user_id, &isbn_13)
+		.await
+		.map_err(handle_repository_error)?;
+	// 手入力した本には取得元がない
+	if book_info.source == BookSource::Manual {
+		return Err(StatusCode::BAD_REQUEST);
+	}
+	let mode = match query.offline {
+		true => LookupMode::Offline,
+		false => LookupMode::Refresh,
+	};
+	let fetched = provider
+		.lookup_with(&isbn_13, mode)
+		.await
+		.map_err(handle_provider_error)?;
+
+	let result = book_repos
+		.refresh(&user_id, &isbn_13, fetched)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(result)))
+}
+
 // 本を削除するハンドラ
 async fn delete_book<T: BookRepository>(
-	Path(isbn_13): Path<String>,
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
 	Extension(book_repos): Extension<T>,
+	Extension(blob_store): Extension<SharedBlobStore>,
 ) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
 	book_repos
-		.delete(&isbn_13)
+		.delete(&user_id, &isbn_13)
 		.await
 		.map_err(handle_repository_error)?;
+	delete_cover(&blob_store, &user_id, &isbn_13).await;
 
 	Ok((StatusCode::OK, ()))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::entity::user::User;
+	use crate::provider::fixture::FixtureProvider;
+	use crate::provider::{BookMetadataProvider, ProviderError};
+	use crate::repos::auth::AuthRepositoryForPg;
+	use crate::repos::book::RefreshResult;
+	use crate::repos::job::JobStatus;
+	use crate::repos::Page;
+	use axum::{
+		async_trait,
+		body::Body,
+		extract::Request,
+		http::{header, Method},
+		middleware::{self, Next},
+	};
+	use axum_login::AuthManagerLayerBuilder;
+	use chrono::{DateTime, Utc};
+	use http_body_util::BodyExt;
+	use sqlx::postgres::PgPoolOptions;
+	use std::sync::{Arc, Mutex};
+	use tower::ServiceExt;
+	use tower_sessions::{MemoryStore, SessionManagerLayer};
+
+	const USER_ID: &str = "00000000-0000-0000-0000-000000000001";
+	const ISBN_13: &str = "9784101010014";
+
+	// テストで使わない操作はpanicさせずにエラーにする
+	fn unsupported<T>() -> Result<T, RepositoryError> {
+		Err(RepositoryError::Unexpected("not supported by the test repository".to_string()))
+	}
+
+	// 登録した本を手元に持つだけのリポジトリ
+	#[derive(Clone, Default)]
+	struct MemoryBookRepository {
+		books: Arc<Mutex<Vec<(String, BookInfo)>>>,
+	}
+
+	impl MemoryBookRepository {
+		fn modify(&self, user_id: &str, isbn_13: &str, f: impl FnOnce(&mut BookInfo)) -> Result<(), RepositoryError> {
+			let mut books = self.books.lock().unwrap();
+			let (_, book_info) = books
+				.iter_mut()
+				.find(|(owner, book_info)| owner == user_id && book_info.isbn_13 == isbn_13)
+				.ok_or(RepositoryError::NotFound(isbn_13.to_string()))?;
+			f(book_info);
+			Ok(())
+		}
+	}
+
+	#[async_trait]
+	impl BookRepository for MemoryBookRepository {
+		async fn find(&self, user_id: &str, isbn_13: &str) -> Result<BookInfo, RepositoryError> {
+			self
+				.books
+				.lock()
+				.unwrap()
+				.iter()
+				.find(|(owner, book_info)| owner == user_id && book_info.isbn_13 == isbn_13)
+				.map(|(_, book_info)| book_info.clone())
+				.ok_or(RepositoryError::NotFound(isbn_13.to_string()))
+		}
+
+		// 絞り込みと並び替えはせず、登録順に1ページで返す
+		async fn find_all(
+			&self,
+			user_id: &str,
+			_filter: &BookFilter,
+			_sort: BookSort,
+			_order: SortOrder,
+			page: &PageRequest,
+		) -> Result<Page<BookInfo>, RepositoryError> {
+			let items: Vec<BookInfo> = self
+				.books
+				.lock()
+				.unwrap()
+				.iter()
+				.filter(|(owner, _)| owner == user_id)
+				.map(|(_, book_info)| book_info.clone())
+				.collect();
+			let total = page.with_total.then_some(items.len() as i64);
+			Ok(Page {
+				items,
+				next_cursor: None,
+				total,
+			})
+		}
+
+		async fn create(&self, user_id: &str, payload: BookInfo) -> Result<BookInfo, RepositoryError> {
+			let book_info = BookInfo {
+				created_at: Some(Utc::now()),
+				updated_at: Some(Utc::now()),
+				..payload
+			};
+			self
+				.books
+				.lock()
+				.unwrap()
+				.push((user_id.to_string(), book_info.clone()));
+			Ok(book_info)
+		}
+
+		async fn update(
+			&self,
+			_user_id: &str,
+			_isbn_13: &str,
+			_payload: UpdateBook,
+		) -> Result<BookInfo, RepositoryError> {
+			unsupported()
+		}
+
+		async fn refresh(
+			&self,
+			_user_id: &str,
+			_isbn_13: &str,
+			_fetched: BookInfo,
+		) -> Result<RefreshResult, RepositoryError> {
+			unsupported()
+		}
+
+		async fn delete(&self, user_id: &str, isbn_13: &str) -> Result<(), RepositoryError> {
+			let mut books = self.books.lock().unwrap();
+			let count = books.len();
+			books.retain(|(owner, book_info)| !(owner == user_id && book_info.isbn_13 == isbn_13));
+			match books.len() < count {
+				true => Ok(()),
+				false => Err(RepositoryError::NotFound(isbn_13.to_string())),
+			}
+		}
+
+		async fn update_cover(
+			&self,
+			user_id: &str,
+			isbn_13: &str,
+			cover_updated_at: Option<DateTime<Utc>>,
+		) -> Result<(), RepositoryError> {
+			self.modify(user_id, isbn_13, |book_info| book_info.cover_updated_at = cover_updated_at)
+		}
+
+		async fn update_rating(&self, user_id: &str, isbn_13: &str, rating: Option<i16>) -> Result<(), RepositoryError> {
+			self.modify(user_id, isbn_13, |book_info| book_info.rating = rating)
+		}
+	}
+
+	// 積まれたジョブを記録するだけのリポジトリ
+	#[derive(Clone, Default)]
+	struct MemoryJobRepository {
+		jobs: Arc<Mutex<Vec<Job>>>,
+	}
+
+	impl MemoryJobRepository {
+		fn modify(&self, id: &str, f: impl FnOnce(&mut Job)) -> Result<(), RepositoryError> {
+			let mut jobs = self.jobs.lock().unwrap();
+			let job = jobs
+				.iter_mut()
+				.find(|job| job.id == id)
+				.ok_or(RepositoryError::NotFound(id.to_string()))?;
+			f(job);
+			job.updated_at = Utc::now();
+			Ok(())
+		}
+	}
+
+	#[async_trait]
+	impl JobRepository for MemoryJobRepository {
+		async fn find(&self, user_id: &str, id: &str) -> Result<Job, RepositoryError> {
+			self
+				.jobs
+				.lock()
+				.unwrap()
+				.iter()
+				.find(|job| job.user_id == user_id && job.id == id)
+				.cloned()
+				.ok_or(RepositoryError::NotFound(id.to_string()))
+		}
+
+		async fn enqueue(&self, user_id: &str, kind: JobKind, isbn_13: &str) -> Result<Job, RepositoryError> {
+			let job = Job {
+				id: uuid::Uuid::new_v4().to_string(),
+				user_id: user_id.to_string(),
+				kind,
+				isbn_13: isbn_13.to_string(),
+				status: JobStatus::Pending,
+				attempts: 0,
+				max_attempts: 5,
+				run_at: Utc::now(),
+				last_error: None,
+				created_at: Utc::now(),
+				updated_at: Utc::now(),
+			};
+			self.jobs.lock().unwrap().push(job.clone());
+			Ok(job)
+		}
+
+		async fn claim(&self) -> Result<Option<Job>, RepositoryError> {
+			let mut jobs = self.jobs.lock().unwrap();
+			let job = jobs
+				.iter_mut()
+				.find(|job| job.status == JobStatus::Pending && job.run_at <= Utc::now());
+			Ok(job.map(|job| {
+				job.status = JobStatus::Running;
+				job.attempts += 1;
+				job.clone()
+			}))
+		}
+
+		async fn succeed(&self, id: &str) -> Result<(), RepositoryError> {
+			self.modify(id, |job| job.status = JobStatus::Succeeded)
+		}
+
+		async fn retry(&self, id: &str, error: &str, run_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+			self.modify(id, |job| {
+				job.status = JobStatus::Pending;
+				job.last_error = Some(error.to_string());
+				job.run_at = run_at;
+			})
+		}
+
+		async fn fail(&self, id: &str, error: &str) -> Result<(), RepositoryError> {
+			self.modify(id, |job| {
+				job.status = JobStatus::Failed;
+				job.last_error = Some(error.to_string());
+			})
+		}
+	}
+
+	// 問い合わせると必ず失敗する取得元
+	struct FailingProvider;
+
+	#[async_trait]
+	impl BookMetadataProvider for FailingProvider {
+		async fn lookup_with(&self, _isbn_13: &str, _mode: LookupMode) -> Result<BookInfo, ProviderError> {
+			Err(ProviderError::Unexpected("service unavailable".to_string()))
+		}
+	}
+
+	fn kokoro() -> BookInfo {
+		BookInfo {
+			isbn_13: ISBN_13.to_string(),
+			title: "こころ".to_string(),
+			authors: vec!["夏目漱石".to_string()],
+			publisher: "新潮社".to_string(),
+			published_date: "2004-03".to_string(),
+			image_url: "https://cover.openbd.jp/9784101010014.jpg".to_string(),
+			page_count: Some(326),
+			..Default::default()
+		}
+	}
+
+	// ログイン済みのセッションを持たせてPOST /bookだけを受け付けるアプリ
+	fn app(book_repos: &MemoryBookRepository, job_repos: &MemoryJobRepository, provider: SharedProvider) -> axum::Router {
+		// ログインの確認ではDBに問い合わせないので、接続しないプールで足りる
+		let pool = PgPoolOptions::new()
+			.connect_lazy("postgres://localhost/unused")
+			.unwrap();
+		let session_layer = SessionManagerLayer::new(MemoryStore::default());
+		let auth_layer = AuthManagerLayerBuilder::new(AuthRepositoryForPg::new(pool), session_layer).build();
+		let login = |mut auth_session: AuthSession, mut req: Request, next: Next| async move {
+			let user = User {
+				id: USER_ID.to_string(),
+				email: "reader@example.com".to_string(),
+				password: String::new(),
+			};
+			auth_session.login(&user).await.unwrap();
+			req.extensions_mut().insert(auth_session);
+			next.run(req).await
+		};
+
+		axum::Router::new()
+			.route(
+				"/",
+				axum::routing::post(create_book::<MemoryBookRepository, MemoryJobRepository>),
+			)
+			.layer(Extension(book_repos.clone()))
+			.layer(Extension(job_repos.clone()))
+			.layer(Extension(provider))
+			.layer(middleware::from_fn(login))
+			.layer(auth_layer)
+	}
+
+	async fn post_book(app: axum::Router, isbn_13: &str) -> (StatusCode, serde_json::Value) {
+		let req = Request::builder()
+			.method(Method::POST)
+			.uri("/")
+			.header(header::CONTENT_TYPE, "application/json")
+			.body(Body::from(serde_json::json!({ "isbn_13": isbn_13 }).to_string()))
+			.unwrap();
+		let res = app.oneshot(req).await.unwrap();
+		let status = res.status();
+		let body = res.into_body().collect().await.unwrap().to_bytes();
+		(status, serde_json::from_slice(&body).unwrap_or_default())
+	}
+
+	#[tokio::test]
+	async fn create_book_registers_the_fetched_book() {
+		let book_repos = MemoryBookRepository::default();
+		let job_repos = MemoryJobRepository::default();
+		let provider: SharedProvider = Arc::new(FixtureProvider::new(vec![kokoro()]));
+
+		let (status, body) = post_book(app(&book_repos, &job_repos, provider), ISBN_13).await;
+		assert_eq!(status, StatusCode::CREATED);
+		assert_eq!(body["isbn_13"], ISBN_13);
+		assert_eq!(body["title"], "こころ");
+		assert_eq!(book_repos.find(USER_ID, ISBN_13).await.unwrap().title, "こころ");
+		// 表紙は後からジョブで取り寄せる
+		let jobs = job_repos.jobs.lock().unwrap();
+		assert_eq!(jobs.len(), 1);
+		assert_eq!(jobs[0].kind, JobKind::FetchCover);
+	}
+
+	#[tokio::test]
+	async fn create_book_rejects_a_registered_book() {
+		let book_repos = MemoryBookRepository::default();
+		let job_repos = MemoryJobRepository::default();
+		book_repos.create(USER_ID, kokoro()).await.unwrap();
+		let provider: SharedProvider = Arc::new(FixtureProvider::new(vec![kokoro()]));
+
+		let (status, _) = post_book(app(&book_repos, &job_repos, provider), ISBN_13).await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+		assert_eq!(book_repos.books.lock().unwrap().len(), 1);
+		assert!(job_repos.jobs.lock().unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn create_book_returns_not_found_for_unknown_isbn() {
+		let book_repos = MemoryBookRepository::default();
+		let job_repos = MemoryJobRepository::default();
+		let provider: SharedProvider = Arc::new(FixtureProvider::new(vec![kokoro()]));
+
+		let (status, _) = post_book(app(&book_repos, &job_repos, provider), "9784003101018").await;
+		assert_eq!(status, StatusCode::NOT_FOUND);
+		assert!(book_repos.books.lock().unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn create_book_reports_provider_errors() {
+		let book_repos = MemoryBookRepository::default();
+		let job_repos = MemoryJobRepository::default();
+
+		let (status, _) = post_book(app(&book_repos, &job_repos, Arc::new(FailingProvider)), ISBN_13).await;
+		assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+		assert!(book_repos.books.lock().unwrap().is_empty());
+	}
+}
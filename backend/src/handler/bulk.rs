@@ -0,0 +1,159 @@
+use axum::{
+	extract::{Extension, Json},
+	http::StatusCode,
+	response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, sync::Arc};
+use tokio::sync::Semaphore;
+use validator::Validate;
+
+use crate::entity::isbn::Isbn13;
+use crate::handler::current_user_id;
+use crate::modules::job_worker::enqueue_cover;
+use crate::modules::validate_json::ValidatedJson;
+use crate::provider::{ProviderError, SharedProvider};
+use crate::repos::auth::AuthSession;
+use crate::repos::book::{BookInfo, BookRepository};
+use crate::repos::job::JobRepository;
+use crate::repos::RepositoryError;
+
+// 取得元へ同時に問い合わせる数
+pub const BULK_CONCURRENCY: usize = 4;
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct BulkRegister {
+	#[validate(length(min = 1, max = 100))]
+	pub isbn_13: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BulkOutcome {
+	Created { book: Box<BookInfo> },
+	AlreadyRegistered,
+	// 同じリクエスト内で前に出てきたISBN
+	Duplicate,
+	NotFound,
+	Invalid { message: String },
+	ProviderError { message: String },
+	Failed { message: String },
+}
+
+#[derive(Serialize, Debug)]
+pub struct BulkResult {
+	pub input: String,
+	pub isbn_13: Option<Isbn13>,
+	#[serde(flatten)]
+	pub outcome: BulkOutcome,
+}
+
+// 1冊分の登録。POST /bookと同じ流れで、結果を状態コードではなく項目ごとに返す
+pub async fn register_one<T: BookRepository, U: JobRepository>(
+	book_repos: &T,
+	job_repos: &U,
+	provider: &SharedProvider,
+	user_id: &str,
+	isbn_13: &Isbn13,
+) -> BulkOutcome {
+	if book_repos.find(user_id, isbn_13).await.is_ok() {
+		return BulkOutcome::AlreadyRegistered;
+	}
+	let books = match provider.lookup(isbn_13).await {
+		Ok(books) => books,
+		Err(ProviderError::NotFound(_)) => return BulkOutcome::NotFound,
+		Err(err) => {
+			return BulkOutcome::ProviderError {
+				message: err.to_string(),
+			}
+		}
+	};
+
+	match book_repos.create(user_id, books).await {
+		Ok(book) => {
+			enqueue_cover(job_repos, user_id, &book).await;
+			BulkOutcome::Created { book: Box::new(book) }
+		}
+		Err(RepositoryError::Registered(_)) => BulkOutcome::AlreadyRegistered,
+		Err(err) => BulkOutcome::Failed {
+			message: err.to_string(),
+		},
+	}
+}
+
+// 複数のISBNをまとめて登録するハンドラ
+pub async fn register_books<T: BookRepository, U: JobRepository>(
+	auth_session: AuthSession,
+	Extension(book_repos): Extension<T>,
+	Extension(job_repos): Extension<U>,
+	Extension(provider): Extension<SharedProvider>,
+	ValidatedJson(payload): ValidatedJson<BulkRegister>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+
+	let mut results: Vec<Option<BulkResult>> = Vec::new();
+	let mut seen = HashSet::new();
+	let mut tasks = Vec::new();
+	let semaphore = Arc::new(Semaphore::new(BULK_CONCURRENCY));
+	for (index, input) in payload.isbn_13.into_iter().enumerate() {
+		let isbn_13 = match Isbn13::parse(&input) {
+			Ok(isbn_13) if !isbn_13.is_internal() => isbn_13,
+			Ok(_) => {
+				results.push(Some(BulkResult {
+					input,
+					isbn_13: None,
+					outcome: BulkOutcome::Invalid {
+						message: "internal identifiers cannot be looked up".to_string(),
+					},
+				}));
+				continue;
+			}
+			Err(err) => {
+				results.push(Some(BulkResult {
+					input,
+					isbn_13: None,
+					outcome: BulkOutcome::Invalid {
+						message: err.to_string(),
+					},
+				}));
+				continue;
+			}
+		};
+		// 同じリクエスト内で重複したISBNは最初の1件だけ問い合わせる
+		if !seen.insert(isbn_13.clone()) {
+			results.push(Some(BulkResult {
+				input,
+				isbn_13: Some(isbn_13),
+				outcome: BulkOutcome::Duplicate,
+			}));
+			continue;
+		}
+
+		results.push(None);
+		let book_repos = book_repos.clone();
+		let job_repos = job_repos.clone();
+		let provider = provider.clone();
+		let user_id = user_id.clone();
+		let semaphore = semaphore.clone();
+		let task_isbn_13 = isbn_13.clone();
+		let task = tokio::spawn(async move {
+			let _permit = semaphore.acquire_owned().await;
+			register_one(&book_repos, &job_repos, &provider, &user_id, &task_isbn_13).await
+		});
+		tasks.push((index, input, isbn_13, task));
+	}
+
+	// 1冊の処理がpanicしても、その本だけを失敗として返す
+	for (index, input, isbn_13, task) in tasks {
+		let outcome = task.await.unwrap_or_else(|err| BulkOutcome::Failed {
+			message: err.to_string(),
+		});
+		results[index] = Some(BulkResult {
+			input,
+			isbn_13: Some(isbn_13),
+			outcome,
+		});
+	}
+
+	Ok((StatusCode::OK, Json(results.into_iter().flatten().collect::<Vec<_>>())))
+}
@@ -0,0 +1,138 @@
+use axum::{
+	extract::{Extension, Json, Multipart, Query},
+	http::{header, HeaderMap, StatusCode},
+	response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::entity::isbn::Isbn13;
+use crate::handler::current_user_id;
+use crate::modules::cover::{cover_key, save_cover, CoverError, CoverSize};
+use crate::repos::auth::AuthSession;
+use crate::repos::book::BookRepository;
+use crate::repos::handle_repository_error;
+use crate::storage::{SharedBlobStore, StorageError};
+
+// 表紙は差し替えるとETagが変わるので、ブラウザには長めに持たせる
+const COVER_CACHE_CONTROL: &str = "private, max-age=86400";
+
+#[derive(Deserialize, Debug, Default)]
+pub struct CoverQuery {
+	#[serde(default)]
+	pub size: CoverSize,
+}
+
+// 表紙の誤りは理由を付けて返す
+pub enum UploadError {
+	Status(StatusCode),
+	Cover(CoverError),
+}
+
+impl From<StatusCode> for UploadError {
+	fn from(status: StatusCode) -> Self {
+		UploadError::Status(status)
+	}
+}
+
+impl From<CoverError> for UploadError {
+	fn from(err: CoverError) -> Self {
+		UploadError::Cover(err)
+	}
+}
+
+impl IntoResponse for UploadError {
+	fn into_response(self) -> Response {
+		match self {
+			UploadError::Status(status) => status.into_response(),
+			UploadError::Cover(err) => err.into_response(),
+		}
+	}
+}
+
+// 手元に保存した表紙を返すハンドラ
+// まだ保存していない本は取得元の画像へ転送する
+pub async fn find_cover<T: BookRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Query(query): Query<CoverQuery>,
+	headers: HeaderMap,
+	Extension(book_repos): Extension<T>,
+	Extension(blob_store): Extension<SharedBlobStore>,
+) -> Result<Response, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let book_info = book_repos
+		.find(&user_id, &isbn_13)
+		.await
+		.map_err(handle_repository_error)?;
+
+	let updated_at = match book_info.cover_updated_at {
+		Some(updated_at) => updated_at,
+		None if !book_info.image_url.is_empty() => {
+			return Ok((StatusCode::TEMPORARY_REDIRECT, [(header::LOCATION, book_info.image_url)]).into_response());
+		}
+		None => return Err(StatusCode::NOT_FOUND),
+	};
+
+	let etag = format!("\"{}-{}\"", updated_at.timestamp_millis(), query.size.name());
+	let last_modified = updated_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+	let not_modified = headers
+		.get(header::IF_NONE_MATCH)
+		.and_then(|value| value.to_str().ok())
+		.is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+	if not_modified {
+		return Ok((
+			StatusCode::NOT_MODIFIED,
+			[(header::ETAG, etag), (header::CACHE_CONTROL, COVER_CACHE_CONTROL.to_string())],
+		)
+			.into_response());
+	}
+
+	let bytes = blob_store
+		.get(&cover_key(&user_id, &isbn_13, query.size))
+		.await
+		.map_err(|err| match err {
+			StorageError::NotFound(_) => StatusCode::NOT_FOUND,
+			_ => StatusCode::INTERNAL_SERVER_ERROR,
+		})?;
+
+	Ok((
+		StatusCode::OK,
+		[
+			(header::CONTENT_TYPE, "image/jpeg".to_string()),
+			(header::ETAG, etag),
+			(header::LAST_MODIFIED, last_modified),
+			(header::CACHE_CONTROL, COVER_CACHE_CONTROL.to_string()),
+		],
+		bytes,
+	)
+		.into_response())
+}
+
+// 表紙を差し替えるハンドラ
+pub async fn upload_cover<T: BookRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Extension(book_repos): Extension<T>,
+	Extension(blob_store): Extension<SharedBlobStore>,
+	mut multipart: Multipart,
+) -> Result<impl IntoResponse, UploadError> {
+	let user_id = current_user_id(&auth_session)?;
+	book_repos
+		.find(&user_id, &isbn_13)
+		.await
+		.map_err(handle_repository_error)?;
+	let field = multipart
+		.next_field()
+		.await
+		.map_err(|_| StatusCode::BAD_REQUEST)?
+		.ok_or(StatusCode::BAD_REQUEST)?;
+	let bytes = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+
+	save_cover(&book_repos, &blob_store, &user_id, &isbn_13, bytes.to_vec()).await?;
+	let book_info = book_repos
+		.find(&user_id, &isbn_13)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(book_info)))
+}
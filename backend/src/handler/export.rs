@@ -0,0 +1,148 @@
+use axum::{
+	extract::Extension,
+	http::{header, StatusCode},
+	response::IntoResponse,
+};
+
+use crate::handler::current_user_id;
+use crate::modules::csv_export::write_goodreads;
+use crate::modules::markdown_export::write_markdown_zip;
+use crate::repos::auth::AuthSession;
+use crate::repos::book::{BookFilter, BookInfo, BookRepository, BookSort};
+use crate::repos::handle_repository_error;
+use crate::repos::memo::{Memo, MemoFilter, MemoRepository, MemoSort};
+use crate::repos::{PageRequest, RepositoryError, SortOrder};
+
+// 書き出しでは1ページずつ全件を読むので、一覧の上限まで取る
+const EXPORT_PAGE_LIMIT: i64 = 200;
+
+pub fn create_export_app<BookRepos, MemoRepos>(book_repos: &BookRepos, memo_repos: &MemoRepos) -> axum::Router
+where
+	BookRepos: BookRepository,
+	MemoRepos: MemoRepository,
+{
+	axum::Router::new()
+		.route("/goodreads", axum::routing::get(export_goodreads::<BookRepos, MemoRepos>))
+		.route("/markdown", axum::routing::get(export_markdown::<BookRepos, MemoRepos>))
+		.layer(Extension(book_repos.clone()))
+		.layer(Extension(memo_repos.clone()))
+}
+
+// 蔵書を登録順に全て読む
+pub async fn load_books<B: BookRepository>(book_repos: &B, user_id: &str) -> Result<Vec<BookInfo>, RepositoryError> {
+	let mut books = Vec::new();
+	let mut cursor = None;
+	loop {
+		let page = PageRequest {
+			limit: Some(EXPORT_PAGE_LIMIT),
+			cursor,
+			with_total: false,
+		};
+		let page = book_repos
+			.find_all(user_id, &BookFilter::default(), BookSort::Added, SortOrder::Asc, &page)
+			.await?;
+		books.extend(page.items);
+		cursor = page.next_cursor;
+		if cursor.is_none() {
+			break;
+		}
+	}
+
+	Ok(books)
+}
+
+// 蔵書を登録順に、それぞれのメモを書いた順に全て読む
+pub async fn load_library<B: BookRepository, M: MemoRepository>(
+	book_repos: &B,
+	memo_repos: &M,
+	user_id: &str,
+) -> Result<Vec<(BookInfo, Vec<Memo>)>, RepositoryError> {
+	let books = load_books(book_repos, user_id).await?;
+	let mut library = Vec::with_capacity(books.len());
+	for book_info in books {
+		let mut memos = Vec::new();
+		let mut cursor = None;
+		loop {
+			let page = PageRequest {
+				limit: Some(EXPORT_PAGE_LIMIT),
+				cursor,
+				with_total: false,
+			};
+			let page = memo_repos
+				.find_all(
+					user_id,
+					&book_info.isbn_13,
+					&MemoFilter::default(),
+					MemoSort::Added,
+					SortOrder::Asc,
+					&page,
+				)
+				.await?;
+			memos.extend(page.items);
+			cursor = page.next_cursor;
+			if cursor.is_none() {
+				break;
+			}
+		}
+		library.push((book_info, memos));
+	}
+
+	Ok(library)
+}
+
+// Goodreadsに取り込める形式のCSVで蔵書を書き出すハンドラ
+pub async fn export_goodreads<B: BookRepository, M: MemoRepository>(
+	auth_session: AuthSession,
+	Extension(book_repos): Extension<B>,
+	Extension(memo_repos): Extension<M>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let library = load_library(&book_repos, &memo_repos, &user_id)
+		.await
+		.map_err(handle_repository_error)?;
+	let csv = write_goodreads(&library).map_err(|err| {
+		println!("{}", err);
+		StatusCode::INTERNAL_SERVER_ERROR
+	})?;
+
+	Ok((
+		StatusCode::OK,
+		[
+			(header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+			(
+				header::CONTENT_DISPOSITION,
+				"attachment; filename=\"goodreads_library_export.csv\"",
+			),
+		],
+		csv,
+	))
+}
+
+// 1冊ごとのMarkdownをzipにまとめて書き出すハンドラ。ObsidianのVaultにそのまま展開できる
+pub async fn export_markdown<B: BookRepository, M: MemoRepository>(
+	auth_session: AuthSession,
+	Extension(book_repos): Extension<B>,
+	Extension(memo_repos): Extension<M>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let library = load_library(&book_repos, &memo_repos, &user_id)
+		.await
+		.map_err(handle_repository_error)?;
+	// 圧縮はブロッキングするので専用のスレッドで行う
+	let zip = tokio::task::spawn_blocking(move || write_markdown_zip(&library))
+		.await
+		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+		.map_err(|err| {
+			println!("{}", err);
+			StatusCode::INTERNAL_SERVER_ERROR
+		})?;
+
+	Ok((
+		StatusCode::OK,
+		[
+			(header::CONTENT_TYPE, "application/zip"),
+			(header::CONTENT_DISPOSITION, "attachment; filename=\"books_markdown.zip\""),
+		],
+		zip,
+	))
+}
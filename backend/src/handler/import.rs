@@ -0,0 +1,407 @@
+use axum::{
+	extract::{DefaultBodyLimit, Extension, Json, Multipart, Query},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, HashSet},
+	sync::Arc,
+};
+use tokio::sync::Semaphore;
+use validator::Validate;
+
+use crate::entity::isbn::Isbn13;
+use crate::handler::bulk::{register_one, BulkOutcome, BULK_CONCURRENCY};
+use crate::handler::current_user_id;
+use crate::handler::kindle::import_kindle;
+use crate::handler::kobo::import_kobo;
+use crate::modules::csv_import::{parse_reading_log, ImportError, ImportFormat, ImportRow, TextEncoding};
+use crate::modules::kobo::KoboError;
+use crate::provider::SharedProvider;
+use crate::repos::auth::AuthSession;
+use crate::repos::book::BookRepository;
+use crate::repos::job::JobRepository;
+use crate::repos::memo::{CreateMemo, MemoRepository};
+use crate::repos::reading::{ChangeStatus, ReadingRepository, ReadingStatus};
+use crate::repos::tag::{TagName, TagRepository};
+use crate::repos::RepositoryError;
+
+// 何年分もの記録を書き出したCSVも受け付けられる大きさ
+const IMPORT_UPLOAD_LIMIT: usize = 8 * 1024 * 1024;
+// Koboのデータベースは本の目次なども含むので大きい
+const KOBO_UPLOAD_LIMIT: usize = 128 * 1024 * 1024;
+
+pub fn create_import_app<BookRepos, MemoRepos, ReadingRepos, TagRepos, JobRepos>(
+	book_repos: &BookRepos,
+	memo_repos: &MemoRepos,
+	reading_repos: &ReadingRepos,
+	tag_repos: &TagRepos,
+	job_repos: &JobRepos,
+	provider: &SharedProvider,
+) -> axum::Router
+where
+	BookRepos: BookRepository,
+	MemoRepos: MemoRepository,
+	ReadingRepos: ReadingRepository,
+	TagRepos: TagRepository,
+	JobRepos: JobRepository,
+{
+	axum::Router::new()
+		.route(
+			"/csv",
+			axum::routing::post(import_csv::<BookRepos, MemoRepos, ReadingRepos, TagRepos, JobRepos>)
+				.layer(DefaultBodyLimit::max(IMPORT_UPLOAD_LIMIT)),
+		)
+		.route(
+			"/kindle",
+			axum::routing::post(import_kindle::<BookRepos, MemoRepos>)
+				.layer(DefaultBodyLimit::max(IMPORT_UPLOAD_LIMIT)),
+		)
+		.route(
+			"/kobo",
+			axum::routing::post(import_kobo::<BookRepos, MemoRepos>)
+				.layer(DefaultBodyLimit::max(KOBO_UPLOAD_LIMIT)),
+		)
+		.layer(Extension(book_repos.clone()))
+		.layer(Extension(memo_repos.clone()))
+		.layer(Extension(reading_repos.clone()))
+		.layer(Extension(tag_repos.clone()))
+		.layer(Extension(job_repos.clone()))
+		.layer(Extension(provider.clone()))
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct ImportQuery {
+	// 省略した場合は中身から判別する
+	pub format: Option<ImportFormat>,
+	// trueのときだけ登録する。省略した場合は登録せずに結果の見込みだけを返す
+	#[serde(default)]
+	pub commit: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum ImportOutcome {
+	WillCreate,
+	Created,
+	AlreadyRegistered,
+	// 同じファイルの前の行と同じ本
+	Duplicate,
+	NotFound,
+	Invalid { message: String },
+	ProviderError { message: String },
+	Failed { message: String },
+}
+
+impl ImportOutcome {
+	fn name(&self) -> &'static str {
+		match self {
+			ImportOutcome::WillCreate => "will_create",
+			ImportOutcome::Created => "created",
+			ImportOutcome::AlreadyRegistered => "already_registered",
+			ImportOutcome::Duplicate => "duplicate",
+			ImportOutcome::NotFound => "not_found",
+			ImportOutcome::Invalid { .. } => "invalid",
+			ImportOutcome::ProviderError { .. } => "provider_error",
+			ImportOutcome::Failed { .. } => "failed",
+		}
+	}
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportRowResult {
+	pub line: u64,
+	pub isbn_13: Option<Isbn13>,
+	pub title: String,
+	pub status: Option<ReadingStatus>,
+	pub memos: usize,
+	pub tags: Vec<String>,
+	#[serde(flatten)]
+	pub outcome: ImportOutcome,
+	// 読み取れなかった値や、本は登録できたが読書状況・メモ・タグを反映できなかったもの
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportReport {
+	pub format: ImportFormat,
+	pub encoding: TextEncoding,
+	pub committed: bool,
+	pub summary: BTreeMap<&'static str, usize>,
+	pub rows: Vec<ImportRowResult>,
+}
+
+impl ImportReport {
+	fn new(format: ImportFormat, encoding: TextEncoding, committed: bool, rows: Vec<ImportRowResult>) -> Self {
+		let mut summary = BTreeMap::new();
+		for row in &rows {
+			*summary.entry(row.outcome.name()).or_insert(0) += 1;
+		}
+		ImportReport {
+			format,
+			encoding,
+			committed,
+			summary,
+			rows,
+		}
+	}
+}
+
+// 取り込むファイルの誤りは理由を付けて返す
+pub enum ImportRejection {
+	Status(StatusCode),
+	Import(ImportError),
+	Kobo(KoboError),
+}
+
+impl From<StatusCode> for ImportRejection {
+	fn from(status: StatusCode) -> Self {
+		ImportRejection::Status(status)
+	}
+}
+
+impl From<ImportError> for ImportRejection {
+	fn from(err: ImportError) -> Self {
+		ImportRejection::Import(err)
+	}
+}
+
+impl From<KoboError> for ImportRejection {
+	fn from(err: KoboError) -> Self {
+		ImportRejection::Kobo(err)
+	}
+}
+
+#[derive(Serialize)]
+struct ImportErrorBody {
+	error: &'static str,
+	message: String,
+}
+
+impl IntoResponse for ImportRejection {
+	fn into_response(self) -> Response {
+		let message = match self {
+			ImportRejection::Status(status) => return status.into_response(),
+			ImportRejection::Kobo(KoboError::Unexpected(message)) => {
+				println!("{}", message);
+				return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+			}
+			ImportRejection::Import(err) => err.to_string(),
+			ImportRejection::Kobo(err) => err.to_string(),
+		};
+		let body = ImportErrorBody {
+			error: "invalid_import",
+			message,
+		};
+		(StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response()
+	}
+}
+
+// multipartの最初の項目をファイルとして読む
+pub async fn read_upload(multipart: &mut Multipart) -> Result<Vec<u8>, StatusCode> {
+	let field = multipart
+		.next_field()
+		.await
+		.map_err(|_| StatusCode::BAD_REQUEST)?
+		.ok_or(StatusCode::BAD_REQUEST)?;
+	let bytes = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+
+	Ok(bytes.to_vec())
+}
+
+// 名前でタグを探し、無ければ作る
+async fn find_or_create_tag<T: TagRepository>(tag_repos: &T, user_id: &str, name: &str) -> Result<String, RepositoryError> {
+	let find = || async {
+		tag_repos
+			.find_all(user_id)
+			.await
+			.map(|tags| tags.into_iter().find(|tag| tag.name == name).map(|tag| tag.id))
+	};
+	if let Some(id) = find().await? {
+		return Ok(id);
+	}
+	let payload = TagName {
+		name: name.to_string(),
+	};
+	payload
+		.validate()
+		.map_err(|err| RepositoryError::InvalidInput(err.to_string()))?;
+	match tag_repos.create(user_id, payload).await {
+		Ok(tag) => Ok(tag.id),
+		// 同時に取り込んだ別の行が先に作った
+		Err(RepositoryError::Registered(_)) => find().await?.ok_or(RepositoryError::NotFound(name.to_string())),
+		Err(err) => Err(err),
+	}
+}
+
+// 登録した本に読書状況・メモ・タグを反映する。反映できなかったものは警告として返す
+async fn apply_row<B: BookRepository, M: MemoRepository, R: ReadingRepository, T: TagRepository>(
+	book_repos: &B,
+	memo_repos: &M,
+	reading_repos: &R,
+	tag_repos: &T,
+	user_id: &str,
+	isbn_13: &Isbn13,
+	row: &ImportRow,
+) -> Vec<String> {
+	let mut warnings = Vec::new();
+	// 登録したばかりの本は読みたい本なので、それ以外の状況だけ変える
+	let change = match row.status {
+		Some(ReadingStatus::Reading) => Some(ChangeStatus {
+			status: ReadingStatus::Reading,
+			date: row.registered_on,
+			note: None,
+		}),
+		Some(ReadingStatus::Finished) => Some(ChangeStatus {
+			status: ReadingStatus::Finished,
+			date: row.finished_on.or(row.registered_on),
+			note: None,
+		}),
+		_ => None,
+	};
+	if let Some(change) = change {
+		if let Err(err) = reading_repos.change_status(user_id, isbn_13, change).await {
+			warnings.push(format!("status was not changed: {}", err));
+		}
+	}
+	if let Some(rating) = row.rating {
+		if let Err(err) = book_repos.update_rating(user_id, isbn_13, Some(rating)).await {
+			warnings.push(format!("rating was not set: {}", err));
+		}
+	}
+	for text in &row.memos {
+		let memo = CreateMemo { text: text.clone() };
+		if let Err(err) = memo_repos.create(user_id, memo, isbn_13).await {
+			warnings.push(format!("memo was not created: {}", err));
+		}
+	}
+	for name in &row.tags {
+		let tagged = match find_or_create_tag(tag_repos, user_id, name).await {
+			Ok(id) => tag_repos.tag_book(user_id, &id, isbn_13).await,
+			Err(err) => Err(err),
+		};
+		if let Err(err) = tagged {
+			warnings.push(format!("tag '{}' was not added: {}", name, err));
+		}
+	}
+
+	warnings
+}
+
+fn row_result(row: &ImportRow, isbn_13: Option<Isbn13>, outcome: ImportOutcome) -> ImportRowResult {
+	ImportRowResult {
+		line: row.line,
+		isbn_13,
+		title: row.title.clone(),
+		status: row.status,
+		memos: row.memos.len(),
+		tags: row.tags.clone(),
+		outcome,
+		warnings: row.warnings.clone(),
+	}
+}
+
+// ブクログ・読書メーター・GoodreadsのCSVを取り込むハンドラ
+#[allow(clippy::too_many_arguments)]
+async fn import_csv<B, M, R, T, J>(
+	auth_session: AuthSession,
+	Query(query): Query<ImportQuery>,
+	Extension(book_repos): Extension<B>,
+	Extension(memo_repos): Extension<M>,
+	Extension(reading_repos): Extension<R>,
+	Extension(tag_repos): Extension<T>,
+	Extension(job_repos): Extension<J>,
+	Extension(provider): Extension<SharedProvider>,
+	mut multipart: Multipart,
+) -> Result<impl IntoResponse, ImportRejection>
+where
+	B: BookRepository,
+	M: MemoRepository,
+	R: ReadingRepository,
+	T: TagRepository,
+	J: JobRepository,
+{
+	let user_id = current_user_id(&auth_session)?;
+	let bytes = read_upload(&mut multipart).await?;
+	let parsed = parse_reading_log(&bytes, query.format)?;
+
+	let mut results: Vec<Option<ImportRowResult>> = Vec::new();
+	let mut seen = HashSet::new();
+	let mut tasks = Vec::new();
+	let semaphore = Arc::new(Semaphore::new(BULK_CONCURRENCY));
+	for (index, row) in parsed.rows.into_iter().enumerate() {
+		let isbn_13 = match &row.isbn_13 {
+			Ok(isbn_13) => isbn_13.clone(),
+			Err(message) => {
+				let outcome = ImportOutcome::Invalid {
+					message: message.clone(),
+				};
+				results.push(Some(row_result(&row, None, outcome)));
+				continue;
+			}
+		};
+		if !seen.insert(isbn_13.clone()) {
+			results.push(Some(row_result(&row, Some(isbn_13), ImportOutcome::Duplicate)));
+			continue;
+		}
+		if !query.commit {
+			let outcome = match book_repos.find(&user_id, &isbn_13).await {
+				Ok(_) => ImportOutcome::AlreadyRegistered,
+				Err(_) => ImportOutcome::WillCreate,
+			};
+			results.push(Some(row_result(&row, Some(isbn_13), outcome)));
+			continue;
+		}
+
+		results.push(None);
+		let book_repos = book_repos.clone();
+		let memo_repos = memo_repos.clone();
+		let reading_repos = reading_repos.clone();
+		let tag_repos = tag_repos.clone();
+		let job_repos = job_repos.clone();
+		let provider = provider.clone();
+		let user_id = user_id.clone();
+		let semaphore = semaphore.clone();
+		// panicしたときに報告するための行
+		let failed = (index, row.clone(), isbn_13.clone());
+		let task = tokio::spawn(async move {
+			let _permit = semaphore.acquire_owned().await;
+			// 登録済みの本は読書状況やメモも取り込み済みとみなし、二重に取り込まない
+			let (outcome, warnings) = match register_one(&book_repos, &job_repos, &provider, &user_id, &isbn_13).await {
+				BulkOutcome::Created { .. } => (
+					ImportOutcome::Created,
+					apply_row(&book_repos, &memo_repos, &reading_repos, &tag_repos, &user_id, &isbn_13, &row).await,
+				),
+				BulkOutcome::AlreadyRegistered => (ImportOutcome::AlreadyRegistered, Vec::new()),
+				BulkOutcome::Duplicate => (ImportOutcome::Duplicate, Vec::new()),
+				BulkOutcome::NotFound => (ImportOutcome::NotFound, Vec::new()),
+				BulkOutcome::Invalid { message } => (ImportOutcome::Invalid { message }, Vec::new()),
+				BulkOutcome::ProviderError { message } => (ImportOutcome::ProviderError { message }, Vec::new()),
+				BulkOutcome::Failed { message } => (ImportOutcome::Failed { message }, Vec::new()),
+			};
+			let mut result = row_result(&row, Some(isbn_13), outcome);
+			result.warnings.extend(warnings);
+			result
+		});
+		tasks.push((failed, task));
+	}
+
+	// 1行の取り込みがpanicしても、その行だけを失敗として返す
+	for ((index, row, isbn_13), task) in tasks {
+		let result = task.await.unwrap_or_else(|err| {
+			let outcome = ImportOutcome::Failed {
+				message: err.to_string(),
+			};
+			row_result(&row, Some(isbn_13), outcome)
+		});
+		results[index] = Some(result);
+	}
+
+	let rows = results.into_iter().flatten().collect();
+	Ok((
+		StatusCode::OK,
+		Json(ImportReport::new(parsed.format, parsed.encoding, query.commit, rows)),
+	))
+}
@@ -0,0 +1,51 @@
+use axum::{
+	extract::{Extension, Json, Path},
+	http::StatusCode,
+	response::IntoResponse,
+};
+use serde::Serialize;
+
+use crate::handler::current_user_id;
+use crate::repos::auth::AuthSession;
+use crate::repos::book::{BookInfo, BookRepository};
+use crate::repos::handle_repository_error;
+use crate::repos::job::{Job, JobRepository, JobStatus};
+
+#[derive(Serialize, Debug)]
+pub struct JobDetail {
+	#[serde(flatten)]
+	pub job: Job,
+	// 成功したジョブで登録された本
+	pub book: Option<BookInfo>,
+}
+
+pub fn create_job_app<JobRepos: JobRepository, BookRepos: BookRepository>(
+	job_repos: &JobRepos,
+	book_repos: &BookRepos,
+) -> axum::Router {
+	axum::Router::new()
+		.route("/:id", axum::routing::get(find_job::<JobRepos, BookRepos>))
+		.layer(Extension(job_repos.clone()))
+		.layer(Extension(book_repos.clone()))
+}
+
+// ジョブの状態を返すハンドラ
+async fn find_job<T: JobRepository, U: BookRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(job_repos): Extension<T>,
+	Extension(book_repos): Extension<U>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let job = job_repos
+		.find(&user_id, &id)
+		.await
+		.map_err(handle_repository_error)?;
+	// 登録後に削除された場合は本を付けない
+	let book = match job.status {
+		JobStatus::Succeeded => book_repos.find(&user_id, &job.isbn_13).await.ok(),
+		_ => None,
+	};
+
+	Ok((StatusCode::OK, Json(JobDetail { job, book })))
+}
@@ -0,0 +1,291 @@
+use axum::{
+	extract::{Extension, Json, Multipart, Query},
+	http::StatusCode,
+	response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::handler::current_user_id;
+use crate::handler::export::load_books;
+use crate::handler::import::ImportRejection;
+use crate::modules::csv_import::{decode_text, ImportError};
+use crate::modules::kindle_clippings::{find_candidates, parse_clippings, ClippingGroup, ClippingKind, SkippedClipping};
+use crate::repos::auth::AuthSession;
+use crate::repos::book::{internal_book_id, BookInfo, BookRepository, BookSource};
+use crate::repos::handle_repository_error;
+use crate::repos::memo::{ImportMemo, MemoRepository};
+
+// 一冊ごとに示す候補の数
+const CANDIDATE_LIMIT: usize = 5;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct KindleQuery {
+	// trueのときだけ登録する。省略した場合は登録せずに本の候補と結果の見込みだけを返す
+	#[serde(default)]
+	pub commit: bool,
+}
+
+// 切り抜きのまとまりをどの本に取り込むか。matchesの項目で題名の行ごとに指定する
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum KindleMatch {
+	// 登録済みの本に取り込む
+	Book { isbn_13: String },
+	// 題名と著者から手入力の本を作って取り込む
+	Create,
+	Skip,
+}
+
+#[derive(Serialize, Debug)]
+pub struct KindleCandidate {
+	pub isbn_13: String,
+	pub title: String,
+	pub authors: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum KindleOutcome {
+	WillImport { isbn_13: String },
+	WillCreate,
+	// 候補が無いか複数あり、取り込む本が指定されていない
+	Unmatched,
+	Skipped,
+	NotFound { isbn_13: String },
+	Imported {
+		isbn_13: String,
+		book_created: bool,
+		memos_created: usize,
+		// 以前に取り込み済みだった切り抜き
+		memos_duplicate: usize,
+	},
+	Failed { message: String },
+}
+
+impl KindleOutcome {
+	fn name(&self) -> &'static str {
+		match self {
+			KindleOutcome::WillImport { .. } => "will_import",
+			KindleOutcome::WillCreate => "will_create",
+			KindleOutcome::Unmatched => "unmatched",
+			KindleOutcome::Skipped => "skipped",
+			KindleOutcome::NotFound { .. } => "not_found",
+			KindleOutcome::Imported { .. } => "imported",
+			KindleOutcome::Failed { .. } => "failed",
+		}
+	}
+}
+
+#[derive(Serialize, Debug)]
+pub struct KindleBookResult {
+	pub key: String,
+	pub title: String,
+	pub authors: Vec<String>,
+	pub highlights: usize,
+	pub notes: usize,
+	// ブックマークは本文が無いので取り込まない
+	pub bookmarks: usize,
+	pub candidates: Vec<KindleCandidate>,
+	#[serde(flatten)]
+	pub outcome: KindleOutcome,
+	// 取り込めなかった切り抜き
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct KindleReport {
+	pub committed: bool,
+	pub summary: BTreeMap<&'static str, usize>,
+	pub books: Vec<KindleBookResult>,
+	// 読み取れなかった切り抜き
+	pub skipped: Vec<SkippedClipping>,
+}
+
+// fileにMy Clippings.txtを、省略できるmatchesに取り込み先の指定をJSONで受け取る
+async fn read_kindle_upload(
+	multipart: &mut Multipart,
+) -> Result<(Vec<u8>, HashMap<String, KindleMatch>), StatusCode> {
+	let mut file = None;
+	let mut matches = HashMap::new();
+	while let Some(field) = multipart.next_field().await.map_err(|_| StatusCode::BAD_REQUEST)? {
+		match field.name() {
+			Some("matches") => {
+				let bytes = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+				matches = serde_json::from_slice(&bytes).map_err(|_| StatusCode::BAD_REQUEST)?;
+			}
+			_ => file = Some(field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?.to_vec()),
+		}
+	}
+
+	Ok((file.ok_or(StatusCode::BAD_REQUEST)?, matches))
+}
+
+// 指定が無ければ、候補が一冊だけのときにその本へ取り込む
+fn choose(group: &ClippingGroup, candidates: &[&BookInfo], matches: &HashMap<String, KindleMatch>) -> Option<KindleMatch> {
+	match (matches.get(&group.key), candidates) {
+		(Some(choice), _) => Some(choice.clone()),
+		(None, [only]) => Some(KindleMatch::Book {
+			isbn_13: only.isbn_13.clone(),
+		}),
+		_ => None,
+	}
+}
+
+// 取り込み直しで同じ本を作らないように、前回作った手入力の本があればそれを使う
+async fn find_or_create_book<B: BookRepository>(
+	book_repos: &B,
+	books: &[BookInfo],
+	user_id: &str,
+	group: &ClippingGroup,
+) -> Result<(String, bool), String> {
+	let created = books.iter().find(|book_info| {
+		book_info.source == BookSource::Manual && book_info.title == group.title && book_info.authors == group.authors
+	});
+	if let Some(book_info) = created {
+		return Ok((book_info.isbn_13.clone(), false));
+	}
+	let payload = BookInfo {
+		isbn_13: internal_book_id(),
+		title: group.title.clone(),
+		authors: group.authors.clone(),
+		source: BookSource::Manual,
+		..Default::default()
+	};
+	book_repos
+		.create(user_id, payload)
+		.await
+		.map(|book_info| (book_info.isbn_13, true))
+		.map_err(|err| err.to_string())
+}
+
+// ハイライトとメモを位置付きのメモとして登録する。取り込み済みのものは数えるだけにする
+async fn import_clippings<M: MemoRepository>(
+	memo_repos: &M,
+	user_id: &str,
+	isbn_13: &str,
+	group: &ClippingGroup,
+) -> (usize, usize, Vec<String>) {
+	let mut created = 0;
+	let mut duplicate = 0;
+	let mut warnings = Vec::new();
+	for clipping in group
+		.clippings
+		.iter()
+		.filter(|clipping| clipping.kind != ClippingKind::Bookmark && !clipping.text.is_empty())
+	{
+		let payload = ImportMemo {
+			text: clipping.memo_text(),
+			source_key: clipping.source_key(),
+			// 端末の時刻にはタイムゾーンが無いのでUTCとみなす
+			created_at: clipping.added_at.map(|added_at| added_at.and_utc()),
+		};
+		match memo_repos.import(user_id, isbn_13, payload).await {
+			Ok(Some(_)) => created += 1,
+			Ok(None) => duplicate += 1,
+			Err(err) => warnings.push(format!("line {}: memo was not created: {}", clipping.line, err)),
+		}
+	}
+
+	(created, duplicate, warnings)
+}
+
+// KindleのMy Clippings.txtからハイライトとメモを取り込むハンドラ
+pub async fn import_kindle<B: BookRepository, M: MemoRepository>(
+	auth_session: AuthSession,
+	Query(query): Query<KindleQuery>,
+	Extension(book_repos): Extension<B>,
+	Extension(memo_repos): Extension<M>,
+	mut multipart: Multipart,
+) -> Result<impl IntoResponse, ImportRejection> {
+	let user_id = current_user_id(&auth_session)?;
+	let (bytes, matches) = read_kindle_upload(&mut multipart).await?;
+	let (text, _) = decode_text(&bytes);
+	let parsed = parse_clippings(&text);
+	if parsed.groups.is_empty() && parsed.skipped.is_empty() {
+		return Err(ImportError::Empty.into());
+	}
+	let books = load_books(&book_repos, &user_id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	let mut results = Vec::new();
+	for group in &parsed.groups {
+		let count = |kind: ClippingKind| group.clippings.iter().filter(|clipping| clipping.kind == kind).count();
+		let candidates = find_candidates(group, &books);
+		let has_text = group
+			.clippings
+			.iter()
+			.any(|clipping| clipping.kind != ClippingKind::Bookmark && !clipping.text.is_empty());
+		let choice = match has_text {
+			true => choose(group, &candidates, &matches),
+			false => Some(KindleMatch::Skip),
+		};
+
+		let mut warnings = Vec::new();
+		let outcome = match choice {
+			None => KindleOutcome::Unmatched,
+			Some(KindleMatch::Skip) => KindleOutcome::Skipped,
+			Some(KindleMatch::Book { isbn_13 }) if !books.iter().any(|book_info| book_info.isbn_13 == isbn_13) => {
+				KindleOutcome::NotFound { isbn_13 }
+			}
+			Some(KindleMatch::Book { isbn_13 }) if !query.commit => KindleOutcome::WillImport { isbn_13 },
+			Some(KindleMatch::Create) if !query.commit => KindleOutcome::WillCreate,
+			Some(choice) => {
+				let target = match choice {
+					KindleMatch::Book { isbn_13 } => Ok((isbn_13, false)),
+					_ => find_or_create_book(&book_repos, &books, &user_id, group).await,
+				};
+				match target {
+					Ok((isbn_13, book_created)) => {
+						let (memos_created, memos_duplicate, memo_warnings) =
+							import_clippings(&memo_repos, &user_id, &isbn_13, group).await;
+						warnings = memo_warnings;
+						KindleOutcome::Imported {
+							isbn_13,
+							book_created,
+							memos_created,
+							memos_duplicate,
+						}
+					}
+					Err(message) => KindleOutcome::Failed { message },
+				}
+			}
+		};
+
+		results.push(KindleBookResult {
+			key: group.key.clone(),
+			title: group.title.clone(),
+			authors: group.authors.clone(),
+			highlights: count(ClippingKind::Highlight),
+			notes: count(ClippingKind::Note),
+			bookmarks: count(ClippingKind::Bookmark),
+			candidates: candidates
+				.iter()
+				.take(CANDIDATE_LIMIT)
+				.map(|book_info| KindleCandidate {
+					isbn_13: book_info.isbn_13.clone(),
+					title: book_info.title.clone(),
+					authors: book_info.authors.clone(),
+				})
+				.collect(),
+			outcome,
+			warnings,
+		});
+	}
+
+	let mut summary = BTreeMap::new();
+	for result in &results {
+		*summary.entry(result.outcome.name()).or_insert(0) += 1;
+	}
+	Ok((
+		StatusCode::OK,
+		Json(KindleReport {
+			committed: query.commit,
+			summary,
+			books: results,
+			skipped: parsed.skipped,
+		}),
+	))
+}
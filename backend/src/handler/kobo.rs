@@ -0,0 +1,191 @@
+use axum::{
+	extract::{Extension, Json, Multipart, Query},
+	http::StatusCode,
+	response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::entity::isbn::Isbn13;
+use crate::handler::current_user_id;
+use crate::handler::export::load_books;
+use crate::handler::import::{read_upload, ImportRejection};
+use crate::modules::kindle_clippings::normalize_title;
+use crate::modules::kobo::{read_kobo_upload, KoboBook};
+use crate::repos::auth::AuthSession;
+use crate::repos::book::{BookInfo, BookRepository};
+use crate::repos::handle_repository_error;
+use crate::repos::memo::{ImportMemo, MemoRepository};
+
+#[derive(Deserialize, Debug, Default)]
+pub struct KoboQuery {
+	// trueのときだけ登録する。省略した場合は登録せずに結果の見込みだけを返す
+	#[serde(default)]
+	pub commit: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedBy {
+	Isbn,
+	// ISBNの無い本は題名が一致する本が一冊だけのときに取り込む
+	Title,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum UnmatchedReason {
+	IsbnNotRegistered,
+	TitleNotRegistered,
+	// 題名が一致する本が複数ある
+	AmbiguousTitle,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum KoboOutcome {
+	WillImport {
+		isbn_13: String,
+		matched_by: MatchedBy,
+	},
+	Unmatched {
+		reason: UnmatchedReason,
+	},
+	Imported {
+		isbn_13: String,
+		matched_by: MatchedBy,
+		memos_created: usize,
+		// 以前に取り込み済みだったハイライト
+		memos_duplicate: usize,
+	},
+}
+
+impl KoboOutcome {
+	fn name(&self) -> &'static str {
+		match self {
+			KoboOutcome::WillImport { .. } => "will_import",
+			KoboOutcome::Unmatched { .. } => "unmatched",
+			KoboOutcome::Imported { .. } => "imported",
+		}
+	}
+}
+
+#[derive(Serialize, Debug)]
+pub struct KoboBookResult {
+	pub title: String,
+	pub authors: Vec<String>,
+	// 端末のデータベースに書かれていたISBN
+	pub isbn: String,
+	pub highlights: usize,
+	pub notes: usize,
+	#[serde(flatten)]
+	pub outcome: KoboOutcome,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct KoboReport {
+	pub committed: bool,
+	pub summary: BTreeMap<&'static str, usize>,
+	pub books: Vec<KoboBookResult>,
+}
+
+// ISBNで蔵書の本を探し、ISBNが無ければ題名で探す
+fn match_book(kobo_book: &KoboBook, books: &[BookInfo]) -> Result<(String, MatchedBy), UnmatchedReason> {
+	if let Ok(isbn_13) = Isbn13::parse(&kobo_book.isbn) {
+		return books
+			.iter()
+			.find(|book_info| book_info.isbn_13 == isbn_13.as_str())
+			.map(|book_info| (book_info.isbn_13.clone(), MatchedBy::Isbn))
+			.ok_or(UnmatchedReason::IsbnNotRegistered);
+	}
+	let title = normalize_title(&kobo_book.title);
+	let matched: Vec<&BookInfo> = books
+		.iter()
+		.filter(|book_info| !title.is_empty() && normalize_title(&book_info.title) == title)
+		.collect();
+	match matched.as_slice() {
+		[only] => Ok((only.isbn_13.clone(), MatchedBy::Title)),
+		[] => Err(UnmatchedReason::TitleNotRegistered),
+		_ => Err(UnmatchedReason::AmbiguousTitle),
+	}
+}
+
+// KoboのKoboReader.sqliteからハイライトと書き込みを取り込むハンドラ
+pub async fn import_kobo<B: BookRepository, M: MemoRepository>(
+	auth_session: AuthSession,
+	Query(query): Query<KoboQuery>,
+	Extension(book_repos): Extension<B>,
+	Extension(memo_repos): Extension<M>,
+	mut multipart: Multipart,
+) -> Result<impl IntoResponse, ImportRejection> {
+	let user_id = current_user_id(&auth_session)?;
+	let bytes = read_upload(&mut multipart).await?;
+	// SQLiteの読み込みはブロッキングするので専用のスレッドで行う
+	let kobo_books = tokio::task::spawn_blocking(move || read_kobo_upload(&bytes))
+		.await
+		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+	let books = load_books(&book_repos, &user_id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	let mut results = Vec::new();
+	for kobo_book in kobo_books {
+		let mut warnings = Vec::new();
+		let outcome = match match_book(&kobo_book, &books) {
+			Err(reason) => KoboOutcome::Unmatched { reason },
+			Ok((isbn_13, matched_by)) if !query.commit => KoboOutcome::WillImport { isbn_13, matched_by },
+			Ok((isbn_13, matched_by)) => {
+				let mut memos_created = 0;
+				let mut memos_duplicate = 0;
+				for annotation in &kobo_book.annotations {
+					let payload = ImportMemo {
+						text: annotation.memo_text(),
+						source_key: annotation.source_key(),
+						created_at: annotation.created_at,
+					};
+					match memo_repos.import(&user_id, &isbn_13, payload).await {
+						Ok(Some(_)) => memos_created += 1,
+						Ok(None) => memos_duplicate += 1,
+						Err(err) => warnings.push(format!("memo was not created: {}", err)),
+					}
+				}
+				KoboOutcome::Imported {
+					isbn_13,
+					matched_by,
+					memos_created,
+					memos_duplicate,
+				}
+			}
+		};
+
+		let notes = kobo_book
+			.annotations
+			.iter()
+			.filter(|annotation| annotation.is_note())
+			.count();
+		results.push(KoboBookResult {
+			highlights: kobo_book.annotations.len() - notes,
+			notes,
+			title: kobo_book.title,
+			authors: kobo_book.authors,
+			isbn: kobo_book.isbn,
+			outcome,
+			warnings,
+		});
+	}
+
+	let mut summary = BTreeMap::new();
+	for result in &results {
+		*summary.entry(result.outcome.name()).or_insert(0) += 1;
+	}
+	Ok((
+		StatusCode::OK,
+		Json(KoboReport {
+			committed: query.commit,
+			summary,
+			books: results,
+		}),
+	))
+}
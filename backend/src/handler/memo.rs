@@ -1,40 +1,98 @@
 use axum::{
-	extract::{Json, Path, Extension},
+	extract::{Json, Path, Extension, Query},
 	http::StatusCode,
 	response::IntoResponse,
 };
+use serde::Deserialize;
 
+use crate::entity::isbn::Isbn13;
+use crate::handler::current_user_id;
+use crate::handler::tag::{tag_memo, untag_memo};
+use crate::repos::auth::AuthSession;
 use crate::repos::handle_repository_error;
-use crate::repos::memo::{CreateMemo, MemoRepository};
+use crate::repos::memo::{CreateMemo, MemoFilter, MemoRepository, MemoSort, UpdateMemo};
+use crate::repos::tag::TagRepository;
+use crate::repos::{PageRequest, SortOrder};
 
-pub fn create_memo_app<MemoRepos: MemoRepository>(memo_repos: &MemoRepos) -> axum::Router {
+pub fn create_memo_app<MemoRepos, TagRepos>(memo_repos: &MemoRepos, tag_repos: &TagRepos) -> axum::Router
+where
+	MemoRepos: MemoRepository,
+	TagRepos: TagRepository,
+{
 	axum::Router::new().route(
 		"/:id",
-		axum::routing::get(find_memo::<MemoRepos>).delete(delete_memo::<MemoRepos>)
+		axum::routing::get(find_memo::<MemoRepos>)
+			.put(update_memo::<MemoRepos>)
+			.patch(update_memo::<MemoRepos>)
+			.delete(delete_memo::<MemoRepos>)
 	)
+		.route("/:id/revisions", axum::routing::get(find_memo_revisions::<MemoRepos>))
+		.route(
+			"/:id/revisions/:revision_id/restore",
+			axum::routing::post(restore_memo::<MemoRepos>),
+		)
+		.route(
+			"/:id/tag/:tag_id",
+			axum::routing::put(tag_memo::<TagRepos>).delete(untag_memo::<TagRepos>),
+		)
 		.layer(Extension(memo_repos.clone()))
+		.layer(Extension(tag_repos.clone()))
+}
+
+#[derive(Deserialize)]
+pub struct MemoListQuery {
+	tag: Option<String>,
+	#[serde(default)]
+	sort: MemoSort,
+	#[serde(default)]
+	order: SortOrder,
+	limit: Option<i64>,
+	cursor: Option<String>,
+	#[serde(default)]
+	with_total: bool,
+}
+
+impl MemoListQuery {
+	fn filter(&self) -> MemoFilter {
+		MemoFilter {
+			tag: self.tag.clone(),
+		}
+	}
+
+	fn page_request(&self) -> PageRequest {
+		PageRequest {
+			limit: self.limit,
+			cursor: self.cursor.clone(),
+			with_total: self.with_total,
+		}
+	}
 }
 
 // 登録済みのメモを全て返すハンドラ
 pub async fn find_all_memo<T: MemoRepository>(
-	Path(isbn_13): Path<String>,
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Query(query): Query<MemoListQuery>,
 	Extension(memo_repos): Extension<T>,
 ) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
 	let memo_list = memo_repos
-		.find_all(&isbn_13)
+		.find_all(&user_id, &isbn_13, &query.filter(), query.sort, query.order, &query.page_request())
 		.await
-		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+		.map_err(handle_repository_error)?;
 
 	Ok((StatusCode::OK, Json(memo_list)))
 }
 
 // メモを検索するハンドラ
 async fn find_memo<T: MemoRepository>(
+	auth_session: AuthSession,
 	Path(id): Path<String>,
 	Extension(memo_repos): Extension<T>,
 ) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
 	let memo = memo_repos
-		.find(&id)
+		.find(&user_id, &id)
 		.await
 		.map_err(handle_repository_error)?;
 
@@ -43,12 +101,14 @@ async fn find_memo<T: MemoRepository>(
 
 // メモを登録するハンドラ
 pub async fn create_memo<T: MemoRepository>(
-	Path(isbn_13): Path<String>,
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
 	Extension(memo_repos): Extension<T>,
 	Json(payload): Json<CreateMemo>,
 ) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
 	let memo = memo_repos
-		.create(payload, &isbn_13)
+		.create(&user_id, payload, &isbn_13)
 		.await
 		.map_err(handle_repository_error)?;
 
@@ -57,13 +117,61 @@ pub async fn create_memo<T: MemoRepository>(
 
 // メモを削除するハンドラ
 async fn delete_memo<T: MemoRepository>(
+	auth_session: AuthSession,
 	Path(id): Path<String>,
 	Extension(memo_repos): Extension<T>,
 ) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
 	memo_repos
-		.delete(&id)
+		.delete(&user_id, &id)
 		.await
 		.map_err(handle_repository_error)?;
 
 	Ok((StatusCode::OK, ()))
 }
+
+// メモを更新するハンドラ
+async fn update_memo<T: MemoRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(memo_repos): Extension<T>,
+	Json(payload): Json<UpdateMemo>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let memo = memo_repos
+		.update(&user_id, &id, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(memo)))
+}
+
+// メモの編集履歴を返すハンドラ
+async fn find_memo_revisions<T: MemoRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(memo_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let revisions = memo_repos
+		.find_revisions(&user_id, &id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(revisions)))
+}
+
+// メモを過去の版に戻すハンドラ
+async fn restore_memo<T: MemoRepository>(
+	auth_session: AuthSession,
+	Path((id, revision_id)): Path<(String, String)>,
+	Extension(memo_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let memo = memo_repos
+		.restore(&user_id, &id, &revision_id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(memo)))
+}
@@ -1,3 +1,38 @@
 pub mod book;
 pub mod memo;
 pub mod auth;
+pub mod reading;
+pub mod search;
+pub mod tag;
+pub mod shelf;
+pub mod bulk;
+pub mod job;
+pub mod admin;
+pub mod cover;
+pub mod import;
+pub mod export;
+pub mod kindle;
+pub mod kobo;
+
+use axum::http::StatusCode;
+
+use crate::repos::auth::AuthSession;
+
+// ログイン中のユーザーのIDを返す
+pub fn current_user_id(auth_session: &AuthSession) -> Result<String, StatusCode> {
+	auth_session
+		.user
+		.as_ref()
+		.map(|user| user.id.clone())
+		.ok_or(StatusCode::UNAUTHORIZED)
+}
+
+// ADMIN_EMAILSにカンマ区切りで並べたユーザーだけに管理用の操作を許す
+pub fn require_admin(auth_session: &AuthSession) -> Result<(), StatusCode> {
+	let user = auth_session.user.as_ref().ok_or(StatusCode::UNAUTHORIZED)?;
+	let admins = std::env::var("ADMIN_EMAILS").unwrap_or_default();
+	match admins.split(',').map(str::trim).any(|email| email == user.email) {
+		true => Ok(()),
+		false => Err(StatusCode::FORBIDDEN),
+	}
+}
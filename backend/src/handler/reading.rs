@@ -0,0 +1,74 @@
+use axum::{
+	extract::{Extension, Json},
+	http::StatusCode,
+	response::IntoResponse,
+};
+
+use crate::entity::isbn::Isbn13;
+use crate::handler::current_user_id;
+use crate::repos::auth::AuthSession;
+use crate::repos::handle_repository_error;
+use crate::modules::validate_json::ValidatedJson;
+use crate::repos::reading::{ChangeStatus, CreateProgress, ReadingRepository};
+
+// 読書状態とその履歴を返すハンドラ
+pub async fn find_reading_history<T: ReadingRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Extension(reading_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let history = reading_repos
+		.find_history(&user_id, &isbn_13)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(history)))
+}
+
+// 読書状態を変更するハンドラ
+pub async fn change_reading_status<T: ReadingRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Extension(reading_repos): Extension<T>,
+	Json(payload): Json<ChangeStatus>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let state = reading_repos
+		.change_status(&user_id, &isbn_13, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(state)))
+}
+
+// 読書の進捗を記録するハンドラ
+pub async fn add_reading_progress<T: ReadingRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Extension(reading_repos): Extension<T>,
+	ValidatedJson(payload): ValidatedJson<CreateProgress>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let progress = reading_repos
+		.add_progress(&user_id, &isbn_13, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::CREATED, Json(progress)))
+}
+
+// 進捗の履歴と読了予定日を返すハンドラ
+pub async fn find_reading_progress<T: ReadingRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Extension(reading_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let history = reading_repos
+		.find_progress(&user_id, &isbn_13)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(history)))
+}
@@ -0,0 +1,52 @@
+use axum::{
+	extract::{Extension, Json, Query},
+	http::StatusCode,
+	response::IntoResponse,
+};
+use serde::Deserialize;
+
+use crate::handler::current_user_id;
+use crate::repos::auth::AuthSession;
+use crate::repos::handle_repository_error;
+use crate::repos::search::SearchRepository;
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+const MAX_SEARCH_LIMIT: usize = 100;
+
+pub fn create_search_app<SearchRepos: SearchRepository>(search_repos: &SearchRepos) -> axum::Router {
+	axum::Router::new()
+		.route("/", axum::routing::get(search::<SearchRepos>))
+		.layer(Extension(search_repos.clone()))
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+	q: String,
+	limit: Option<usize>,
+}
+
+// メモと本を横断して検索するハンドラ
+async fn search<T: SearchRepository>(
+	auth_session: AuthSession,
+	Query(query): Query<SearchQuery>,
+	Extension(search_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+
+	// 全角スペースも区切りとして扱い、全ての語を含むものを探す
+	let terms: Vec<String> = query.q.split_whitespace().map(str::to_string).collect();
+	if terms.is_empty() {
+		return Err(StatusCode::BAD_REQUEST);
+	}
+	let limit = query
+		.limit
+		.unwrap_or(DEFAULT_SEARCH_LIMIT)
+		.clamp(1, MAX_SEARCH_LIMIT);
+
+	let hits = search_repos
+		.search(&user_id, &terms, limit)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(hits)))
+}
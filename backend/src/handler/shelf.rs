@@ -0,0 +1,248 @@
+use axum::{
+	extract::{Extension, Json, Path, Query},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+};
+
+use crate::entity::isbn::Isbn13;
+use crate::handler::book::BookListQuery;
+use crate::handler::current_user_id;
+use crate::modules::filter_expr::{self, ParseError};
+use crate::modules::validate_json::ValidatedJson;
+use crate::repos::auth::AuthSession;
+use crate::repos::book::BookRepository;
+use crate::repos::handle_repository_error;
+use crate::repos::shelf::{
+	AddShelfBook, CreateShelf, ReorderShelf, ShelfRepository, UpdateShelf, UpdateShelfBook,
+};
+
+pub fn create_shelf_app<ShelfRepos, BookRepos>(shelf_repos: &ShelfRepos, book_repos: &BookRepos) -> axum::Router
+where
+	ShelfRepos: ShelfRepository,
+	BookRepos: BookRepository,
+{
+	axum::Router::new()
+		.route("/", axum::routing::get(find_all_shelf::<ShelfRepos>).post(create_shelf::<ShelfRepos>))
+		.route(
+			"/:id",
+			axum::routing::get(find_shelf::<ShelfRepos>)
+				.patch(update_shelf::<ShelfRepos>)
+				.delete(delete_shelf::<ShelfRepos>),
+		)
+		.route("/:id/books", axum::routing::get(find_shelf_books::<ShelfRepos, BookRepos>))
+		.route("/:id/order", axum::routing::put(reorder_shelf::<ShelfRepos>))
+		.route("/:id/book", axum::routing::post(add_shelf_book::<ShelfRepos>))
+		.route(
+			"/:id/book/:isbn_13",
+			axum::routing::patch(update_shelf_book::<ShelfRepos>).delete(remove_shelf_book::<ShelfRepos>),
+		)
+		.layer(Extension(shelf_repos.clone()))
+		.layer(Extension(book_repos.clone()))
+}
+
+// 条件式の誤りは位置を付けて返す
+pub enum ShelfError {
+	Status(StatusCode),
+	Expression(ParseError),
+}
+
+impl From<StatusCode> for ShelfError {
+	fn from(status: StatusCode) -> Self {
+		ShelfError::Status(status)
+	}
+}
+
+impl From<ParseError> for ShelfError {
+	fn from(err: ParseError) -> Self {
+		ShelfError::Expression(err)
+	}
+}
+
+impl IntoResponse for ShelfError {
+	fn into_response(self) -> Response {
+		match self {
+			ShelfError::Status(status) => status.into_response(),
+			ShelfError::Expression(err) => err.into_response(),
+		}
+	}
+}
+
+// 本棚を全て返すハンドラ
+async fn find_all_shelf<T: ShelfRepository>(
+	auth_session: AuthSession,
+	Extension(shelf_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let shelves = shelf_repos
+		.find_all(&user_id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(shelves)))
+}
+
+// 本棚と並んでいる本を返すハンドラ
+async fn find_shelf<T: ShelfRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(shelf_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let shelf = shelf_repos
+		.find(&user_id, &id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(shelf)))
+}
+
+// 本棚に並んでいる本、またはスマート本棚の条件に合う本を返すハンドラ
+async fn find_shelf_books<T: ShelfRepository, U: BookRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Query(query): Query<BookListQuery>,
+	Extension(shelf_repos): Extension<T>,
+	Extension(book_repos): Extension<U>,
+) -> Result<impl IntoResponse, ShelfError> {
+	let user_id = current_user_id(&auth_session)?;
+	let expression = shelf_repos
+		.find_query(&user_id, &id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	let mut filter = query.filter();
+	match expression {
+		Some(expression) => filter.expression = Some(filter_expr::parse(&expression)?),
+		// 手で並べた本棚は並べた順に、添えたメモと一緒に返す
+		None => {
+			let entries = shelf_repos
+				.find_books(&user_id, &id, &filter, &query.page_request())
+				.await
+				.map_err(handle_repository_error)?;
+			return Ok((StatusCode::OK, Json(entries)).into_response());
+		}
+	}
+	let book_info_list = book_repos
+		.find_all(&user_id, &filter, query.sort, query.order, &query.page_request())
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(book_info_list)).into_response())
+}
+
+// 本棚を作成するハンドラ
+async fn create_shelf<T: ShelfRepository>(
+	auth_session: AuthSession,
+	Extension(shelf_repos): Extension<T>,
+	ValidatedJson(payload): ValidatedJson<CreateShelf>,
+) -> Result<impl IntoResponse, ShelfError> {
+	let user_id = current_user_id(&auth_session)?;
+	if let Some(query) = &payload.query {
+		filter_expr::parse(query)?;
+	}
+	let shelf = shelf_repos
+		.create(&user_id, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::CREATED, Json(shelf)))
+}
+
+// 本棚の名前や説明を変更するハンドラ
+async fn update_shelf<T: ShelfRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(shelf_repos): Extension<T>,
+	ValidatedJson(payload): ValidatedJson<UpdateShelf>,
+) -> Result<impl IntoResponse, ShelfError> {
+	let user_id = current_user_id(&auth_session)?;
+	if let Some(query) = &payload.query {
+		filter_expr::parse(query)?;
+	}
+	let shelf = shelf_repos
+		.update(&user_id, &id, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(shelf)))
+}
+
+// 本棚を削除するハンドラ
+async fn delete_shelf<T: ShelfRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(shelf_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	shelf_repos
+		.delete(&user_id, &id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok(StatusCode::NO_CONTENT)
+}
+
+// 本棚の本を並べ替えるハンドラ
+async fn reorder_shelf<T: ShelfRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(shelf_repos): Extension<T>,
+	Json(payload): Json<ReorderShelf>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let shelf = shelf_repos
+		.reorder(&user_id, &id, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(shelf)))
+}
+
+// 本棚に本を追加するハンドラ
+async fn add_shelf_book<T: ShelfRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(shelf_repos): Extension<T>,
+	ValidatedJson(payload): ValidatedJson<AddShelfBook>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let shelf = shelf_repos
+		.add_book(&user_id, &id, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::CREATED, Json(shelf)))
+}
+
+// 本棚の本に添えたメモを変更するハンドラ
+async fn update_shelf_book<T: ShelfRepository>(
+	auth_session: AuthSession,
+	Path((id, _)): Path<(String, String)>,
+	isbn_13: Isbn13,
+	Extension(shelf_repos): Extension<T>,
+	Json(payload): Json<UpdateShelfBook>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let shelf = shelf_repos
+		.update_book(&user_id, &id, &isbn_13, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(shelf)))
+}
+
+// 本棚から本を外すハンドラ
+async fn remove_shelf_book<T: ShelfRepository>(
+	auth_session: AuthSession,
+	Path((id, _)): Path<(String, String)>,
+	isbn_13: Isbn13,
+	Extension(shelf_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let shelf = shelf_repos
+		.remove_book(&user_id, &id, &isbn_13)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(shelf)))
+}
@@ -0,0 +1,161 @@
+use axum::{
+	extract::{Extension, Json, Path},
+	http::StatusCode,
+	response::IntoResponse,
+};
+
+use crate::entity::isbn::Isbn13;
+use crate::handler::current_user_id;
+use crate::modules::validate_json::ValidatedJson;
+use crate::repos::auth::AuthSession;
+use crate::repos::handle_repository_error;
+use crate::repos::tag::{MergeTag, TagName, TagRepository};
+
+pub fn create_tag_app<TagRepos: TagRepository>(tag_repos: &TagRepos) -> axum::Router {
+	axum::Router::new()
+		.route("/", axum::routing::get(find_all_tag::<TagRepos>).post(create_tag::<TagRepos>))
+		.route(
+			"/:id",
+			axum::routing::patch(rename_tag::<TagRepos>).delete(delete_tag::<TagRepos>),
+		)
+		.route("/:id/merge", axum::routing::post(merge_tag::<TagRepos>))
+		.layer(Extension(tag_repos.clone()))
+}
+
+// 登録済みのタグを全て返すハンドラ
+async fn find_all_tag<T: TagRepository>(
+	auth_session: AuthSession,
+	Extension(tag_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let tags = tag_repos
+		.find_all(&user_id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(tags)))
+}
+
+// タグを作成するハンドラ
+async fn create_tag<T: TagRepository>(
+	auth_session: AuthSession,
+	Extension(tag_repos): Extension<T>,
+	ValidatedJson(payload): ValidatedJson<TagName>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let tag = tag_repos
+		.create(&user_id, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::CREATED, Json(tag)))
+}
+
+// タグの名前を変更するハンドラ
+async fn rename_tag<T: TagRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(tag_repos): Extension<T>,
+	ValidatedJson(payload): ValidatedJson<TagName>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let tag = tag_repos
+		.rename(&user_id, &id, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(tag)))
+}
+
+// タグを別のタグに統合するハンドラ
+async fn merge_tag<T: TagRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(tag_repos): Extension<T>,
+	Json(payload): Json<MergeTag>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	let tag = tag_repos
+		.merge(&user_id, &id, payload)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok((StatusCode::OK, Json(tag)))
+}
+
+// タグを削除するハンドラ
+async fn delete_tag<T: TagRepository>(
+	auth_session: AuthSession,
+	Path(id): Path<String>,
+	Extension(tag_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	tag_repos
+		.delete(&user_id, &id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok(StatusCode::NO_CONTENT)
+}
+
+// 本にタグを付けるハンドラ
+pub async fn tag_book<T: TagRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Path((_, tag_id)): Path<(String, String)>,
+	Extension(tag_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	tag_repos
+		.tag_book(&user_id, &tag_id, &isbn_13)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok(StatusCode::NO_CONTENT)
+}
+
+// 本からタグを外すハンドラ
+pub async fn untag_book<T: TagRepository>(
+	auth_session: AuthSession,
+	isbn_13: Isbn13,
+	Path((_, tag_id)): Path<(String, String)>,
+	Extension(tag_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	tag_repos
+		.untag_book(&user_id, &tag_id, &isbn_13)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok(StatusCode::NO_CONTENT)
+}
+
+// メモにタグを付けるハンドラ
+pub async fn tag_memo<T: TagRepository>(
+	auth_session: AuthSession,
+	Path((id, tag_id)): Path<(String, String)>,
+	Extension(tag_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	tag_repos
+		.tag_memo(&user_id, &tag_id, &id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok(StatusCode::NO_CONTENT)
+}
+
+// メモからタグを外すハンドラ
+pub async fn untag_memo<T: TagRepository>(
+	auth_session: AuthSession,
+	Path((id, tag_id)): Path<(String, String)>,
+	Extension(tag_repos): Extension<T>,
+) -> Result<impl IntoResponse, StatusCode> {
+	let user_id = current_user_id(&auth_session)?;
+	tag_repos
+		.untag_memo(&user_id, &tag_id, &id)
+		.await
+		.map_err(handle_repository_error)?;
+
+	Ok(StatusCode::NO_CONTENT)
+}
@@ -2,4 +2,6 @@ pub mod handler;
 pub mod repos;
 pub mod entity;
 pub mod modules;
+pub mod provider;
+pub mod storage;
 pub mod app;
\ No newline at end of file
@@ -0,0 +1,398 @@
+use axum::{
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use std::io::Cursor;
+
+use image::{DynamicImage, GrayImage, ImageFormat, ImageReader, ImageResult, Limits};
+use serde::Serialize;
+use thiserror::Error;
+
+// 横・縦それぞれに走査する線の数
+const SCAN_LINES: u32 = 48;
+// 1モジュールあたりの幅のずれの許容値（平均と1本あたりの最大）
+const MAX_VARIANCE: f64 = 0.4;
+const MAX_RUN_VARIANCE: f64 = 0.8;
+// 読み込む画像の縦横と、展開に使うメモリの上限
+const MAX_IMAGE_SIDE: u32 = 8000;
+const MAX_IMAGE_ALLOC: u64 = 256 * 1024 * 1024;
+
+// 左側の数字のLコードの幅（白・黒・白・黒）。Rコードは同じ幅で色が逆、Gコードは幅を逆順にしたもの
+const DIGIT_WIDTHS: [[u32; 4]; 10] = [
+	[3, 2, 1, 1],
+	[2, 2, 2, 1],
+	[2, 1, 2, 2],
+	[1, 4, 1, 1],
+	[1, 1, 3, 2],
+	[1, 2, 3, 1],
+	[1, 1, 1, 4],
+	[1, 3, 1, 2],
+	[1, 2, 1, 3],
+	[3, 1, 1, 2],
+];
+
+// 先頭の数字ごとの左側6桁のパリティ（1はGコード）
+const FIRST_DIGIT_PARITY: [u8; 10] = [
+	0b000000, 0b001011, 0b001101, 0b001110, 0b010011, 0b011001, 0b011100, 0b010101, 0b010110, 0b011010,
+];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum BarcodeError {
+	#[error("image must be JPEG or PNG")]
+	UnsupportedFormat,
+	#[error("image could not be decoded: {0}")]
+	InvalidImage(String),
+	#[error("no EAN-13 barcode was found in the image")]
+	NotFound,
+	#[error("barcode {0} is not an ISBN")]
+	NotIsbn(String),
+}
+
+impl BarcodeError {
+	fn code(&self) -> &'static str {
+		match self {
+			BarcodeError::UnsupportedFormat => "unsupported_format",
+			BarcodeError::InvalidImage(_) => "invalid_image",
+			BarcodeError::NotFound => "barcode_not_found",
+			BarcodeError::NotIsbn(_) => "not_isbn",
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct BarcodeErrorBody {
+	error: &'static str,
+	message: String,
+}
+
+impl IntoResponse for BarcodeError {
+	fn into_response(self) -> Response {
+		let body = BarcodeErrorBody {
+			error: self.code(),
+			message: self.to_string(),
+		};
+		(StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response()
+	}
+}
+
+// JPEG・PNGの画像から読み取れたEAN-13を見つかった順に返す
+pub fn decode_ean_13(bytes: &[u8]) -> Result<Vec<String>, BarcodeError> {
+	let format = match image::guess_format(bytes) {
+		Ok(format @ (ImageFormat::Jpeg | ImageFormat::Png)) => format,
+		_ => return Err(BarcodeError::UnsupportedFormat),
+	};
+	let gray = load_image(bytes, format)
+		.map_err(|err| BarcodeError::InvalidImage(err.to_string()))?
+		.to_luma8();
+
+	let mut codes: Vec<String> = Vec::new();
+	for line in scan_lines(&gray) {
+		for runs in [global_runs(&line), local_runs(&line)].into_iter().flatten() {
+			for code in find_codes(&runs) {
+				if !codes.contains(&code) {
+					codes.push(code);
+				}
+			}
+		}
+	}
+
+	match codes.is_empty() {
+		true => Err(BarcodeError::NotFound),
+		false => Ok(codes),
+	}
+}
+
+// 巨大な画像で展開時にメモリを使い切らないよう上限を付けて読む
+fn load_image(bytes: &[u8], format: ImageFormat) -> ImageResult<DynamicImage> {
+	let mut limits = Limits::default();
+	limits.max_image_width = Some(MAX_IMAGE_SIDE);
+	limits.max_image_height = Some(MAX_IMAGE_SIDE);
+	limits.max_alloc = Some(MAX_IMAGE_ALLOC);
+
+	let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+	reader.limits(limits);
+	reader.decode()
+}
+
+// 画像を横と縦に等間隔で切った輝度の列
+fn scan_lines(gray: &GrayImage) -> Vec<Vec<u8>> {
+	let (width, height) = gray.dimensions();
+	let mut lines = Vec::new();
+	for i in 1..=SCAN_LINES {
+		let y = height * i / (SCAN_LINES + 1);
+		if y < height {
+			lines.push((0..width).map(|x| gray.get_pixel(x, y).0[0]).collect());
+		}
+		let x = width * i / (SCAN_LINES + 1);
+		if x < width {
+			lines.push((0..height).map(|y| gray.get_pixel(x, y).0[0]).collect());
+		}
+	}
+
+	lines
+}
+
+// 黒から始まる黒白交互の幅の列にする
+fn to_runs(dark: impl Iterator<Item = bool>) -> Vec<u32> {
+	let mut runs = Vec::new();
+	let mut current: Option<bool> = None;
+	let mut length = 0;
+	for is_dark in dark {
+		match current {
+			Some(color) if color == is_dark => length += 1,
+			Some(_) => {
+				runs.push(length);
+				current = Some(is_dark);
+				length = 1;
+			}
+			// 先頭の白は読み飛ばす
+			None if is_dark => {
+				current = Some(true);
+				length = 1;
+			}
+			None => {}
+		}
+	}
+	if current.is_some() {
+		runs.push(length);
+	}
+
+	runs
+}
+
+// 線全体の明暗の中間で二値化する
+fn global_runs(line: &[u8]) -> Option<Vec<u32>> {
+	let min = *line.iter().min()?;
+	let max = *line.iter().max()?;
+	if max - min < 32 {
+		return None;
+	}
+	let threshold = (min as u32 + max as u32) / 2;
+
+	Some(to_runs(line.iter().map(|value| (*value as u32) < threshold)))
+}
+
+// 明るさにむらのある写真向けに、周辺の平均との差で二値化する
+fn local_runs(line: &[u8]) -> Option<Vec<u32>> {
+	let window = (line.len() / 16).max(8);
+	let mut prefix = vec![0u64; line.len() + 1];
+	for (i, value) in line.iter().enumerate() {
+		prefix[i + 1] = prefix[i] + *value as u64;
+	}
+
+	let dark = (0..line.len()).map(|i| {
+		let start = i.saturating_sub(window);
+		let end = (i + window + 1).min(line.len());
+		let mean = (prefix[end] - prefix[start]) as f64 / (end - start) as f64;
+		(line[i] as f64) < mean - 8.0
+	});
+
+	Some(to_runs(dark))
+}
+
+// 幅の列を想定するモジュール数の比と比べたずれ
+fn variance(runs: &[u32], pattern: &[u32]) -> f64 {
+	let total: u32 = runs.iter().sum();
+	let modules: u32 = pattern.iter().sum();
+	if total == 0 {
+		return f64::MAX;
+	}
+	let unit = total as f64 / modules as f64;
+
+	let deviations: Vec<f64> = runs
+		.iter()
+		.zip(pattern)
+		.map(|(run, expected)| (*run as f64 / unit - *expected as f64).abs())
+		.collect();
+	if deviations.iter().any(|deviation| *deviation > MAX_RUN_VARIANCE) {
+		return f64::MAX;
+	}
+
+	deviations.iter().sum::<f64>() / deviations.len() as f64
+}
+
+// 4本の幅に最も近い数字と、Gコードだったかを返す
+fn decode_digit(runs: &[u32], allow_g: bool) -> Option<(u32, bool)> {
+	let mut best: Option<(u32, bool, f64)> = None;
+	for (digit, widths) in DIGIT_WIDTHS.iter().enumerate() {
+		let mut candidates = vec![(*widths, false)];
+		if allow_g {
+			let mut reversed = *widths;
+			reversed.reverse();
+			candidates.push((reversed, true));
+		}
+		for (pattern, is_g) in candidates {
+			let score = variance(runs, &pattern);
+			if score < MAX_VARIANCE && best.map(|(_, _, best_score)| score < best_score).unwrap_or(true) {
+				best = Some((digit as u32, is_g, score));
+			}
+		}
+	}
+
+	best.map(|(digit, is_g, _)| (digit, is_g))
+}
+
+fn check_digit_ok(digits: &[u32]) -> bool {
+	let sum: u32 = digits[..12]
+		.iter()
+		.enumerate()
+		.map(|(i, digit)| if i % 2 == 0 { *digit } else { digit * 3 })
+		.sum();
+	(10 - sum % 10) % 10 == digits[12]
+}
+
+// 開始・中央・終了のガードを手がかりに、黒で始まる位置から59本の幅を読む
+fn decode_at(runs: &[u32]) -> Option<String> {
+	let start = &runs[0..3];
+	let left = &runs[3..27];
+	let middle = &runs[27..32];
+	let right = &runs[32..56];
+	let end = &runs[56..59];
+
+	let total: u32 = runs[..59].iter().sum();
+	let unit = total as f64 / 95.0;
+	// ガードの外側に少なくとも数モジュール分の余白があること
+	if (runs.get(59).copied().unwrap_or(u32::MAX) as f64) < unit * 3.0 {
+		return None;
+	}
+	if variance(start, &[1, 1, 1]) > MAX_VARIANCE
+		|| variance(middle, &[1, 1, 1, 1, 1]) > MAX_VARIANCE
+		|| variance(end, &[1, 1, 1]) > MAX_VARIANCE
+	{
+		return None;
+	}
+
+	let mut digits = Vec::with_capacity(13);
+	let mut parity = 0u8;
+	for chunk in left.chunks(4) {
+		let (digit, is_g) = decode_digit(chunk, true)?;
+		parity = (parity << 1) | is_g as u8;
+		digits.push(digit);
+	}
+	for chunk in right.chunks(4) {
+		let (digit, _) = decode_digit(chunk, false)?;
+		digits.push(digit);
+	}
+	let first = FIRST_DIGIT_PARITY.iter().position(|pattern| *pattern == parity)? as u32;
+	digits.insert(0, first);
+
+	if !check_digit_ok(&digits) {
+		return None;
+	}
+
+	Some(digits.iter().map(|digit| char::from_digit(*digit, 10).unwrap_or('0')).collect())
+}
+
+fn find_codes(runs: &[u32]) -> Vec<String> {
+	let mut codes = Vec::new();
+	// 黒白交互で始まりは黒なので、黒の幅は偶数番目になる。逆向きに写った場合も読む
+	let mut reversed: Vec<u32> = runs.to_vec();
+	reversed.reverse();
+	if !reversed.is_empty() && reversed.len().is_multiple_of(2) {
+		// 逆順の先頭が白になるので取り除く
+		reversed.remove(0);
+	}
+
+	for candidate in [runs, &reversed[..]] {
+		let mut i = 0;
+		while i + 59 <= candidate.len() {
+			// 開始ガードの手前にも余白があること
+			let quiet = i == 0 || candidate[i - 1] as f64 >= candidate[i..i + 3].iter().sum::<u32>() as f64;
+			if quiet {
+				if let Some(code) = decode_at(&candidate[i..]) {
+					if !codes.contains(&code) {
+						codes.push(code);
+					}
+				}
+			}
+			i += 2;
+		}
+	}
+
+	codes
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use image::Luma;
+
+	const MODULE_WIDTH: u32 = 3;
+	const QUIET_MODULES: u32 = 12;
+
+	// 数字の並びをそのままEAN-13の白黒のモジュールにする。チェックディジットは検証しない
+	fn ean_13_modules(code: &str) -> Vec<bool> {
+		let digits: Vec<usize> = code.chars().map(|c| c.to_digit(10).unwrap() as usize).collect();
+		let parity = FIRST_DIGIT_PARITY[digits[0]];
+		let push_widths = |modules: &mut Vec<bool>, widths: [u32; 4], first_dark: bool| {
+			for (i, width) in widths.iter().enumerate() {
+				let is_dark = (i % 2 == 0) == first_dark;
+				modules.extend(std::iter::repeat_n(is_dark, *width as usize));
+			}
+		};
+
+		let mut modules = vec![true, false, true];
+		for (i, digit) in digits[1..7].iter().enumerate() {
+			let mut widths = DIGIT_WIDTHS[*digit];
+			if parity & (1 << (5 - i)) != 0 {
+				widths.reverse();
+			}
+			push_widths(&mut modules, widths, false);
+		}
+		modules.extend([false, true, false, true, false]);
+		for digit in &digits[7..] {
+			push_widths(&mut modules, DIGIT_WIDTHS[*digit], true);
+		}
+		modules.extend([true, false, true]);
+
+		modules
+	}
+
+	fn png(image: GrayImage) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		DynamicImage::ImageLuma8(image)
+			.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+			.unwrap();
+		bytes
+	}
+
+	fn barcode_png(code: &str) -> Vec<u8> {
+		let modules = ean_13_modules(code);
+		let width = (modules.len() as u32 + QUIET_MODULES * 2) * MODULE_WIDTH;
+		let image = GrayImage::from_fn(width, 80, |x, _| {
+			let module = (x / MODULE_WIDTH).checked_sub(QUIET_MODULES);
+			match module.and_then(|module| modules.get(module as usize)) {
+				Some(true) => Luma([0]),
+				_ => Luma([255]),
+			}
+		});
+		png(image)
+	}
+
+	#[test]
+	fn decode_reads_drawn_ean_13() {
+		let codes = decode_ean_13(&barcode_png("9784101010014")).unwrap();
+		assert_eq!(codes, vec!["9784101010014".to_string()]);
+	}
+
+	#[test]
+	fn decode_rejects_bad_check_digit() {
+		let err = decode_ean_13(&barcode_png("9784101010015")).unwrap_err();
+		assert_eq!(err, BarcodeError::NotFound);
+	}
+
+	#[test]
+	fn decode_reports_missing_barcode_as_unprocessable() {
+		let err = decode_ean_13(&png(GrayImage::from_pixel(300, 80, Luma([255])))).unwrap_err();
+		assert_eq!(err, BarcodeError::NotFound);
+		assert_eq!(err.into_response().status(), StatusCode::UNPROCESSABLE_ENTITY);
+	}
+
+	#[test]
+	fn decode_rejects_oversized_and_unsupported_images() {
+		let oversized = png(GrayImage::from_pixel(MAX_IMAGE_SIDE + 1, 1, Luma([255])));
+		assert!(matches!(decode_ean_13(&oversized), Err(BarcodeError::InvalidImage(_))));
+		assert_eq!(decode_ean_13(b"GIF89a"), Err(BarcodeError::UnsupportedFormat));
+	}
+}
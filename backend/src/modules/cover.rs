@@ -0,0 +1,198 @@
+use axum::{
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use chrono::Utc;
+use std::io::Cursor;
+
+use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageFormat, ImageReader, Limits};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::repos::book::BookRepository;
+use crate::storage::SharedBlobStore;
+
+// 取得元から取り寄せる画像の大きさの上限
+const DOWNLOAD_LIMIT: u64 = 10 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 85;
+// 読み込む画像の縦横と、展開に使うメモリの上限
+const MAX_IMAGE_SIDE: u32 = 8000;
+const MAX_IMAGE_ALLOC: u64 = 256 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverSize {
+	Small,
+	#[default]
+	Medium,
+	Large,
+}
+
+impl CoverSize {
+	pub const ALL: [CoverSize; 3] = [CoverSize::Small, CoverSize::Medium, CoverSize::Large];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			CoverSize::Small => "small",
+			CoverSize::Medium => "medium",
+			CoverSize::Large => "large",
+		}
+	}
+
+	// 縦横比を保ったまま収める幅と高さ
+	fn bounds(&self) -> (u32, u32) {
+		match self {
+			CoverSize::Small => (100, 150),
+			CoverSize::Medium => (200, 300),
+			CoverSize::Large => (400, 600),
+		}
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum CoverError {
+	#[error("image must be JPEG or PNG")]
+	UnsupportedFormat,
+	#[error("image could not be decoded: {0}")]
+	InvalidImage(String),
+	#[error("cover could not be downloaded: {0}")]
+	Unavailable(String),
+	#[error("Unexpected Error: [{0}]")]
+	Unexpected(String),
+}
+
+impl CoverError {
+	fn code(&self) -> &'static str {
+		match self {
+			CoverError::UnsupportedFormat => "unsupported_format",
+			CoverError::InvalidImage(_) => "invalid_image",
+			CoverError::Unavailable(_) => "cover_unavailable",
+			CoverError::Unexpected(_) => "unexpected",
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct CoverErrorBody {
+	error: &'static str,
+	message: String,
+}
+
+impl IntoResponse for CoverError {
+	fn into_response(self) -> Response {
+		let status = match self {
+			CoverError::UnsupportedFormat | CoverError::InvalidImage(_) => StatusCode::UNPROCESSABLE_ENTITY,
+			CoverError::Unavailable(_) => StatusCode::BAD_GATEWAY,
+			CoverError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+		};
+		let body = CoverErrorBody {
+			error: self.code(),
+			message: self.to_string(),
+		};
+		(status, Json(body)).into_response()
+	}
+}
+
+pub fn cover_key(user_id: &str, isbn_13: &str, size: CoverSize) -> String {
+	format!("covers/{}/{}/{}.jpg", user_id, isbn_13, size.name())
+}
+
+// JPEG・PNGの画像から大きさごとのJPEGを作る。元の画像より大きくはしない
+pub fn make_thumbnails(bytes: &[u8]) -> Result<Vec<(CoverSize, Vec<u8>)>, CoverError> {
+	let format = match image::guess_format(bytes) {
+		Ok(format @ (ImageFormat::Jpeg | ImageFormat::Png)) => format,
+		_ => return Err(CoverError::UnsupportedFormat),
+	};
+	let mut limits = Limits::default();
+	limits.max_image_width = Some(MAX_IMAGE_SIDE);
+	limits.max_image_height = Some(MAX_IMAGE_SIDE);
+	limits.max_alloc = Some(MAX_IMAGE_ALLOC);
+	let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+	reader.limits(limits);
+	let original = reader.decode().map_err(|err| CoverError::InvalidImage(err.to_string()))?;
+
+	CoverSize::ALL
+		.iter()
+		.map(|size| {
+			let (width, height) = size.bounds();
+			let resized = match original.width() > width || original.height() > height {
+				true => original.thumbnail(width, height),
+				false => original.clone(),
+			};
+			// JPEGは透過を持てないので色だけにする
+			let rgb = DynamicImage::ImageRgb8(resized.to_rgb8());
+			let mut encoded = Vec::new();
+			rgb
+				.write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY))
+				.map_err(|err| CoverError::Unexpected(err.to_string()))?;
+			Ok((*size, encoded))
+		})
+		.collect()
+}
+
+// 取得元の表紙の画像を取り寄せる
+pub async fn download_cover(client: &reqwest::Client, image_url: &str) -> Result<Vec<u8>, CoverError> {
+	// Google Booksの画像はhttpで返ってくるが、httpsでも取れる
+	let url = match image_url.strip_prefix("http://") {
+		Some(rest) => format!("https://{}", rest),
+		None => image_url.to_string(),
+	};
+	let mut res = client
+		.get(&url)
+		.send()
+		.await
+		.and_then(|res| res.error_for_status())
+		.map_err(|err| CoverError::Unavailable(err.to_string()))?;
+	if res.content_length().is_some_and(|length| length > DOWNLOAD_LIMIT) {
+		return Err(CoverError::InvalidImage("image is too large".to_string()));
+	}
+	// Content-Lengthが無い・偽っている場合もあるので、読みながら大きさを確かめる
+	let mut bytes = Vec::new();
+	while let Some(chunk) = res
+		.chunk()
+		.await
+		.map_err(|err| CoverError::Unavailable(err.to_string()))?
+	{
+		if bytes.len() as u64 + chunk.len() as u64 > DOWNLOAD_LIMIT {
+			return Err(CoverError::InvalidImage("image is too large".to_string()));
+		}
+		bytes.extend_from_slice(&chunk);
+	}
+
+	Ok(bytes)
+}
+
+// 縮小した表紙を保存し、本に保存した日時を記録する
+pub async fn save_cover<T: BookRepository>(
+	book_repos: &T,
+	blob_store: &SharedBlobStore,
+	user_id: &str,
+	isbn_13: &str,
+	bytes: Vec<u8>,
+) -> Result<(), CoverError> {
+	// 画像の展開と縮小は重いのでブロッキング用のスレッドで行う
+	let thumbnails = tokio::task::spawn_blocking(move || make_thumbnails(&bytes))
+		.await
+		.map_err(|err| CoverError::Unexpected(err.to_string()))??;
+	for (size, encoded) in thumbnails {
+		blob_store
+			.put(&cover_key(user_id, isbn_13, size), &encoded)
+			.await
+			.map_err(|err| CoverError::Unexpected(err.to_string()))?;
+	}
+
+	book_repos
+		.update_cover(user_id, isbn_13, Some(Utc::now()))
+		.await
+		.map_err(|err| CoverError::Unexpected(err.to_string()))
+}
+
+// 本を消したときに保存した表紙も消す
+pub async fn delete_cover(blob_store: &SharedBlobStore, user_id: &str, isbn_13: &str) {
+	for size in CoverSize::ALL {
+		if let Err(err) = blob_store.delete(&cover_key(user_id, isbn_13, size)).await {
+			println!("{}", err);
+		}
+	}
+}
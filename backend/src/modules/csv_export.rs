@@ -0,0 +1,169 @@
+use crate::entity::isbn::Isbn13;
+use crate::modules::csv_import::{GOODREADS_ABANDONED_SHELF, GOODREADS_COLUMNS};
+use crate::repos::book::BookInfo;
+use crate::repos::memo::Memo;
+use crate::repos::reading::ReadingStatus;
+
+const GOODREADS_DATE_FORMAT: &str = "%Y/%m/%d";
+
+// Goodreadsの排他的な棚。中断は対応する棚が無いので独自の棚にする
+fn goodreads_shelf(status: ReadingStatus) -> &'static str {
+	match status {
+		ReadingStatus::WantToRead => "to-read",
+		ReadingStatus::Reading => "currently-reading",
+		ReadingStatus::Finished => "read",
+		ReadingStatus::Abandoned => GOODREADS_ABANDONED_SHELF,
+	}
+}
+
+// GoodreadsはISBNが数値として扱われないように="..."で囲んで書き出す
+fn quote_isbn(isbn: &str) -> String {
+	format!("=\"{}\"", isbn)
+}
+
+// 本1冊分の行。メモは感想と区別できないので、すべてPrivate Notesにまとめる
+fn goodreads_record(book_info: &BookInfo, memos: &[Memo]) -> Vec<String> {
+	let isbn = Isbn13::parse(&book_info.isbn_13).ok();
+	let (isbn_13, isbn_10) = match &isbn {
+		Some(isbn) if !isbn.is_internal() => (isbn.as_str().to_string(), isbn.to_isbn_10().unwrap_or_default()),
+		_ => (String::new(), String::new()),
+	};
+	// Goodreadsでは0が評価なし
+	let rating = book_info.rating.unwrap_or(0).to_string();
+	let notes = memos
+		.iter()
+		.map(|memo| memo.text.trim())
+		.collect::<Vec<_>>()
+		.join("\n\n");
+
+	let shelf = goodreads_shelf(book_info.status);
+	let bookshelves = std::iter::once(shelf)
+		.chain(book_info.tags.iter().map(|tag| tag.as_str()))
+		.collect::<Vec<_>>()
+		.join(", ");
+	let author = book_info.authors.first().cloned().unwrap_or_default();
+	let year = book_info
+		.published_date
+		.get(..4)
+		.filter(|year| year.chars().all(|c| c.is_ascii_digit()))
+		.unwrap_or_default();
+	let date_read = match book_info.status {
+		ReadingStatus::Finished => book_info
+			.finished_on
+			.map(|date| date.format(GOODREADS_DATE_FORMAT).to_string())
+			.unwrap_or_default(),
+		_ => String::new(),
+	};
+	let date_added = book_info
+		.created_at
+		.map(|created_at| created_at.format(GOODREADS_DATE_FORMAT).to_string())
+		.unwrap_or_default();
+	let read_count = match book_info.status {
+		ReadingStatus::Finished => "1",
+		_ => "0",
+	};
+
+	vec![
+		// Book IdはGoodreads内の識別子なので空にする
+		String::new(),
+		book_info.title.clone(),
+		author.clone(),
+		// 日本語の著者名は姓名の順で持っているのでそのまま使う
+		author,
+		book_info.authors.iter().skip(1).cloned().collect::<Vec<_>>().join(", "),
+		quote_isbn(&isbn_10),
+		quote_isbn(&isbn_13),
+		rating,
+		String::new(),
+		book_info.publisher.clone(),
+		String::new(),
+		book_info.page_count.map(|count| count.to_string()).unwrap_or_default(),
+		year.to_string(),
+		year.to_string(),
+		date_read,
+		date_added,
+		bookshelves,
+		String::new(),
+		shelf.to_string(),
+		String::new(),
+		String::new(),
+		notes,
+		read_count.to_string(),
+		"0".to_string(),
+	]
+}
+
+// 蔵書をGoodreadsのエクスポートと同じ列の並びのCSVにする
+pub fn write_goodreads(library: &[(BookInfo, Vec<Memo>)]) -> Result<Vec<u8>, String> {
+	let mut writer = csv::Writer::from_writer(Vec::new());
+	writer
+		.write_record(GOODREADS_COLUMNS)
+		.map_err(|err| err.to_string())?;
+	for (book_info, memos) in library {
+		writer
+			.write_record(goodreads_record(book_info, memos))
+			.map_err(|err| err.to_string())?;
+	}
+
+	writer.into_inner().map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::modules::csv_import::{parse_reading_log, ImportFormat};
+	use chrono::{NaiveDate, TimeZone, Utc};
+
+	fn book(isbn_13: &str, status: ReadingStatus, rating: Option<i16>, tags: &[&str]) -> BookInfo {
+		BookInfo {
+			isbn_13: isbn_13.to_string(),
+			title: format!("本 {}", isbn_13),
+			authors: vec!["夏目 漱石".to_string()],
+			status,
+			rating,
+			tags: tags.iter().map(|tag| tag.to_string()).collect(),
+			created_at: Utc.with_ymd_and_hms(2024, 1, 5, 10, 0, 0).single(),
+			finished_on: NaiveDate::from_ymd_opt(2024, 1, 20),
+			..Default::default()
+		}
+	}
+
+	fn memo(text: &str) -> Memo {
+		let created_at = Utc.with_ymd_and_hms(2024, 1, 6, 10, 0, 0).unwrap();
+		Memo {
+			id: "memo".to_string(),
+			isbn_13: String::new(),
+			text: text.to_string(),
+			created_at,
+			updated_at: created_at,
+			tags: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn goodreads_export_round_trips_through_import() {
+		let library = vec![
+			(book("9784101010014", ReadingStatus::Finished, Some(4), &["名作"]), vec![memo("一つ目\n二行目"), memo("二つ目")]),
+			(book("9784003101018", ReadingStatus::Reading, None, &[]), Vec::new()),
+			(book("9784101010021", ReadingStatus::WantToRead, None, &["積読", "文庫"]), Vec::new()),
+			(book("9784101010038", ReadingStatus::Abandoned, Some(2), &[]), Vec::new()),
+		];
+
+		let csv = write_goodreads(&library).unwrap();
+		let parsed = parse_reading_log(&csv, None).unwrap();
+		assert_eq!(parsed.format, ImportFormat::Goodreads);
+		assert_eq!(parsed.rows.len(), library.len());
+		for (row, (book_info, _)) in parsed.rows.iter().zip(&library) {
+			assert_eq!(row.isbn_13.as_ref().map(|isbn| isbn.as_str()), Ok(book_info.isbn_13.as_str()));
+			assert_eq!(row.title, book_info.title);
+			assert_eq!(row.status, Some(book_info.status));
+			assert_eq!(row.rating, book_info.rating);
+			assert_eq!(row.tags, book_info.tags);
+			assert_eq!(row.registered_on, NaiveDate::from_ymd_opt(2024, 1, 5));
+			assert!(row.warnings.is_empty(), "{:?}", row.warnings);
+		}
+		assert_eq!(parsed.rows[0].finished_on, NaiveDate::from_ymd_opt(2024, 1, 20));
+		assert_eq!(parsed.rows[0].memos, vec!["一つ目\n二行目\n\n二つ目".to_string()]);
+		assert_eq!(parsed.rows[3].finished_on, None);
+	}
+}
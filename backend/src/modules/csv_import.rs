@@ -0,0 +1,496 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::entity::isbn::Isbn13;
+use crate::repos::reading::ReadingStatus;
+
+// ブクログのエクスポートは見出しが無く、列の並びが決まっている
+const BOOKLOG_MIN_COLUMNS: usize = 12;
+const BOOKLOG_ASIN: usize = 1;
+const BOOKLOG_ISBN: usize = 2;
+const BOOKLOG_RATING: usize = 4;
+const BOOKLOG_STATUS: usize = 5;
+const BOOKLOG_REVIEW: usize = 6;
+const BOOKLOG_NOTE: usize = 8;
+const BOOKLOG_REGISTERED: usize = 9;
+const BOOKLOG_FINISHED: usize = 10;
+const BOOKLOG_TITLE: usize = 11;
+
+// Goodreadsに中断の棚は無いので、書き出すときに使う独自の棚
+pub const GOODREADS_ABANDONED_SHELF: &str = "abandoned";
+
+// Goodreadsのエクスポートの列。書き出すときもこの並びにする
+pub const GOODREADS_COLUMNS: [&str; 24] = [
+	"Book Id",
+	"Title",
+	"Author",
+	"Author l-f",
+	"Additional Authors",
+	"ISBN",
+	"ISBN13",
+	"My Rating",
+	"Average Rating",
+	"Publisher",
+	"Binding",
+	"Number of Pages",
+	"Year Published",
+	"Original Publication Year",
+	"Date Read",
+	"Date Added",
+	"Bookshelves",
+	"Bookshelves with positions",
+	"Exclusive Shelf",
+	"My Review",
+	"Spoiler",
+	"Private Notes",
+	"Read Count",
+	"Owned Copies",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+	Booklog,
+	Bookmeter,
+	Goodreads,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextEncoding {
+	Utf8,
+	ShiftJis,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ImportError {
+	#[error("file is empty")]
+	Empty,
+	#[error("file is not a known CSV export")]
+	UnknownFormat,
+	#[error("CSV could not be read: {0}")]
+	InvalidCsv(String),
+	#[error("required column {0} is missing")]
+	MissingColumn(&'static str),
+}
+
+// 1行分の取り込む内容。ISBNに変換できなかった行も報告するために残す
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+	// ファイル上の行番号（1始まり）
+	pub line: u64,
+	pub isbn_13: Result<Isbn13, String>,
+	pub title: String,
+	pub status: Option<ReadingStatus>,
+	// 5段階。0や空は評価なし
+	pub rating: Option<i16>,
+	pub registered_on: Option<NaiveDate>,
+	pub finished_on: Option<NaiveDate>,
+	// メモとして登録する本文
+	pub memos: Vec<String>,
+	pub tags: Vec<String>,
+	// 取り込めるが読み取れなかった値
+	pub warnings: Vec<String>,
+}
+
+impl ImportRow {
+	// CSVとして読めなかった行
+	fn malformed(err: &csv::Error) -> Self {
+		ImportRow {
+			line: err.position().map(|position| position.line()).unwrap_or_default(),
+			isbn_13: Err(format!("malformed row: {}", err)),
+			title: String::new(),
+			status: None,
+			rating: None,
+			registered_on: None,
+			finished_on: None,
+			memos: Vec::new(),
+			tags: Vec::new(),
+			warnings: Vec::new(),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct ParsedImport {
+	pub format: ImportFormat,
+	pub encoding: TextEncoding,
+	pub rows: Vec<ImportRow>,
+}
+
+// UTF-8として読めなければShift_JIS（CP932）として読む
+pub fn decode_text(bytes: &[u8]) -> (String, TextEncoding) {
+	let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+	match std::str::from_utf8(bytes) {
+		Ok(text) => (text.to_string(), TextEncoding::Utf8),
+		Err(_) => {
+			let (text, _, _) = encoding_rs::SHIFT_JIS.decode(bytes);
+			(text.into_owned(), TextEncoding::ShiftJis)
+		}
+	}
+}
+
+// 2019-01-05 12:34:56 や 2019/01/05 の日付部分を読む
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+	let date: String = value.trim().chars().take(10).collect();
+	NaiveDate::parse_from_str(&date.replace('/', "-"), "%Y-%m-%d").ok()
+}
+
+fn parse_isbn(candidates: &[&str]) -> Result<Isbn13, String> {
+	let candidates: Vec<&str> = candidates
+		.iter()
+		.map(|candidate| candidate.trim())
+		.filter(|candidate| !candidate.is_empty())
+		.collect();
+	if candidates.is_empty() {
+		return Err("row has no ISBN".to_string());
+	}
+	// ASINは紙の本ならISBN-10と同じになる
+	let mut last_error = String::new();
+	for candidate in candidates {
+		match Isbn13::parse(candidate) {
+			Ok(isbn_13) if !isbn_13.is_internal() => return Ok(isbn_13),
+			Ok(_) => last_error = format!("'{}' is not an ISBN", candidate),
+			Err(err) => last_error = format!("'{}': {}", candidate, err),
+		}
+	}
+
+	Err(last_error)
+}
+
+fn parse_rating(value: &str) -> Option<i16> {
+	value.trim().parse::<i16>().ok().filter(|rating| (1..=5).contains(rating))
+}
+
+fn booklog_status(value: &str) -> Option<ReadingStatus> {
+	match value.trim() {
+		"読みたい" | "積読" => Some(ReadingStatus::WantToRead),
+		"いま読んでる" => Some(ReadingStatus::Reading),
+		"読み終わった" => Some(ReadingStatus::Finished),
+		_ => None,
+	}
+}
+
+fn bookmeter_status(value: &str) -> Option<ReadingStatus> {
+	match value.trim() {
+		"読みたい本" | "積読本" => Some(ReadingStatus::WantToRead),
+		"読んでる本" => Some(ReadingStatus::Reading),
+		"読んだ本" => Some(ReadingStatus::Finished),
+		_ => None,
+	}
+}
+
+fn goodreads_status(value: &str) -> Option<ReadingStatus> {
+	match value.trim() {
+		"to-read" => Some(ReadingStatus::WantToRead),
+		"currently-reading" => Some(ReadingStatus::Reading),
+		"read" => Some(ReadingStatus::Finished),
+		GOODREADS_ABANDONED_SHELF => Some(ReadingStatus::Abandoned),
+		_ => None,
+	}
+}
+
+// 感想のメモ。評価は本の項目に入れるのでメモには書かない
+fn review_memo(review: &str) -> Option<String> {
+	Some(review.trim().to_string()).filter(|review| !review.is_empty())
+}
+
+fn field(record: &csv::StringRecord, index: usize) -> &str {
+	record.get(index).unwrap_or_default()
+}
+
+type CsvRecord = Result<csv::StringRecord, csv::Error>;
+
+// 読めなかった行も行ごとに報告するため、エラーのまま残す
+fn read_records(text: &str) -> Vec<CsvRecord> {
+	// csvの行番号は\r\nを1行と数えないので、改行を\nに揃えてから読む
+	let text = text.replace("\r\n", "\n").replace('\r', "\n");
+	let mut reader = csv::ReaderBuilder::new()
+		.has_headers(false)
+		.flexible(true)
+		.from_reader(text.as_bytes());
+
+	// 空行だけのレコードは読み飛ばす
+	reader
+		.records()
+		.filter(|record| match record {
+			Ok(record) => record.iter().any(|value| !value.trim().is_empty()),
+			Err(_) => true,
+		})
+		.collect()
+}
+
+// 見出しの行を取り出す。見出しが読めなければ列を決められないので取り込めない
+fn split_header(records: &[CsvRecord]) -> Result<(&csv::StringRecord, &[CsvRecord]), ImportError> {
+	match records.split_first() {
+		Some((Ok(header), records)) => Ok((header, records)),
+		Some((Err(err), _)) => Err(ImportError::InvalidCsv(err.to_string())),
+		None => Err(ImportError::Empty),
+	}
+}
+
+fn line_of(record: &csv::StringRecord) -> u64 {
+	record.position().map(|position| position.line()).unwrap_or_default()
+}
+
+fn detect_format(records: &[CsvRecord]) -> Result<ImportFormat, ImportError> {
+	let first = match records.first() {
+		Some(Ok(first)) => first,
+		Some(Err(err)) => return Err(ImportError::InvalidCsv(err.to_string())),
+		None => return Err(ImportError::Empty),
+	};
+	if first.iter().any(|value| value.trim() == "Book Id") && first.iter().any(|value| value.trim() == "ISBN13") {
+		return Ok(ImportFormat::Goodreads);
+	}
+	let is_header = first
+		.iter()
+		.any(|value| matches!(value.trim(), "タイトル" | "書名" | "ISBN" | "ASIN"));
+	if is_header {
+		return Ok(ImportFormat::Bookmeter);
+	}
+	// ブクログは先頭の列がサービスIDの数字
+	if first.len() >= BOOKLOG_MIN_COLUMNS && field(first, 0).trim().chars().all(|c| c.is_ascii_digit()) {
+		return Ok(ImportFormat::Booklog);
+	}
+
+	Err(ImportError::UnknownFormat)
+}
+
+fn parse_booklog(records: &[CsvRecord]) -> Vec<ImportRow> {
+	records
+		.iter()
+		.map(|record| {
+			let record = match record {
+				Ok(record) => record,
+				Err(err) => return ImportRow::malformed(err),
+			};
+			let rating = parse_rating(field(record, BOOKLOG_RATING));
+			let memos = [
+				review_memo(field(record, BOOKLOG_REVIEW)),
+				Some(field(record, BOOKLOG_NOTE).trim().to_string()).filter(|note| !note.is_empty()),
+			]
+			.into_iter()
+			.flatten()
+			.collect();
+			ImportRow {
+				line: line_of(record),
+				isbn_13: parse_isbn(&[field(record, BOOKLOG_ISBN), field(record, BOOKLOG_ASIN)]),
+				title: field(record, BOOKLOG_TITLE).trim().to_string(),
+				status: booklog_status(field(record, BOOKLOG_STATUS)),
+				rating,
+				registered_on: parse_date(field(record, BOOKLOG_REGISTERED)),
+				finished_on: parse_date(field(record, BOOKLOG_FINISHED)),
+				memos,
+				tags: Vec::new(),
+				warnings: Vec::new(),
+			}
+		})
+		.collect()
+}
+
+// 見出しの名前から列の位置を探す
+fn column(header: &csv::StringRecord, names: &[&str]) -> Option<usize> {
+	header.iter().position(|value| names.contains(&value.trim()))
+}
+
+fn parse_bookmeter(records: &[CsvRecord]) -> Result<Vec<ImportRow>, ImportError> {
+	let (header, records) = split_header(records)?;
+	let isbn = column(header, &["ISBN", "ISBN13", "ISBN/ASIN"]);
+	let asin = column(header, &["ASIN"]);
+	if isbn.is_none() && asin.is_none() {
+		return Err(ImportError::MissingColumn("ISBN"));
+	}
+	let title = column(header, &["タイトル", "書名"]);
+	let status = column(header, &["状態", "ステータス", "種別"]);
+	let rating = column(header, &["評価"]);
+	let review = column(header, &["感想", "レビュー"]);
+	let registered = column(header, &["登録日"]);
+	let finished = column(header, &["読了日", "読んだ日"]);
+
+	let get = |record: &csv::StringRecord, index: Option<usize>| -> String {
+		index.map(|index| field(record, index).trim().to_string()).unwrap_or_default()
+	};
+	Ok(records
+		.iter()
+		.map(|record| {
+			let record = match record {
+				Ok(record) => record,
+				Err(err) => return ImportRow::malformed(err),
+			};
+			let finished_on = parse_date(&get(record, finished));
+			let rating = parse_rating(&get(record, rating));
+			// 状態の列が無いエクスポートは読んだ本だけが並ぶ
+			let status = match status {
+				Some(_) => bookmeter_status(&get(record, status)),
+				None if finished_on.is_some() => Some(ReadingStatus::Finished),
+				None => None,
+			};
+			ImportRow {
+				line: line_of(record),
+				isbn_13: parse_isbn(&[&get(record, isbn), &get(record, asin)]),
+				title: get(record, title),
+				status,
+				rating,
+				registered_on: parse_date(&get(record, registered)),
+				finished_on,
+				memos: review_memo(&get(record, review)).into_iter().collect(),
+				tags: Vec::new(),
+				warnings: Vec::new(),
+			}
+		})
+		.collect())
+}
+
+// Excel向けに ="9784101010014" の形で書かれたISBNから中身を取り出す
+fn unquote_isbn(value: &str) -> &str {
+	value.trim().trim_start_matches('=').trim_matches('"')
+}
+
+fn parse_goodreads(records: &[CsvRecord]) -> Result<Vec<ImportRow>, ImportError> {
+	let (header, records) = split_header(records)?;
+	let index = |name: &'static str| column(header, &[name]).ok_or(ImportError::MissingColumn(name));
+	let isbn_13 = index("ISBN13")?;
+	let isbn = index("ISBN")?;
+	let title = index("Title")?;
+	let rating = column(header, &["My Rating"]);
+	let date_read = column(header, &["Date Read"]);
+	let date_added = column(header, &["Date Added"]);
+	let shelves = column(header, &["Bookshelves"]);
+	let exclusive_shelf = column(header, &["Exclusive Shelf"]);
+	let review = column(header, &["My Review"]);
+	let notes = column(header, &["Private Notes"]);
+
+	let get = |record: &csv::StringRecord, index: Option<usize>| -> String {
+		index.map(|index| field(record, index).trim().to_string()).unwrap_or_default()
+	};
+	Ok(records
+		.iter()
+		.map(|record| {
+			let record = match record {
+				Ok(record) => record,
+				Err(err) => return ImportRow::malformed(err),
+			};
+			let mut warnings = Vec::new();
+			let shelf = get(record, exclusive_shelf);
+			let status = goodreads_status(&shelf);
+			if status.is_none() && !shelf.is_empty() {
+				warnings.push(format!("unknown exclusive shelf '{}'", shelf));
+			}
+			let mut date = |name: &str, index: Option<usize>| {
+				let value = get(record, index);
+				let date = parse_date(&value);
+				if date.is_none() && !value.is_empty() {
+					warnings.push(format!("{} '{}' is not a date", name, value));
+				}
+				date
+			};
+			let finished_on = date("Date Read", date_read);
+			let registered_on = date("Date Added", date_added);
+			let rating_value = get(record, rating);
+			let rating = parse_rating(&rating_value);
+			if rating.is_none() && !matches!(rating_value.as_str(), "" | "0") {
+				warnings.push(format!("My Rating '{}' is not between 1 and 5", rating_value));
+			}
+			// 読書状況を表す棚はタグにしない
+			let tags = get(record, shelves)
+				.split(',')
+				.map(str::trim)
+				.filter(|name| !name.is_empty() && goodreads_status(name).is_none() && *name != shelf)
+				.map(str::to_string)
+				.collect();
+			let memos = [
+				// Goodreadsの感想はHTMLの改行を含む
+				review_memo(&get(record, review).replace("<br/>", "\n").replace("<br />", "\n")),
+				Some(get(record, notes)).filter(|notes| !notes.is_empty()),
+			]
+			.into_iter()
+			.flatten()
+			.collect();
+
+			ImportRow {
+				line: line_of(record),
+				isbn_13: parse_isbn(&[
+					unquote_isbn(field(record, isbn_13)),
+					unquote_isbn(field(record, isbn)),
+				]),
+				title: field(record, title).trim().to_string(),
+				status,
+				rating,
+				registered_on,
+				finished_on,
+				memos,
+				tags,
+				warnings,
+			}
+		})
+		.collect())
+}
+
+// ブクログ・読書メーター・GoodreadsのCSVを読む。formatを省略した場合は中身から判別する
+pub fn parse_reading_log(bytes: &[u8], format: Option<ImportFormat>) -> Result<ParsedImport, ImportError> {
+	let (text, encoding) = decode_text(bytes);
+	let records = read_records(&text);
+	let format = match format {
+		Some(format) => format,
+		None => detect_format(&records)?,
+	};
+	let rows = match format {
+		ImportFormat::Booklog => parse_booklog(&records),
+		ImportFormat::Bookmeter => parse_bookmeter(&records)?,
+		ImportFormat::Goodreads => parse_goodreads(&records)?,
+	};
+
+	Ok(ParsedImport { format, encoding, rows })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn lines(parsed: &ParsedImport) -> Vec<u64> {
+		parsed.rows.iter().map(|row| row.line).collect()
+	}
+
+	#[test]
+	fn booklog_crlf_rows_have_their_own_lines() {
+		let text = [
+			"1,4101010013,9784101010014,,4,読み終わった,,,,2024-01-05 10:00:00,2024-01-20 10:00:00,こころ",
+			"2,4003101014,9784003101018,,,積読,,,,2024-02-01 10:00:00,,坊っちゃん",
+		]
+		.join("\r\n");
+		let (bytes, _, _) = encoding_rs::SHIFT_JIS.encode(&text);
+		let parsed = parse_reading_log(&bytes, None).unwrap();
+		assert_eq!(parsed.format, ImportFormat::Booklog);
+		assert_eq!(parsed.encoding, TextEncoding::ShiftJis);
+		assert_eq!(lines(&parsed), vec![1, 2]);
+	}
+
+	#[test]
+	fn goodreads_crlf_rows_start_after_the_header() {
+		let text = [
+			"Book Id,Title,Author,ISBN,ISBN13,My Rating,Exclusive Shelf",
+			"1,Kokoro,Natsume Soseki,\"=\"\"4101010013\"\"\",\"=\"\"9784101010014\"\"\",4,read",
+			"2,Botchan,Natsume Soseki,\"=\"\"\"\"\",\"=\"\"9784003101018\"\"\",0,to-read",
+		]
+		.join("\r\n");
+		let parsed = parse_reading_log(text.as_bytes(), None).unwrap();
+		assert_eq!(parsed.format, ImportFormat::Goodreads);
+		assert_eq!(lines(&parsed), vec![2, 3]);
+	}
+
+	#[test]
+	fn rating_is_kept_out_of_the_review_memo() {
+		let text = [
+			"Book Id,Title,Author,ISBN,ISBN13,My Rating,Exclusive Shelf,My Review",
+			"1,Kokoro,Natsume Soseki,,9784101010014,4,read,Good<br/>book",
+			"2,Botchan,Natsume Soseki,,9784003101018,0,to-read,",
+		]
+		.join("\n");
+		let parsed = parse_reading_log(text.as_bytes(), None).unwrap();
+		assert_eq!(parsed.rows[0].rating, Some(4));
+		assert_eq!(parsed.rows[0].memos, vec!["Good\nbook".to_string()]);
+		assert_eq!(parsed.rows[1].rating, None);
+		assert!(parsed.rows[1].memos.is_empty());
+	}
+}
@@ -0,0 +1,424 @@
+use axum::{
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use serde::Serialize;
+
+use crate::repos::reading::ReadingStatus;
+
+// 括弧やNOTを入れ子にできる深さ。再帰で解析するので深すぎる式はスタックを溢れさせる
+const MAX_DEPTH: usize = 32;
+
+// 本棚の条件式
+//
+//   finished in 2026 AND tag:philosophy AND pages>=300 AND rating>=4
+//   (status:reading OR status:want_to_read) NOT author:"村上 春樹"
+//
+// 並べた条件はANDとして扱う。AND・OR・NOT・INは大文字小文字を区別しない
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+	And(Box<FilterExpr>, Box<FilterExpr>),
+	Or(Box<FilterExpr>, Box<FilterExpr>),
+	Not(Box<FilterExpr>),
+	Tag(String),
+	Status(ReadingStatus),
+	Contains(TextField, String),
+	Year(DateField, i32),
+	Compare(NumberField, CompareOp, i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextField {
+	Title,
+	Author,
+	Publisher,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateField {
+	Added,
+	Started,
+	Finished,
+	Published,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberField {
+	Pages,
+	Rating,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+impl CompareOp {
+	pub fn operator(&self) -> &'static str {
+		match self {
+			CompareOp::Eq => "=",
+			CompareOp::Ne => "<>",
+			CompareOp::Lt => "<",
+			CompareOp::Le => "<=",
+			CompareOp::Gt => ">",
+			CompareOp::Ge => ">=",
+		}
+	}
+}
+
+// 何文字目で解析に失敗したか（0始まり）
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ParseError {
+	pub message: String,
+	pub position: usize,
+}
+
+#[derive(Serialize)]
+struct ParseErrorBody<'a> {
+	error: &'static str,
+	message: &'a str,
+	position: usize,
+}
+
+impl IntoResponse for ParseError {
+	fn into_response(self) -> Response {
+		let body = ParseErrorBody {
+			error: "invalid_expression",
+			message: &self.message,
+			position: self.position,
+		};
+		(StatusCode::BAD_REQUEST, Json(body)).into_response()
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+	LParen,
+	RParen,
+	Colon,
+	Op(CompareOp),
+	Word(String),
+	Quoted(String),
+}
+
+impl Token {
+	fn keyword(&self) -> Option<String> {
+		match self {
+			Token::Word(word) => Some(word.to_uppercase()),
+			_ => None,
+		}
+	}
+}
+
+fn error<T>(message: impl Into<String>, position: usize) -> Result<T, ParseError> {
+	Err(ParseError {
+		message: message.into(),
+		position,
+	})
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+	let chars: Vec<char> = input.chars().collect();
+	let mut tokens = Vec::new();
+	let mut i = 0;
+	while i < chars.len() {
+		let start = i;
+		match chars[i] {
+			c if c.is_whitespace() => {
+				i += 1;
+				continue;
+			}
+			'(' => {
+				tokens.push((Token::LParen, start));
+				i += 1;
+			}
+			')' => {
+				tokens.push((Token::RParen, start));
+				i += 1;
+			}
+			':' => {
+				tokens.push((Token::Colon, start));
+				i += 1;
+			}
+			'<' | '>' | '=' | '!' => {
+				let next = chars.get(i + 1).copied();
+				let (op, len) = match (chars[i], next) {
+					('<', Some('=')) => (CompareOp::Le, 2),
+					('>', Some('=')) => (CompareOp::Ge, 2),
+					('!', Some('=')) => (CompareOp::Ne, 2),
+					('<', _) => (CompareOp::Lt, 1),
+					('>', _) => (CompareOp::Gt, 1),
+					('=', _) => (CompareOp::Eq, 1),
+					_ => return error("expected '=' after '!'", start),
+				};
+				tokens.push((Token::Op(op), start));
+				i += len;
+			}
+			'"' => {
+				i += 1;
+				let mut value = String::new();
+				loop {
+					match chars.get(i) {
+						None => return error("unterminated quoted string", start),
+						Some('"') => break,
+						Some('\\') if chars.get(i + 1).is_some() => {
+							value.push(chars[i + 1]);
+							i += 2;
+						}
+						Some(c) => {
+							value.push(*c);
+							i += 1;
+						}
+					}
+				}
+				i += 1;
+				tokens.push((Token::Quoted(value), start));
+			}
+			_ => {
+				let mut word = String::new();
+				while let Some(c) = chars.get(i) {
+					if c.is_whitespace() || "():<>=!\"".contains(*c) {
+						break;
+					}
+					word.push(*c);
+					i += 1;
+				}
+				tokens.push((Token::Word(word), start));
+			}
+		}
+	}
+
+	Ok(tokens)
+}
+
+struct Parser {
+	tokens: Vec<(Token, usize)>,
+	index: usize,
+	end: usize,
+	depth: usize,
+}
+
+impl Parser {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.index).map(|(token, _)| token)
+	}
+
+	fn position(&self) -> usize {
+		self
+			.tokens
+			.get(self.index)
+			.map(|(_, position)| *position)
+			.unwrap_or(self.end)
+	}
+
+	fn next(&mut self) -> Option<(Token, usize)> {
+		let token = self.tokens.get(self.index).cloned();
+		self.index += 1;
+		token
+	}
+
+	fn peek_keyword(&self, keyword: &str) -> bool {
+		self.peek().and_then(Token::keyword).as_deref() == Some(keyword)
+	}
+
+	// 括弧やNOTの内側を解析する。深すぎる場合は入れ子を始めた位置で失敗させる
+	fn nested<T>(
+		&mut self,
+		position: usize,
+		parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+	) -> Result<T, ParseError> {
+		if self.depth >= MAX_DEPTH {
+			return error(
+				format!("expression is nested more than {} levels deep", MAX_DEPTH),
+				position,
+			);
+		}
+		self.depth += 1;
+		let result = parse(self);
+		self.depth -= 1;
+		result
+	}
+
+	fn parse_or(&mut self) -> Result<FilterExpr, ParseError> {
+		let mut expr = self.parse_and()?;
+		while self.peek_keyword("OR") {
+			self.index += 1;
+			let right = self.parse_and()?;
+			expr = FilterExpr::Or(Box::new(expr), Box::new(right));
+		}
+
+		Ok(expr)
+	}
+
+	fn parse_and(&mut self) -> Result<FilterExpr, ParseError> {
+		let mut expr = self.parse_not()?;
+		loop {
+			if self.peek_keyword("AND") {
+				self.index += 1;
+			} else if self.peek().is_none() || self.peek() == Some(&Token::RParen) || self.peek_keyword("OR") {
+				break;
+			}
+			let right = self.parse_not()?;
+			expr = FilterExpr::And(Box::new(expr), Box::new(right));
+		}
+
+		Ok(expr)
+	}
+
+	fn parse_not(&mut self) -> Result<FilterExpr, ParseError> {
+		if self.peek_keyword("NOT") {
+			let position = self.position();
+			self.index += 1;
+			let expr = self.nested(position, Self::parse_not)?;
+			return Ok(FilterExpr::Not(Box::new(expr)));
+		}
+
+		self.parse_primary()
+	}
+
+	fn parse_primary(&mut self) -> Result<FilterExpr, ParseError> {
+		let position = self.position();
+		match self.next() {
+			Some((Token::LParen, _)) => {
+				let expr = self.nested(position, Self::parse_or)?;
+				let position = self.position();
+				match self.next() {
+					Some((Token::RParen, _)) => Ok(expr),
+					_ => error("expected ')'", position),
+				}
+			}
+			Some((Token::Word(field), _)) => self.parse_condition(&field.to_lowercase(), position),
+			Some(_) => error("expected a condition", position),
+			None => error("unexpected end of expression", position),
+		}
+	}
+
+	fn parse_condition(&mut self, field: &str, field_position: usize) -> Result<FilterExpr, ParseError> {
+		let position = self.position();
+		match self.next() {
+			Some((Token::Colon, _)) => {
+				let value_position = self.position();
+				let value = self.parse_value()?;
+				match field {
+					"tag" => Ok(FilterExpr::Tag(value)),
+					"title" => Ok(FilterExpr::Contains(TextField::Title, value)),
+					"author" => Ok(FilterExpr::Contains(TextField::Author, value)),
+					"publisher" => Ok(FilterExpr::Contains(TextField::Publisher, value)),
+					"status" => serde_json::from_value::<ReadingStatus>(serde_json::Value::String(value.clone()))
+						.map(FilterExpr::Status)
+						.or_else(|_| error(format!("unknown status '{}'", value), value_position)),
+					_ => error(format!("unknown field '{}'", field), field_position),
+				}
+			}
+			Some((Token::Op(op), _)) => {
+				let number = self.parse_number()?;
+				match field {
+					"pages" => Ok(FilterExpr::Compare(NumberField::Pages, op, number)),
+					"rating" => Ok(FilterExpr::Compare(NumberField::Rating, op, number)),
+					_ => error(format!("unknown numeric field '{}'", field), field_position),
+				}
+			}
+			Some((token, _)) if token.keyword().as_deref() == Some("IN") => {
+				let year_position = self.position();
+				let year = self.parse_number()?;
+				let year = i32::try_from(year).or_else(|_| error("year is out of range", year_position))?;
+				match field {
+					"added" => Ok(FilterExpr::Year(DateField::Added, year)),
+					"started" => Ok(FilterExpr::Year(DateField::Started, year)),
+					"finished" => Ok(FilterExpr::Year(DateField::Finished, year)),
+					"published" => Ok(FilterExpr::Year(DateField::Published, year)),
+					_ => error(format!("unknown date field '{}'", field), field_position),
+				}
+			}
+			_ => error(
+				format!("expected ':', a comparison or 'in' after '{}'", field),
+				position,
+			),
+		}
+	}
+
+	fn parse_value(&mut self) -> Result<String, ParseError> {
+		let position = self.position();
+		match self.next() {
+			Some((Token::Word(value), _)) | Some((Token::Quoted(value), _)) if !value.is_empty() => Ok(value),
+			_ => error("expected a value", position),
+		}
+	}
+
+	fn parse_number(&mut self) -> Result<i64, ParseError> {
+		let position = self.position();
+		match self.next() {
+			Some((Token::Word(value), _)) => value
+				.parse::<i64>()
+				.or_else(|_| error(format!("expected a number but found '{}'", value), position)),
+			_ => error("expected a number", position),
+		}
+	}
+}
+
+pub fn parse(input: &str) -> Result<FilterExpr, ParseError> {
+	let tokens = tokenize(input)?;
+	let mut parser = Parser {
+		tokens,
+		index: 0,
+		end: input.chars().count(),
+		depth: 0,
+	};
+	let expr = parser.parse_or()?;
+	if parser.peek().is_some() {
+		return error("unexpected ')'", parser.position());
+	}
+
+	Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_rating_comparison() {
+		let expr = parse("finished in 2026 AND tag:philosophy AND rating>=4").unwrap();
+		let expected = FilterExpr::And(
+			Box::new(FilterExpr::And(
+				Box::new(FilterExpr::Year(DateField::Finished, 2026)),
+				Box::new(FilterExpr::Tag("philosophy".to_string())),
+			)),
+			Box::new(FilterExpr::Compare(NumberField::Rating, CompareOp::Ge, 4)),
+		);
+		assert_eq!(expr, expected);
+	}
+
+	#[test]
+	fn parse_rejects_deep_nesting() {
+		let input = format!("{}tag:a{}", "(".repeat(2000), ")".repeat(2000));
+		let err = parse(&input).unwrap_err();
+		assert_eq!(err.position, MAX_DEPTH);
+
+		let input = "NOT ".repeat(2000) + "tag:a";
+		let err = parse(&input).unwrap_err();
+		assert_eq!(err.position, MAX_DEPTH * 4);
+
+		let input = format!("{}tag:a{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+		assert_eq!(parse(&input).unwrap(), FilterExpr::Tag("a".to_string()));
+	}
+
+	fn assert_error(input: &str, message: &str, position: usize) {
+		let err = parse(input).unwrap_err();
+		assert_eq!((err.message.as_str(), err.position), (message, position), "{}", input);
+	}
+
+	#[test]
+	fn parse_reports_where_it_failed() {
+		assert_error("(tag:a", "expected ')'", 6);
+		assert_error("tag:a AND color:red", "unknown field 'color'", 10);
+		assert_error("pages !3", "expected '=' after '!'", 6);
+		assert_error("tag:a )", "unexpected ')'", 6);
+	}
+}
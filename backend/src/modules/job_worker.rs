@@ -0,0 +1,157 @@
+use chrono::Utc;
+use tokio::time::{sleep, Duration};
+
+use crate::modules::cover::{download_cover, save_cover, CoverError};
+use crate::provider::{ProviderError, SharedProvider};
+use crate::repos::book::{BookInfo, BookRepository};
+use crate::repos::job::{Job, JobKind, JobRepository};
+use crate::repos::RepositoryError;
+use crate::storage::SharedBlobStore;
+
+// 取り出すジョブが無いときに待つ時間
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// 再試行までの待ち時間。失敗するたびに倍にして上限で止める
+const RETRY_BASE_SECS: i64 = 30;
+const RETRY_MAX_SECS: i64 = 60 * 60;
+
+// ジョブを処理した結果
+enum JobOutcome {
+	Done,
+	// 再試行しても結果が変わらない失敗
+	Failed(String),
+	// 時間をおけば成功するかもしれない失敗
+	Retry(String),
+}
+
+// n回目の失敗の後に待つ秒数
+fn retry_delay(attempts: i32) -> i64 {
+	let exponent = (attempts - 1).clamp(0, 16) as u32;
+	(RETRY_BASE_SECS * 2i64.pow(exponent)).min(RETRY_MAX_SECS)
+}
+
+// 登録した本に表紙の画像があれば、取り寄せるジョブを積む
+pub async fn enqueue_cover<J: JobRepository>(job_repos: &J, user_id: &str, book_info: &BookInfo) {
+	if book_info.image_url.is_empty() {
+		return;
+	}
+	// 表紙が無くても登録は済んでいるので、失敗しても登録は失敗にしない
+	if let Err(err) = job_repos
+		.enqueue(user_id, JobKind::FetchCover, &book_info.isbn_13)
+		.await
+	{
+		println!("{}", err);
+	}
+}
+
+async fn fetch_metadata<J: JobRepository, B: BookRepository>(
+	job_repos: &J,
+	book_repos: &B,
+	provider: &SharedProvider,
+	job: &Job,
+) -> JobOutcome {
+	// 受け付けた後に手で登録された場合は何もしない
+	if book_repos.find(&job.user_id, &job.isbn_13).await.is_ok() {
+		return JobOutcome::Done;
+	}
+	let books = match provider.lookup(&job.isbn_13).await {
+		Ok(books) => books,
+		Err(ProviderError::NotFound(_)) => return JobOutcome::Failed(format!("no metadata found for {}", job.isbn_13)),
+		Err(err) => return JobOutcome::Retry(err.to_string()),
+	};
+
+	match book_repos.create(&job.user_id, books).await {
+		Ok(book_info) => {
+			enqueue_cover(job_repos, &job.user_id, &book_info).await;
+			JobOutcome::Done
+		}
+		Err(RepositoryError::Registered(_)) => JobOutcome::Done,
+		Err(err) => JobOutcome::Retry(err.to_string()),
+	}
+}
+
+async fn fetch_cover<B: BookRepository>(
+	book_repos: &B,
+	blob_store: &SharedBlobStore,
+	client: &reqwest::Client,
+	job: &Job,
+) -> JobOutcome {
+	let book_info = match book_repos.find(&job.user_id, &job.isbn_13).await {
+		Ok(book_info) => book_info,
+		// 取り寄せる前に本が消された
+		Err(RepositoryError::NotFound(_)) => return JobOutcome::Done,
+		Err(err) => return JobOutcome::Retry(err.to_string()),
+	};
+	// 利用者が上げた表紙は上書きしない
+	if book_info.cover_updated_at.is_some() || book_info.image_url.is_empty() {
+		return JobOutcome::Done;
+	}
+
+	let result = match download_cover(client, &book_info.image_url).await {
+		Ok(bytes) => save_cover(book_repos, blob_store, &job.user_id, &job.isbn_13, bytes).await,
+		Err(err) => Err(err),
+	};
+	match result {
+		Ok(()) => JobOutcome::Done,
+		Err(err @ (CoverError::UnsupportedFormat | CoverError::InvalidImage(_))) => JobOutcome::Failed(err.to_string()),
+		Err(err) => JobOutcome::Retry(err.to_string()),
+	}
+}
+
+async fn finish<J: JobRepository>(job_repos: &J, job: &Job, outcome: JobOutcome) -> Result<(), RepositoryError> {
+	match outcome {
+		JobOutcome::Done => job_repos.succeed(&job.id).await,
+		JobOutcome::Failed(error) => job_repos.fail(&job.id, &error).await,
+		JobOutcome::Retry(error) if job.attempts >= job.max_attempts => job_repos.fail(&job.id, &error).await,
+		JobOutcome::Retry(error) => {
+			let run_at = Utc::now() + chrono::Duration::seconds(retry_delay(job.attempts));
+			job_repos.retry(&job.id, &error, run_at).await
+		}
+	}
+}
+
+// ジョブを1件ずつ取り出して処理し続ける。App::serveから起動したワーカーの数だけ並行して動く
+pub async fn run_job_worker<J: JobRepository, B: BookRepository>(
+	job_repos: J,
+	book_repos: B,
+	provider: SharedProvider,
+	blob_store: SharedBlobStore,
+) {
+	let client = reqwest::Client::new();
+	loop {
+		let job = match job_repos.claim().await {
+			Ok(Some(job)) => job,
+			Ok(None) => {
+				sleep(POLL_INTERVAL).await;
+				continue;
+			}
+			Err(err) => {
+				println!("failed to claim job: {}", err);
+				sleep(POLL_INTERVAL).await;
+				continue;
+			}
+		};
+
+		// ジョブごとにタスクを分け、処理中にpanicしてもワーカーは止めずにそのジョブを失敗にする
+		let task = {
+			let job_repos = job_repos.clone();
+			let book_repos = book_repos.clone();
+			let provider = provider.clone();
+			let blob_store = blob_store.clone();
+			let client = client.clone();
+			let job = job.clone();
+			tokio::spawn(async move {
+				match job.kind {
+					JobKind::FetchMetadata => fetch_metadata(&job_repos, &book_repos, &provider, &job).await,
+					JobKind::FetchCover => fetch_cover(&book_repos, &blob_store, &client, &job).await,
+				}
+			})
+		};
+		let outcome = task
+			.await
+			.unwrap_or_else(|err| JobOutcome::Failed(err.to_string()));
+		if let Err(err) = finish(&job_repos, &job, outcome).await {
+			// 状態を更新できなかったジョブは、ロックの期限が切れた後に取り直される
+			println!("failed to update job {}: {}", job.id, err);
+		}
+	}
+}
@@ -0,0 +1,481 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use serde::Serialize;
+
+use crate::repos::book::BookInfo;
+
+// My Clippings.txtで各切り抜きの終わりに置かれる行
+const SEPARATOR: &str = "==========";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClippingKind {
+	Highlight,
+	Note,
+	Bookmark,
+}
+
+impl ClippingKind {
+	fn name(&self) -> &'static str {
+		match self {
+			ClippingKind::Highlight => "highlight",
+			ClippingKind::Note => "note",
+			ClippingKind::Bookmark => "bookmark",
+		}
+	}
+
+	fn label(&self) -> &'static str {
+		match self {
+			ClippingKind::Highlight => "Kindleのハイライト",
+			ClippingKind::Note => "Kindleのメモ",
+			ClippingKind::Bookmark => "Kindleのブックマーク",
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clipping {
+	// 本の題名が書かれた行の行番号（1始まり）
+	pub line: u64,
+	pub kind: ClippingKind,
+	pub page: Option<String>,
+	pub location: Option<String>,
+	// 端末の時刻でタイムゾーンが無い
+	pub added_at: Option<NaiveDateTime>,
+	pub text: String,
+}
+
+impl Clipping {
+	// メモの本文。末尾に位置を書き添える
+	pub fn memo_text(&self) -> String {
+		let mut position = vec![self.kind.label().to_string()];
+		if let Some(location) = &self.location {
+			position.push(format!("位置No. {}", location));
+		}
+		if let Some(page) = &self.page {
+			position.push(format!("{}ページ", page));
+		}
+		format!("{}\n\n（{}）", self.text, position.join(" / "))
+	}
+
+	// 取り込み直したときに同じ切り抜きだと分かるように、種類・位置・本文から作る
+	pub fn source_key(&self) -> String {
+		format!(
+			"kindle:{}:{}:{}",
+			self.kind.name(),
+			self.location.as_deref().or(self.page.as_deref()).unwrap_or_default(),
+			self.text
+		)
+	}
+}
+
+// 同じ本の切り抜きをまとめたもの
+#[derive(Debug, Clone)]
+pub struct ClippingGroup {
+	// 題名の行そのもの。取り込み先の本を選ぶときの識別子にする
+	pub key: String,
+	pub title: String,
+	pub authors: Vec<String>,
+	pub clippings: Vec<Clipping>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SkippedClipping {
+	pub line: u64,
+	pub reason: String,
+}
+
+#[derive(Debug)]
+pub struct ParsedClippings {
+	pub groups: Vec<ClippingGroup>,
+	pub skipped: Vec<SkippedClipping>,
+}
+
+// 「題名 (著者)」の行を分ける。複数の著者は;で区切られている
+fn split_title(line: &str) -> (String, Vec<String>) {
+	let line = line.trim();
+	let body = line.strip_suffix(')').or_else(|| line.strip_suffix('）'));
+	let split = body.and_then(|body| {
+		body
+			.rfind(['(', '（'])
+			.map(|open| (&body[..open], &body[open..]))
+	});
+	match split {
+		Some((title, authors)) if !title.trim().is_empty() => {
+			let authors = authors
+				.trim_start_matches(['(', '（'])
+				.split(';')
+				.map(str::trim)
+				.filter(|author| !author.is_empty())
+				.map(str::to_string)
+				.collect();
+			(title.trim().to_string(), authors)
+		}
+		_ => (line.to_string(), Vec::new()),
+	}
+}
+
+fn kind_of(segment: &str) -> Option<ClippingKind> {
+	let segment = segment.to_lowercase();
+	if segment.contains("highlight") || segment.contains("ハイライト") {
+		Some(ClippingKind::Highlight)
+	} else if segment.contains("note") || segment.contains("メモ") {
+		Some(ClippingKind::Note)
+	} else if segment.contains("bookmark") || segment.contains("ブックマーク") {
+		Some(ClippingKind::Bookmark)
+	} else {
+		None
+	}
+}
+
+fn is_position_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || c == '-'
+}
+
+// 目印の直後の番号。123-125のような範囲も含める
+fn value_after(segment: &str, markers: &[&str]) -> Option<String> {
+	let lower = segment.to_lowercase();
+	markers.iter().find_map(|marker| {
+		let start = lower.find(marker)? + marker.len();
+		let value: String = lower[start..]
+			.trim_start()
+			.chars()
+			.take_while(|c| is_position_char(*c))
+			.collect();
+		Some(value).filter(|value| !value.is_empty())
+	})
+}
+
+// 日本語の「12ページ」のように番号が前に来るもの
+fn value_before(segment: &str, marker: &str) -> Option<String> {
+	let end = segment.find(marker)?;
+	let value: String = segment[..end]
+		.trim_end()
+		.chars()
+		.rev()
+		.take_while(|c| is_position_char(*c))
+		.collect::<Vec<_>>()
+		.into_iter()
+		.rev()
+		.collect();
+	Some(value).filter(|value| !value.is_empty())
+}
+
+// Monday, January 1, 2024 10:00:00 AM や Monday, 1 January 2024 10:00:00
+fn parse_english_date(value: &str) -> Option<NaiveDateTime> {
+	let value = value.trim();
+	let value = value.split_once(", ").map(|(_, rest)| rest).unwrap_or(value);
+	["%B %d, %Y %I:%M:%S %p", "%d %B %Y %H:%M:%S", "%B %d, %Y %H:%M:%S"]
+		.iter()
+		.find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+}
+
+// 2024年1月1日月曜日 10:00:00 や 2024年1月1日 月曜日 午後10:00:00
+fn parse_japanese_date(value: &str) -> Option<NaiveDateTime> {
+	let (year, rest) = value.trim().split_once('年')?;
+	let (month, rest) = rest.split_once('月')?;
+	let (day, rest) = rest.split_once('日')?;
+	let date = NaiveDate::from_ymd_opt(
+		year.trim().parse().ok()?,
+		month.trim().parse().ok()?,
+		day.trim().parse().ok()?,
+	)?;
+	let time = rest.split_whitespace().last().unwrap_or_default();
+	let (afternoon, time) = match time.strip_prefix("午後") {
+		Some(time) => (Some(true), time),
+		None => match time.strip_prefix("午前") {
+			Some(time) => (Some(false), time),
+			None => (None, time),
+		},
+	};
+	let time = match NaiveTime::parse_from_str(time, "%H:%M:%S") {
+		Ok(time) => time,
+		Err(_) => return Some(date.and_time(NaiveTime::MIN)),
+	};
+	let hour = match afternoon {
+		Some(true) if time.hour() < 12 => time.hour() + 12,
+		Some(false) if time.hour() == 12 => 0,
+		_ => time.hour(),
+	};
+
+	Some(date.and_time(time.with_hour(hour)?))
+}
+
+// 題名の次の行に書かれた切り抜きの種類と位置
+struct ClippingMeta {
+	kind: ClippingKind,
+	page: Option<String>,
+	location: Option<String>,
+	added_at: Option<NaiveDateTime>,
+}
+
+// 「- 位置No. 123-125のハイライト |作成日: …」や「- Your Highlight on page 12 | Location 123-125 | Added on …」
+fn parse_meta(line: &str) -> Option<ClippingMeta> {
+	let mut kind = None;
+	let mut page = None;
+	let mut location = None;
+	let mut added_at = None;
+	for segment in line.trim().trim_start_matches('-').split('|').map(str::trim) {
+		if let Some(date) = segment.strip_prefix("Added on") {
+			added_at = parse_english_date(date);
+			continue;
+		}
+		if let Some(date) = segment.strip_prefix("作成日:").or_else(|| segment.strip_prefix("作成日：")) {
+			added_at = parse_japanese_date(date);
+			continue;
+		}
+		kind = kind.or_else(|| kind_of(segment));
+		location = location.or_else(|| value_after(segment, &["location", "loc.", "位置no."]));
+		page = page
+			.or_else(|| value_after(segment, &["page"]))
+			.or_else(|| value_before(segment, "ページ"));
+	}
+
+	kind.map(|kind| ClippingMeta {
+		kind,
+		page,
+		location,
+		added_at,
+	})
+}
+
+// 題名・切り抜きの種類と位置・空行・本文の順に並んだ1件を読む
+fn parse_entry(entry: &str, start: u64) -> Result<Option<(String, Clipping)>, SkippedClipping> {
+	let mut lines = entry
+		.lines()
+		.enumerate()
+		.skip_while(|(_, line)| line.trim().is_empty());
+	let (index, title) = match lines.next() {
+		Some(line) => line,
+		None => return Ok(None),
+	};
+	let line = start + index as u64;
+	let skip = |reason: &str| SkippedClipping {
+		line,
+		reason: reason.to_string(),
+	};
+	let meta = lines
+		.next()
+		.map(|(_, meta)| meta)
+		.ok_or_else(|| skip("clipping has no details line"))?;
+	let meta = parse_meta(meta).ok_or_else(|| skip(&format!("unknown clipping type '{}'", meta.trim())))?;
+	let text = lines
+		.map(|(_, line)| line)
+		.collect::<Vec<_>>()
+		.join("\n")
+		.trim()
+		.to_string();
+
+	Ok(Some((
+		title.trim().to_string(),
+		Clipping {
+			line,
+			kind: meta.kind,
+			page: meta.page,
+			location: meta.location,
+			added_at: meta.added_at,
+			text,
+		},
+	)))
+}
+
+// My Clippings.txtを読み、本ごとにまとめる。まとめる順はファイルに初めて出てきた順
+pub fn parse_clippings(text: &str) -> ParsedClippings {
+	let text = text.trim_start_matches('\u{feff}');
+	let mut groups: Vec<ClippingGroup> = Vec::new();
+	let mut skipped = Vec::new();
+	let mut line = 1;
+	for entry in text.split(SEPARATOR) {
+		let start = line;
+		line += entry.matches('\n').count() as u64;
+		let (key, clipping) = match parse_entry(entry, start) {
+			Ok(Some(parsed)) => parsed,
+			Ok(None) => continue,
+			Err(skip) => {
+				skipped.push(skip);
+				continue;
+			}
+		};
+		// 同じ本でも端末によって題名の前に目に見えない文字が付くことがある
+		let key = key.trim_start_matches('\u{feff}').to_string();
+		match groups.iter_mut().find(|group| group.key == key) {
+			Some(group) => group.clippings.push(clipping),
+			None => {
+				let (title, authors) = split_title(&key);
+				groups.push(ClippingGroup {
+					key,
+					title,
+					authors,
+					clippings: vec![clipping],
+				});
+			}
+		}
+	}
+
+	ParsedClippings { groups, skipped }
+}
+
+// 題名を比べるために括弧書き・空白・記号を除き、全角の英数字を半角にする
+pub fn normalize_title(title: &str) -> String {
+	let mut normalized = String::new();
+	let mut depth = 0;
+	for c in title.chars() {
+		match c {
+			'(' | '（' | '【' | '[' | '［' => depth += 1,
+			')' | '）' | '】' | ']' | '］' => depth = (depth - 1).max(0),
+			_ if depth > 0 => {}
+			'\u{ff01}'..='\u{ff5e}' => {
+				let c = char::from_u32(c as u32 - 0xfee0).unwrap_or(c);
+				if c.is_alphanumeric() {
+					normalized.extend(c.to_lowercase());
+				}
+			}
+			_ if c.is_alphanumeric() => normalized.extend(c.to_lowercase()),
+			_ => {}
+		}
+	}
+	normalized
+}
+
+// 蔵書から題名が一致する本を探す。Kindleの題名はシリーズ名などが付くので前方一致も候補にする
+// 題名が完全に一致する本、著者も一致する本の順に並べる
+pub fn find_candidates<'a>(group: &ClippingGroup, books: &'a [BookInfo]) -> Vec<&'a BookInfo> {
+	let title = normalize_title(&group.title);
+	let authors: Vec<String> = group.authors.iter().map(|author| normalize_title(author)).collect();
+	if title.is_empty() {
+		return Vec::new();
+	}
+	let mut candidates: Vec<(bool, bool, &BookInfo)> = books
+		.iter()
+		.filter_map(|book_info| {
+			let book_title = normalize_title(&book_info.title);
+			if book_title.is_empty() || !(title.starts_with(&book_title) || book_title.starts_with(&title)) {
+				return None;
+			}
+			let same_author = book_info
+				.authors
+				.iter()
+				.any(|author| authors.contains(&normalize_title(author)));
+			Some((book_title == title, same_author, book_info))
+		})
+		.collect();
+	candidates.sort_by_key(|(exact, same_author, _)| (!exact, !same_author));
+
+	candidates.into_iter().map(|(_, _, book_info)| book_info).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+		NaiveDate::from_ymd_opt(year, month, day)
+			.unwrap()
+			.and_hms_opt(hour, minute, 0)
+			.unwrap()
+	}
+
+	#[test]
+	fn parse_meta_reads_kind_and_position() {
+		let meta = parse_meta(
+			"- Your Highlight on page 12 | Location 123-125 | Added on Monday, January 1, 2024 10:00:00 PM",
+		)
+		.unwrap();
+		assert_eq!(meta.kind, ClippingKind::Highlight);
+		assert_eq!(meta.page.as_deref(), Some("12"));
+		assert_eq!(meta.location.as_deref(), Some("123-125"));
+		assert_eq!(meta.added_at, Some(at(2024, 1, 1, 22, 0)));
+
+		let meta = parse_meta("- 12ページ|位置No. 130のメモ |作成日: 2024年1月1日月曜日 10:00:00").unwrap();
+		assert_eq!(meta.kind, ClippingKind::Note);
+		assert_eq!(meta.page.as_deref(), Some("12"));
+		assert_eq!(meta.location.as_deref(), Some("130"));
+		assert_eq!(meta.added_at, Some(at(2024, 1, 1, 10, 0)));
+
+		assert!(parse_meta("- Your Clip on Location 5").is_none());
+	}
+
+	#[test]
+	fn parse_japanese_date_handles_am_and_pm() {
+		assert_eq!(parse_japanese_date("2024年1月1日 月曜日 午後10:00:00"), Some(at(2024, 1, 1, 22, 0)));
+		assert_eq!(parse_japanese_date("2024年1月1日 月曜日 午後12:30:00"), Some(at(2024, 1, 1, 12, 30)));
+		assert_eq!(parse_japanese_date("2024年1月1日 月曜日 午前12:30:00"), Some(at(2024, 1, 1, 0, 30)));
+		assert_eq!(parse_japanese_date("2024年1月1日 月曜日 午前9:05:00"), Some(at(2024, 1, 1, 9, 5)));
+		assert_eq!(parse_japanese_date("2024年1月1日月曜日"), Some(at(2024, 1, 1, 0, 0)));
+		assert_eq!(parse_japanese_date("2024年13月1日"), None);
+	}
+
+	#[test]
+	fn parse_clippings_groups_by_title_with_line_numbers() {
+		let text = [
+			"\u{feff}こころ (夏目 漱石)",
+			"- 位置No. 10-12のハイライト |作成日: 2024年1月1日月曜日 10:00:00",
+			"",
+			"私はその人を常に先生と呼んでいた。",
+			SEPARATOR,
+			"坊っちゃん (夏目 漱石)",
+			"- Your Note on Location 5 | Added on Monday, January 1, 2024 10:00:00 AM",
+			"",
+			"親譲りの無鉄砲",
+			SEPARATOR,
+			"\u{feff}こころ (夏目 漱石)",
+			"- 位置No. 20のハイライト |作成日: 2024年1月2日火曜日 10:00:00",
+			"",
+			"一行目",
+			"二行目",
+			SEPARATOR,
+			"題名だけの切り抜き",
+			SEPARATOR,
+			"",
+		]
+		.join("\r\n");
+
+		let parsed = parse_clippings(&text);
+		assert_eq!(parsed.groups.len(), 2);
+
+		let kokoro = &parsed.groups[0];
+		assert_eq!(kokoro.title, "こころ");
+		assert_eq!(kokoro.authors, vec!["夏目 漱石".to_string()]);
+		let lines: Vec<u64> = kokoro.clippings.iter().map(|clipping| clipping.line).collect();
+		assert_eq!(lines, vec![1, 11]);
+		assert_eq!(kokoro.clippings[1].text, "一行目\n二行目");
+		assert_eq!(kokoro.clippings[1].location.as_deref(), Some("20"));
+
+		let botchan = &parsed.groups[1];
+		assert_eq!(botchan.title, "坊っちゃん");
+		assert_eq!(botchan.clippings[0].line, 6);
+		assert_eq!(botchan.clippings[0].kind, ClippingKind::Note);
+
+		assert_eq!(parsed.skipped.len(), 1);
+		assert_eq!(parsed.skipped[0].line, 17);
+	}
+
+	#[test]
+	fn find_candidates_prefers_exact_titles_then_authors() {
+		let book = |isbn_13: &str, title: &str, author: &str| BookInfo {
+			isbn_13: isbn_13.to_string(),
+			title: title.to_string(),
+			authors: vec![author.to_string()],
+			..Default::default()
+		};
+		let books = vec![
+			book("1", "こころ 新装版", "他の人"),
+			book("2", "こころ", "別人"),
+			book("3", "こころ", "夏目漱石"),
+			book("4", "それから", "夏目 漱石"),
+			book("5", "こころ（新潮文庫）", "夏目 漱石"),
+			book("6", "こころ 新装版", "夏目漱石"),
+		];
+		let group = ClippingGroup {
+			key: "こころ (夏目 漱石)".to_string(),
+			title: "こころ".to_string(),
+			authors: vec!["夏目 漱石".to_string()],
+			clippings: Vec::new(),
+		};
+
+		let candidates: Vec<&str> = find_candidates(&group, &books)
+			.iter()
+			.map(|book_info| book_info.isbn_13.as_str())
+			.collect();
+		assert_eq!(candidates, vec!["3", "5", "2", "6", "1"]);
+	}
+}
@@ -0,0 +1,262 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{Connection, OpenFlags};
+use std::io::Write;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+// contentテーブルで本そのものを表す行の種類
+const BOOK_CONTENT_TYPE: i64 = 6;
+// SQLiteのファイルの先頭に必ず置かれる文字列
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+// 細工されたデータベースで読み込みが終わらないときに打ち切るまでの時間
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// 打ち切るかを確かめる間隔（SQLiteの命令数）
+const PROGRESS_INTERVAL: i32 = 10_000;
+
+#[derive(Debug, Error)]
+pub enum KoboError {
+	#[error("file is not a Kobo database: {0}")]
+	InvalidDatabase(String),
+	#[error("Unexpected Error: [{0}]")]
+	Unexpected(String),
+}
+
+impl From<rusqlite::Error> for KoboError {
+	fn from(err: rusqlite::Error) -> Self {
+		KoboError::InvalidDatabase(err.to_string())
+	}
+}
+
+// Bookmarkテーブルの1行。しおり（ドッグイヤー）は本文が無いので読まない
+#[derive(Debug, Clone, PartialEq)]
+pub struct KoboAnnotation {
+	pub bookmark_id: String,
+	pub text: String,
+	pub annotation: String,
+	pub chapter: Option<String>,
+	pub created_at: Option<DateTime<Utc>>,
+}
+
+impl KoboAnnotation {
+	pub fn is_note(&self) -> bool {
+		!self.annotation.is_empty()
+	}
+
+	// メモの本文。書き込みはハイライトの後に置き、末尾に章を書き添える
+	pub fn memo_text(&self) -> String {
+		let mut sections = Vec::new();
+		if !self.text.is_empty() {
+			sections.push(self.text.clone());
+		}
+		if self.is_note() {
+			sections.push(format!("メモ: {}", self.annotation));
+		}
+		let mut position = vec![match self.is_note() {
+			true => "Koboのメモ",
+			false => "Koboのハイライト",
+		}
+		.to_string()];
+		if let Some(chapter) = &self.chapter {
+			position.push(chapter.clone());
+		}
+		sections.push(format!("（{}）", position.join(" / ")));
+		sections.join("\n\n")
+	}
+
+	// 端末上の識別子は取り込み直しても変わらない
+	pub fn source_key(&self) -> String {
+		format!("kobo:{}", self.bookmark_id)
+	}
+}
+
+// 同じ本のハイライトと書き込みをまとめたもの
+#[derive(Debug, Clone)]
+pub struct KoboBook {
+	pub volume_id: String,
+	pub title: String,
+	pub authors: Vec<String>,
+	// 書店で買った本にしか入っていない
+	pub isbn: String,
+	pub annotations: Vec<KoboAnnotation>,
+}
+
+// DateCreatedは2024-01-05T20:49:49.000のようなUTCの日時
+fn parse_kobo_date(value: &str) -> Option<DateTime<Utc>> {
+	let value = value.get(..19)?;
+	NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+		.ok()
+		.map(|date| date.and_utc())
+}
+
+// 同じ名前のビューなどではなく、本物のテーブルがあるか
+fn has_table(conn: &Connection, table: &str) -> Result<bool, KoboError> {
+	let mut statement = conn.prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1")?;
+	Ok(statement.exists([table])?)
+}
+
+// 端末から消したハイライトはHidden列で隠されるが、古い端末のデータベースにはこの列が無い
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, KoboError> {
+	let mut statement = conn.prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?;
+	Ok(statement.exists([table, column])?)
+}
+
+// KoboReader.sqliteからハイライトと書き込みを読み、本ごとにまとめる
+fn read_kobo_annotations(conn: &Connection) -> Result<Vec<KoboBook>, KoboError> {
+	for table in ["Bookmark", "content"] {
+		if !has_table(conn, table)? {
+			return Err(KoboError::InvalidDatabase(format!("table {} was not found", table)));
+		}
+	}
+	let deadline = Instant::now() + READ_TIMEOUT;
+	conn.progress_handler(PROGRESS_INTERVAL, Some(move || Instant::now() > deadline));
+
+	let hidden = match has_column(conn, "Bookmark", "Hidden")? {
+		true => "AND (bookmark.Hidden IS NULL OR bookmark.Hidden <> 'true')",
+		false => "",
+	};
+	let query = format!(
+		r#"
+			SELECT bookmark.BookmarkID, bookmark.VolumeID, bookmark.Text, bookmark.Annotation, bookmark.DateCreated,
+				book.Title, book.Attribution, book.ISBN, chapter.Title
+			FROM Bookmark AS bookmark
+			LEFT JOIN content AS book ON book.ContentID = bookmark.VolumeID AND book.ContentType = ?1
+			LEFT JOIN content AS chapter ON chapter.ContentID = bookmark.ContentID AND chapter.ContentType <> ?1
+			WHERE (trim(coalesce(bookmark.Text, '')) <> '' OR trim(coalesce(bookmark.Annotation, '')) <> '')
+			{hidden}
+			ORDER BY bookmark.VolumeID, bookmark.DateCreated, bookmark.BookmarkID
+		"#,
+		hidden = hidden,
+	);
+	let mut statement = conn.prepare(&query)?;
+	let rows = statement.query_map([BOOK_CONTENT_TYPE], |row| {
+		let text = |index: usize| -> rusqlite::Result<String> {
+			Ok(row.get::<_, Option<String>>(index)?.unwrap_or_default().trim().to_string())
+		};
+		Ok((
+			text(1)?,
+			text(5)?,
+			text(6)?,
+			text(7)?,
+			KoboAnnotation {
+				bookmark_id: text(0)?,
+				text: text(2)?,
+				annotation: text(3)?,
+				chapter: Some(text(8)?).filter(|chapter| !chapter.is_empty()),
+				created_at: parse_kobo_date(&text(4)?),
+			},
+		))
+	})?;
+
+	let mut books: Vec<KoboBook> = Vec::new();
+	for row in rows {
+		let (volume_id, title, attribution, isbn, annotation) = row?;
+		match books.last_mut() {
+			Some(book) if book.volume_id == volume_id => book.annotations.push(annotation),
+			_ => books.push(KoboBook {
+				// 持ち込んだ本で題名が無いものはファイルの場所で示す
+				title: match title.is_empty() {
+					true => volume_id.clone(),
+					false => title,
+				},
+				volume_id,
+				authors: attribution
+					.split([',', '&'])
+					.map(str::trim)
+					.filter(|author| !author.is_empty())
+					.map(str::to_string)
+					.collect(),
+				isbn,
+				annotations: vec![annotation],
+			}),
+		}
+	}
+
+	Ok(books)
+}
+
+// アップロードされたデータベースを一時ファイルに書き出して読む。一時ファイルは読み終えたら消える
+// ブロッキングするので非同期の処理からはspawn_blockingで呼ぶ
+pub fn read_kobo_upload(bytes: &[u8]) -> Result<Vec<KoboBook>, KoboError> {
+	if !bytes.starts_with(SQLITE_HEADER) {
+		return Err(KoboError::InvalidDatabase("not a SQLite file".to_string()));
+	}
+	let mut file = tempfile::Builder::new()
+		.prefix("kobo-")
+		.suffix(".sqlite")
+		.tempfile()
+		.map_err(|err| KoboError::Unexpected(err.to_string()))?;
+	file
+		.write_all(bytes)
+		.and_then(|_| file.flush())
+		.map_err(|err| KoboError::Unexpected(err.to_string()))?;
+
+	let conn = Connection::open_with_flags(file.path(), OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+	read_kobo_annotations(&conn)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn kobo_database() -> Connection {
+		let conn = Connection::open_in_memory().unwrap();
+		conn
+			.execute_batch(
+				r#"
+					CREATE TABLE content (ContentID TEXT, ContentType INTEGER, Title TEXT, Attribution TEXT, ISBN TEXT);
+					CREATE TABLE Bookmark (
+						BookmarkID TEXT, VolumeID TEXT, ContentID TEXT, Text TEXT, Annotation TEXT, DateCreated TEXT, Hidden TEXT
+					);
+					INSERT INTO content VALUES
+						('kokoro', 6, 'こころ', '夏目漱石', '9784101010014'),
+						('kokoro!ch1', 9, '上 先生と私', NULL, NULL),
+						('file:///mnt/onboard/memo.epub', 6, NULL, 'A & B, C', NULL);
+					INSERT INTO Bookmark VALUES
+						('b2', 'kokoro', 'kokoro!ch1', '私はその人を常に先生と呼んでいた。', '', '2024-01-05T20:49:49.000', 'false'),
+						('b1', 'kokoro', 'kokoro!ch1', '  隠した行  ', '', '2024-01-04T10:00:00.000', 'true'),
+						('b3', 'kokoro', 'kokoro!ch1', '', '', '2024-01-06T10:00:00.000', NULL),
+						('b4', 'file:///mnt/onboard/memo.epub', 'missing', '', '読み返す', '2024-02-01T08:00:00.000', NULL);
+				"#,
+			)
+			.unwrap();
+		conn
+	}
+
+	#[test]
+	fn read_groups_annotations_by_book_and_skips_hidden() {
+		let books = read_kobo_annotations(&kobo_database()).unwrap();
+		assert_eq!(books.len(), 2);
+
+		let memo = &books[0];
+		assert_eq!(memo.title, "file:///mnt/onboard/memo.epub");
+		assert_eq!(memo.authors, vec!["A", "B", "C"]);
+		assert_eq!(memo.annotations.len(), 1);
+		assert!(memo.annotations[0].is_note());
+		assert_eq!(memo.annotations[0].chapter, None);
+
+		let kokoro = &books[1];
+		assert_eq!((kokoro.title.as_str(), kokoro.isbn.as_str()), ("こころ", "9784101010014"));
+		assert_eq!(kokoro.authors, vec!["夏目漱石"]);
+		let ids: Vec<&str> = kokoro.annotations.iter().map(|annotation| annotation.bookmark_id.as_str()).collect();
+		assert_eq!(ids, vec!["b2"]);
+		assert_eq!(kokoro.annotations[0].chapter.as_deref(), Some("上 先生と私"));
+		assert_eq!(
+			kokoro.annotations[0].created_at,
+			DateTime::parse_from_rfc3339("2024-01-05T20:49:49Z").ok().map(|date| date.to_utc())
+		);
+	}
+
+	#[test]
+	fn read_rejects_databases_without_kobo_tables() {
+		let conn = Connection::open_in_memory().unwrap();
+		conn
+			.execute_batch(
+				r#"
+					CREATE TABLE content (ContentID TEXT, ContentType INTEGER, Title TEXT, Attribution TEXT, ISBN TEXT);
+					CREATE VIEW Bookmark AS SELECT 1 AS BookmarkID;
+				"#,
+			)
+			.unwrap();
+		assert!(matches!(read_kobo_annotations(&conn), Err(KoboError::InvalidDatabase(_))));
+	}
+}
@@ -0,0 +1,240 @@
+use chrono::NaiveDate;
+use std::io::{Cursor, Write};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::repos::book::BookInfo;
+use crate::repos::memo::Memo;
+
+// ファイル名に使う題名の長さの上限。日本語は1文字3バイトなので多くのファイルシステムの255バイトに収まる
+const FILE_NAME_TITLE_LIMIT: usize = 60;
+
+// YAMLの文字列。JSONの文字列はそのままYAMLのダブルクォートの文字列として読める
+fn yaml_string(value: &str) -> String {
+	serde_json::to_string(value).unwrap_or_default()
+}
+
+fn yaml_list(key: &str, values: &[String]) -> String {
+	match values.is_empty() {
+		true => format!("{}: []\n", key),
+		false => values.iter().fold(format!("{}:\n", key), |list, value| {
+			format!("{}  - {}\n", list, yaml_string(value))
+		}),
+	}
+}
+
+// ファイルシステムやObsidianのリンクで使えない文字を全角に置き換える
+pub fn sanitize_file_name(title: &str) -> String {
+	let sanitized: String = title
+		.chars()
+		.map(|c| match c {
+			'/' => '／',
+			'\\' => '＼',
+			':' => '：',
+			'*' => '＊',
+			'?' => '？',
+			'"' => '＂',
+			'<' => '＜',
+			'>' => '＞',
+			'|' => '｜',
+			'#' => '＃',
+			'^' => '＾',
+			'[' => '［',
+			']' => '］',
+			_ if c.is_control() || c.is_whitespace() => ' ',
+			_ => c,
+		})
+		.collect();
+	// 連続する空白をまとめ、末尾の点や空白はWindowsで扱えないので除く
+	let sanitized = sanitized.split_whitespace().collect::<Vec<_>>().join(" ");
+	let truncated: String = sanitized.chars().take(FILE_NAME_TITLE_LIMIT).collect();
+	truncated.trim_matches(|c: char| c == '.' || c.is_whitespace()).to_string()
+}
+
+// 題名が同じ本や書き換えた題名でも重ならないように、識別子を付ける
+pub fn markdown_file_name(book_info: &BookInfo) -> String {
+	match sanitize_file_name(&book_info.title).as_str() {
+		"" => format!("{}.md", book_info.isbn_13),
+		title => format!("{} ({}).md", title, book_info.isbn_13),
+	}
+}
+
+// 本1冊分のMarkdown。書き出した日時などは含めず、同じ内容なら同じ文字列になるようにする
+pub fn book_markdown(book_info: &BookInfo, memos: &[Memo]) -> String {
+	let status = serde_json::to_value(book_info.status)
+		.ok()
+		.and_then(|status| status.as_str().map(str::to_string))
+		.unwrap_or_default();
+	let date = |date: Option<NaiveDate>| {
+		date
+			.map(|date| date.format("%Y-%m-%d").to_string())
+			.unwrap_or("null".to_string())
+	};
+
+	let mut markdown = String::from("---\n");
+	markdown.push_str(&format!("title: {}\n", yaml_string(&book_info.title)));
+	markdown.push_str(&yaml_list("authors", &book_info.authors));
+	markdown.push_str(&format!("publisher: {}\n", yaml_string(&book_info.publisher)));
+	markdown.push_str(&format!("published_date: {}\n", yaml_string(&book_info.published_date)));
+	markdown.push_str(&format!("isbn_13: {}\n", yaml_string(&book_info.isbn_13)));
+	markdown.push_str(&format!("cover_url: {}\n", yaml_string(&book_info.image_url)));
+	markdown.push_str(&yaml_list("tags", &book_info.tags));
+	markdown.push_str(&format!("status: {}\n", status));
+	match book_info.rating {
+		Some(rating) => markdown.push_str(&format!("rating: {}\n", rating)),
+		None => markdown.push_str("rating: null\n"),
+	}
+	markdown.push_str(&format!("started_on: {}\n", date(book_info.started_on)));
+	markdown.push_str(&format!("finished_on: {}\n", date(book_info.finished_on)));
+	markdown.push_str("---\n\n");
+	// 見出しは1行にしか書けないので、題名の改行は空白にする
+	let heading = book_info.title.split_whitespace().collect::<Vec<_>>().join(" ");
+	markdown.push_str(&format!("# {}\n", heading));
+
+	// メモは書いた順に、日時を見出しにして本文を引用にする
+	for memo in memos {
+		markdown.push_str(&format!("\n## {}\n\n", memo.created_at.format("%Y-%m-%d %H:%M UTC")));
+		for line in memo.text.trim().lines() {
+			match line.trim_end().is_empty() {
+				true => markdown.push_str(">\n"),
+				false => markdown.push_str(&format!("> {}\n", line.trim_end())),
+			}
+		}
+		if !memo.tags.is_empty() {
+			markdown.push_str(&format!("\nタグ: {}\n", memo.tags.join(", ")));
+		}
+	}
+
+	markdown
+}
+
+// 1冊1ファイルのMarkdownをまとめたzip。差分を取れるように、ファイル名の順に並べて日時を固定する
+pub fn write_markdown_zip(library: &[(BookInfo, Vec<Memo>)]) -> Result<Vec<u8>, String> {
+	let mut files: Vec<(String, String)> = library
+		.iter()
+		.map(|(book_info, memos)| (markdown_file_name(book_info), book_markdown(book_info, memos)))
+		.collect();
+	files.sort();
+
+	let options = SimpleFileOptions::default()
+		.compression_method(CompressionMethod::Deflated)
+		.last_modified_time(zip::DateTime::default())
+		.unix_permissions(0o644);
+	let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+	for (file_name, markdown) in files {
+		writer
+			.start_file(file_name, options)
+			.map_err(|err| err.to_string())?;
+		writer
+			.write_all(markdown.as_bytes())
+			.map_err(|err| err.to_string())?;
+	}
+
+	writer
+		.finish()
+		.map(|cursor| cursor.into_inner())
+		.map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::repos::reading::ReadingStatus;
+	use chrono::{TimeZone, Utc};
+
+	fn book(title: &str) -> BookInfo {
+		BookInfo {
+			isbn_13: "9784101010014".to_string(),
+			title: title.to_string(),
+			authors: vec!["夏目 漱石".to_string()],
+			publisher: "新潮社".to_string(),
+			published_date: "2004-03".to_string(),
+			status: ReadingStatus::Finished,
+			finished_on: NaiveDate::from_ymd_opt(2024, 1, 2),
+			rating: Some(4),
+			..Default::default()
+		}
+	}
+
+	fn memo(text: &str, tags: &[&str]) -> Memo {
+		let created_at = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+		Memo {
+			id: "memo".to_string(),
+			isbn_13: "9784101010014".to_string(),
+			text: text.to_string(),
+			created_at,
+			updated_at: created_at,
+			tags: tags.iter().map(|tag| tag.to_string()).collect(),
+		}
+	}
+
+	#[test]
+	fn sanitize_file_name_keeps_names_portable() {
+		assert_eq!(sanitize_file_name("こころ"), "こころ");
+		assert_eq!(
+			sanitize_file_name(r#"a/b\c:d*e?f"g<h>i|j#k^l[m]n"#),
+			"a／b＼c：d＊e？f＂g＜h＞i｜j＃k＾l［m］n"
+		);
+		assert_eq!(sanitize_file_name("  吾輩は\t猫\nである  "), "吾輩は 猫 である");
+		assert_eq!(sanitize_file_name("あ".repeat(70).as_str()), "あ".repeat(60));
+		assert_eq!(sanitize_file_name("Vol. 1..."), "Vol. 1");
+		// 切り詰めた末尾が点になった場合も除く
+		assert_eq!(sanitize_file_name(&format!("{}.い", "あ".repeat(59))), "あ".repeat(59));
+		assert_eq!(sanitize_file_name(" ... "), "");
+	}
+
+	#[test]
+	fn book_markdown_escapes_frontmatter() {
+		let markdown = book_markdown(
+			&book("He said \"hi\"\nこころ"),
+			&[memo("一段落目\n\n二段落目 ", &["名言"])],
+		);
+		let expected = r#"---
+title: "He said \"hi\"\nこころ"
+authors:
+  - "夏目 漱石"
+publisher: "新潮社"
+published_date: "2004-03"
+isbn_13: "9784101010014"
+cover_url: ""
+tags: []
+status: finished
+rating: 4
+started_on: null
+finished_on: 2024-01-02
+---
+
+# He said "hi" こころ
+
+## 2024-01-02 03:04 UTC
+
+> 一段落目
+>
+> 二段落目
+
+タグ: 名言
+"#;
+		assert_eq!(markdown, expected);
+	}
+
+	#[test]
+	fn write_markdown_zip_is_deterministic() {
+		let library = || {
+			vec![
+				(book("こころ"), vec![memo("本文", &[])]),
+				(
+					BookInfo {
+						isbn_13: "9784003101018".to_string(),
+						..book("それから")
+					},
+					Vec::new(),
+				),
+			]
+		};
+		let mut reversed = library();
+		reversed.reverse();
+
+		let zip = write_markdown_zip(&library()).unwrap();
+		assert_eq!(zip, write_markdown_zip(&library()).unwrap());
+		assert_eq!(zip, write_markdown_zip(&reversed).unwrap());
+	}
+}
@@ -1 +1,11 @@
+pub mod barcode;
+pub mod csv_export;
+pub mod csv_import;
+pub mod cover;
+pub mod filter_expr;
+pub mod job_worker;
+pub mod kindle_clippings;
+pub mod kobo;
+pub mod markdown_export;
+pub mod snippet;
 pub mod validate_json;
\ No newline at end of file
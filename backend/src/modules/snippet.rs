@@ -0,0 +1,143 @@
+use serde::Serialize;
+
+// 一致箇所の前後に残す文字数
+const CONTEXT_CHARS: usize = 40;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Snippet {
+	pub text: String,
+	// textの中で一致した範囲（文字単位、終端は含まない）
+	pub highlights: Vec<(usize, usize)>,
+}
+
+// 大文字小文字を区別せず比較するため、1文字ずつ小文字に揃える
+fn fold(text: &str) -> Vec<char> {
+	text
+		.chars()
+		.map(|c| c.to_lowercase().next().unwrap_or(c))
+		.collect()
+}
+
+fn find_all(haystack: &[char], needle: &[char]) -> Vec<usize> {
+	if needle.is_empty() || haystack.len() < needle.len() {
+		return Vec::new();
+	}
+	(0..=haystack.len() - needle.len())
+		.filter(|&start| haystack[start..start + needle.len()] == *needle)
+		.collect()
+}
+
+// 検索語が出現する回数
+pub fn count_matches(text: &str, terms: &[String]) -> usize {
+	let folded = fold(text);
+	terms
+		.iter()
+		.map(|term| find_all(&folded, &fold(term)).len())
+		.sum()
+}
+
+// 最初に一致した箇所の前後を切り出し、切り出した中の一致範囲を返す
+pub fn build_snippet(text: &str, terms: &[String]) -> Snippet {
+	let chars: Vec<char> = text.chars().collect();
+	let folded = fold(text);
+
+	let mut matches: Vec<(usize, usize)> = terms
+		.iter()
+		.map(|term| fold(term))
+		.flat_map(|term| {
+			find_all(&folded, &term)
+				.into_iter()
+				.map(move |start| (start, start + term.len()))
+		})
+		.collect();
+	matches.sort();
+
+	let first = matches.first().map(|(start, _)| *start).unwrap_or(0);
+	let start = first.saturating_sub(CONTEXT_CHARS);
+	let end = (first + CONTEXT_CHARS * 2).min(chars.len());
+
+	let mut snippet: String = chars[start..end].iter().collect();
+	let offset = if start > 0 { 1 } else { 0 };
+	if start > 0 {
+		snippet.insert(0, '…');
+	}
+	if end < chars.len() {
+		snippet.push('…');
+	}
+
+	// 重なる一致はまとめ、切り出し範囲に収まるものだけを残す
+	let mut highlights: Vec<(usize, usize)> = Vec::new();
+	for (match_start, match_end) in matches {
+		if match_start < start || match_end > end {
+			continue;
+		}
+		let range = (match_start - start + offset, match_end - start + offset);
+		match highlights.last_mut() {
+			Some(last) if range.0 <= last.1 => last.1 = last.1.max(range.1),
+			_ => highlights.push(range),
+		}
+	}
+
+	Snippet {
+		text: snippet,
+		highlights,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn terms(terms: &[&str]) -> Vec<String> {
+		terms.iter().map(|term| term.to_string()).collect()
+	}
+
+	// 一致範囲に当たる文字列
+	fn highlighted(snippet: &Snippet) -> Vec<String> {
+		snippet
+			.highlights
+			.iter()
+			.map(|(start, end)| snippet.text.chars().skip(*start).take(end - start).collect())
+			.collect()
+	}
+
+	#[test]
+	fn build_snippet_highlights_multibyte_matches() {
+		let snippet = build_snippet("吾輩は猫である。名前はまだ無い。", &terms(&["吾輩"]));
+		assert_eq!(snippet.text, "吾輩は猫である。名前はまだ無い。");
+		assert_eq!(snippet.highlights, vec![(0, 2)]);
+
+		let text = format!("{}猫{}", "あ".repeat(50), "い".repeat(100));
+		let snippet = build_snippet(&text, &terms(&["猫"]));
+		assert_eq!(snippet.text, format!("…{}猫{}…", "あ".repeat(40), "い".repeat(79)));
+		assert_eq!(snippet.highlights, vec![(41, 42)]);
+		assert_eq!(highlighted(&snippet), vec!["猫"]);
+
+		let text = format!("{}吾輩は猫", "い".repeat(100));
+		let snippet = build_snippet(&text, &terms(&["猫"]));
+		assert_eq!(snippet.text, format!("…{}吾輩は猫", "い".repeat(37)));
+		assert_eq!(highlighted(&snippet), vec!["猫"]);
+	}
+
+	#[test]
+	fn build_snippet_clips_matches_outside_the_window() {
+		let text = format!("猫{}猫", "あ".repeat(100));
+		let snippet = build_snippet(&text, &terms(&["猫"]));
+		assert_eq!(snippet.text, format!("猫{}…", "あ".repeat(79)));
+		assert_eq!(snippet.highlights, vec![(0, 1)]);
+	}
+
+	#[test]
+	fn build_snippet_merges_overlapping_matches() {
+		let snippet = build_snippet("I love NEKO cats", &terms(&["neko", "ko c"]));
+		assert_eq!(highlighted(&snippet), vec!["NEKO c"]);
+	}
+
+	#[test]
+	fn build_snippet_without_match_keeps_the_beginning() {
+		let text = "あ".repeat(100);
+		let snippet = build_snippet(&text, &terms(&["猫"]));
+		assert_eq!(snippet.text, format!("{}…", "あ".repeat(80)));
+		assert!(snippet.highlights.is_empty());
+	}
+}
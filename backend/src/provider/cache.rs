@@ -0,0 +1,64 @@
+use axum::async_trait;
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+
+use super::{BookMetadataProvider, LookupMode, PayloadProvider, ProviderError};
+use crate::repos::book::BookInfo;
+use crate::repos::metadata_cache::MetadataCacheRepository;
+
+// 問い合わせる前にDBに保存した応答を探し、無ければ取得した応答を保存する
+#[derive(Clone)]
+pub struct CachedProvider<C: MetadataCacheRepository> {
+	inner: Arc<dyn PayloadProvider>,
+	cache: C,
+	ttl: Duration,
+}
+
+impl<C: MetadataCacheRepository> CachedProvider<C> {
+	pub fn new(inner: Arc<dyn PayloadProvider>, cache: C, ttl: Duration) -> Self {
+		CachedProvider { inner, cache, ttl }
+	}
+}
+
+#[async_trait]
+impl<C: MetadataCacheRepository> BookMetadataProvider for CachedProvider<C> {
+	async fn lookup_with(&self, isbn_13: &str, mode: LookupMode) -> Result<BookInfo, ProviderError> {
+		let name = self.inner.name();
+		// キャッシュを読めなくても取得元への問い合わせは続ける
+		let cached = match mode {
+			LookupMode::Refresh => None,
+			_ => self.cache.find(name, isbn_13).await.unwrap_or_else(|err| {
+				println!("{}", err);
+				None
+			}),
+		};
+
+		match (&cached, mode) {
+			(Some(cached), LookupMode::Offline) => return self.inner.parse(isbn_13, &cached.payload),
+			(None, LookupMode::Offline) => return Err(ProviderError::NotFound(isbn_13.to_string())),
+			(Some(cached), _) if !cached.is_expired() => return self.inner.parse(isbn_13, &cached.payload),
+			_ => {}
+		}
+
+		let payload = match self.inner.fetch(isbn_13).await {
+			Ok(payload) => payload,
+			// 取得元に繋がらないときは期限切れの応答で代える
+			Err(err) => match cached {
+				Some(cached) => return self.inner.parse(isbn_13, &cached.payload),
+				None => return Err(err),
+			},
+		};
+		let book_info = self.inner.parse(isbn_13, &payload)?;
+
+		// 見つからなかった応答は保存せず、次の登録で問い合わせ直す
+		if let Err(err) = self
+			.cache
+			.store(name, isbn_13, &payload, Utc::now() + self.ttl)
+			.await
+		{
+			println!("{}", err);
+		}
+
+		Ok(book_info)
+	}
+}
@@ -0,0 +1,173 @@
+use axum::async_trait;
+
+use super::{BookMetadataProvider, LookupMode, ProviderError, SharedProvider};
+use crate::repos::book::BookInfo;
+
+// 登録順にプロバイダへ問い合わせ、欠けている項目を後続の結果で補う
+#[derive(Clone)]
+pub struct ProviderChain {
+	providers: Vec<SharedProvider>,
+}
+
+impl ProviderChain {
+	pub fn new(providers: Vec<SharedProvider>) -> Self {
+		ProviderChain { providers }
+	}
+}
+
+fn is_complete(book_info: &BookInfo) -> bool {
+	!(book_info.title.is_empty()
+		|| book_info.authors.is_empty()
+		|| book_info.publisher.is_empty()
+		|| book_info.published_date.is_empty()
+		|| book_info.description.is_empty()
+		|| book_info.image_url.is_empty()
+		|| book_info.page_count.is_none())
+}
+
+fn fill_missing(base: &mut BookInfo, other: BookInfo) {
+	let fill = |field: &mut String, value: String| {
+		if field.is_empty() {
+			*field = value;
+		}
+	};
+	fill(&mut base.title, other.title);
+	fill(&mut base.publisher, other.publisher);
+	fill(&mut base.published_date, other.published_date);
+	fill(&mut base.description, other.description);
+	fill(&mut base.image_url, other.image_url);
+	if base.authors.is_empty() {
+		base.authors = other.authors;
+	}
+	if base.page_count.is_none() {
+		base.page_count = other.page_count;
+	}
+}
+
+#[async_trait]
+impl BookMetadataProvider for ProviderChain {
+	async fn lookup_with(&self, isbn_13: &str, mode: LookupMode) -> Result<BookInfo, ProviderError> {
+		let mut merged: Option<BookInfo> = None;
+		let mut last_error = ProviderError::NotFound(isbn_13.to_string());
+
+		for provider in &self.providers {
+			match provider.lookup_with(isbn_13, mode).await {
+				Ok(book_info) => match merged.as_mut() {
+					Some(base) => fill_missing(base, book_info),
+					None => merged = Some(book_info),
+				},
+				// 見つからない場合は次のプロバイダを試す
+				Err(ProviderError::NotFound(_)) => {}
+				Err(err) => {
+					println!("{}", err);
+					last_error = err;
+				}
+			}
+
+			if merged.as_ref().is_some_and(is_complete) {
+				break;
+			}
+		}
+
+		match merged {
+			// タイトルが無いものは登録できないため見つからなかった扱いにする
+			Some(book_info) if !book_info.title.is_empty() => Ok(book_info),
+			Some(_) => Err(ProviderError::NotFound(isbn_13.to_string())),
+			None => Err(last_error),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::provider::{
+		fixture::FixtureProvider, google_books::GoogleBooksProvider, ndl::NdlProvider, openbd::OpenBdProvider,
+		PayloadProvider,
+	};
+	use std::sync::Arc;
+
+	const ISBN_13: &str = "9784101010014";
+
+	// 問い合わせると必ず失敗する取得元
+	struct FailingProvider;
+
+	#[async_trait]
+	impl BookMetadataProvider for FailingProvider {
+		async fn lookup_with(&self, _isbn_13: &str, _mode: LookupMode) -> Result<BookInfo, ProviderError> {
+			Err(ProviderError::Unexpected("service unavailable".to_string()))
+		}
+	}
+
+	fn fixture(book_info: BookInfo) -> SharedProvider {
+		Arc::new(FixtureProvider::new(vec![book_info]))
+	}
+
+	fn google_books() -> BookInfo {
+		GoogleBooksProvider::new()
+			.parse(ISBN_13, include_str!("fixtures/google_books.json"))
+			.unwrap()
+	}
+
+	fn openbd() -> BookInfo {
+		OpenBdProvider::new()
+			.parse(ISBN_13, include_str!("fixtures/openbd.json"))
+			.unwrap()
+	}
+
+	fn ndl() -> BookInfo {
+		NdlProvider::new()
+			.parse(ISBN_13, include_str!("fixtures/ndl.xml"))
+			.unwrap()
+	}
+
+	#[tokio::test]
+	async fn earlier_providers_win_and_later_ones_fill_gaps() {
+		let partial = BookInfo {
+			description: String::new(),
+			image_url: String::new(),
+			page_count: None,
+			..openbd()
+		};
+		let chain = ProviderChain::new(vec![fixture(partial), fixture(google_books())]);
+		let book_info = chain.lookup(ISBN_13).await.unwrap();
+
+		assert_eq!(book_info.authors, vec!["夏目 漱石".to_string()]);
+		assert_eq!(book_info.published_date, "2004-03-01");
+		assert_eq!(book_info.description, google_books().description);
+		assert_eq!(book_info.image_url, google_books().image_url);
+		assert_eq!(book_info.page_count, Some(326));
+	}
+
+	#[tokio::test]
+	async fn failures_and_missing_books_fall_through() {
+		let chain = ProviderChain::new(vec![
+			Arc::new(FailingProvider),
+			Arc::new(FixtureProvider::default()),
+			fixture(ndl()),
+		]);
+		assert_eq!(chain.lookup(ISBN_13).await.unwrap(), ndl());
+	}
+
+	#[tokio::test]
+	async fn lookup_reports_the_last_error_when_nothing_is_found() {
+		let chain = ProviderChain::new(vec![Arc::new(FixtureProvider::default()), Arc::new(FailingProvider)]);
+		let err = chain.lookup(ISBN_13).await.unwrap_err();
+		assert!(matches!(err, ProviderError::Unexpected(_)));
+
+		let chain = ProviderChain::new(vec![Arc::new(FixtureProvider::default())]);
+		let err = chain.lookup(ISBN_13).await.unwrap_err();
+		assert!(matches!(err, ProviderError::NotFound(_)));
+	}
+
+	#[tokio::test]
+	async fn books_without_title_are_not_found() {
+		let untitled = BookInfo {
+			title: String::new(),
+			..ndl()
+		};
+		let chain = ProviderChain::new(vec![fixture(untitled)]);
+		let err = chain.lookup(ISBN_13).await.unwrap_err();
+		assert!(matches!(err, ProviderError::NotFound(_)));
+	}
+}
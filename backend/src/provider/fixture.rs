@@ -0,0 +1,46 @@
+use axum::async_trait;
+use std::{collections::HashMap, sync::Arc};
+
+use super::{BookMetadataProvider, LookupMode, ProviderError};
+use crate::repos::book::BookInfo;
+
+// 事前に用意した書誌情報を返す、ネットワークを使わないプロバイダ
+#[derive(Clone, Default)]
+pub struct FixtureProvider {
+	books: Arc<HashMap<String, BookInfo>>,
+}
+
+impl FixtureProvider {
+	pub fn new(books: Vec<BookInfo>) -> Self {
+		FixtureProvider {
+			books: Arc::new(
+				books
+					.into_iter()
+					.map(|book| (book.isbn_13.clone(), book))
+					.collect(),
+			),
+		}
+	}
+
+	// BookInfoのJSON配列が書かれたファイルから読み込む
+	pub fn from_file(path: &str) -> Result<Self, ProviderError> {
+		let text =
+			std::fs::read_to_string(path).map_err(|err| ProviderError::Unexpected(err.to_string()))?;
+		let books = serde_json::from_str::<Vec<BookInfo>>(&text)
+			.map_err(|err| ProviderError::Unexpected(err.to_string()))?;
+
+		Ok(Self::new(books))
+	}
+}
+
+#[async_trait]
+impl BookMetadataProvider for FixtureProvider {
+	// 手元のデータなので保存済みの応答の使い方によらない
+	async fn lookup_with(&self, isbn_13: &str, _mode: LookupMode) -> Result<BookInfo, ProviderError> {
+		self
+			.books
+			.get(isbn_13)
+			.cloned()
+			.ok_or(ProviderError::NotFound(isbn_13.to_string()))
+	}
+}
@@ -0,0 +1,193 @@
+use axum::async_trait;
+use serde::Deserialize;
+
+use super::{PayloadProvider, ProviderError};
+use crate::repos::book::BookInfo;
+
+const DEFAULT_BASE_URL: &str = "https://www.googleapis.com/books/v1/volumes";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Identifier {
+	#[serde(rename = "type")]
+	identifier_type: String,
+	identifier: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ImageLinks {
+	thumbnail: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct VolumeInfoResult {
+	title: String,
+	#[serde(default)]
+	description: String,
+	#[serde(default)]
+	authors: Vec<String>,
+	#[serde(default)]
+	publisher: String,
+	#[serde(default)]
+	published_date: String,
+	image_links: Option<ImageLinks>,
+	page_count: Option<i32>,
+	#[serde(default)]
+	industry_identifiers: Vec<Identifier>,
+}
+
+impl VolumeInfoResult {
+	// 検索したisbnと一致する識別子を持つ場合のみ変換する
+	fn to_book_info(&self, isbn_13: &str) -> Option<BookInfo> {
+		let matched = self
+			.industry_identifiers
+			.iter()
+			.any(|identifier| match identifier.identifier_type.as_str() {
+				"ISBN_13" => identifier.identifier == isbn_13,
+				// ISBN_13が無い場合はチェックディジットを除いた9桁で比較する。9桁目が文字の途中なら一致しない
+				"ISBN_10" => {
+					isbn_13.len() == 13
+						&& identifier.identifier.len() == 10
+						&& isbn_13.get(3..12).is_some_and(|body| identifier.identifier.get(..9) == Some(body))
+				}
+				_ => false,
+			});
+		if !matched {
+			return None;
+		}
+
+		Some(BookInfo {
+			isbn_13: isbn_13.to_string(),
+			title: self.title.clone(),
+			description: self.description.clone(),
+			authors: self.authors.clone(),
+			publisher: self.publisher.clone(),
+			published_date: self.published_date.clone(),
+			image_url: self
+				.image_links
+				.as_ref()
+				.map(|links| links.thumbnail.clone())
+				.unwrap_or_default(),
+			page_count: self.page_count.filter(|page_count| *page_count > 0),
+			..Default::default()
+		})
+	}
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BookInfoResult {
+	volume_info: VolumeInfoResult,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SearchBooksResult {
+	#[serde(default)]
+	items: Vec<BookInfoResult>,
+}
+
+#[derive(Clone)]
+pub struct GoogleBooksProvider {
+	client: reqwest::Client,
+	base_url: String,
+}
+
+impl GoogleBooksProvider {
+	pub fn new() -> Self {
+		Self::with_base_url(DEFAULT_BASE_URL)
+	}
+
+	pub fn with_base_url(base_url: &str) -> Self {
+		GoogleBooksProvider {
+			client: reqwest::Client::new(),
+			base_url: base_url.to_string(),
+		}
+	}
+}
+
+impl Default for GoogleBooksProvider {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[async_trait]
+impl PayloadProvider for GoogleBooksProvider {
+	fn name(&self) -> &'static str {
+		"google_books"
+	}
+
+	async fn fetch(&self, isbn_13: &str) -> Result<String, ProviderError> {
+		self
+			.client
+			.get(format!("{}?q=isbn:{}", self.base_url, isbn_13))
+			.send()
+			.await
+			.and_then(|response| response.error_for_status())
+			.map_err(|err| ProviderError::Unexpected(err.to_string()))?
+			.text()
+			.await
+			.map_err(|err| ProviderError::Unexpected(err.to_string()))
+	}
+
+	fn parse(&self, isbn_13: &str, payload: &str) -> Result<BookInfo, ProviderError> {
+		let search_books_result = serde_json::from_str::<SearchBooksResult>(payload)
+			.map_err(|err| ProviderError::Unexpected(err.to_string()))?;
+
+		// Googleがisbn不一致でも良しなに変換してくれるが、ここでははじく
+		let book_info = search_books_result
+			.items
+			.iter()
+			.find_map(|item| item.volume_info.to_book_info(isbn_13))
+			.ok_or(ProviderError::NotFound(isbn_13.to_string()))?;
+
+		Ok(book_info)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SEARCH_RESULT: &str = include_str!("fixtures/google_books.json");
+	const ISBN_10_ONLY: &str = include_str!("fixtures/google_books_isbn_10_only.json");
+
+	#[test]
+	fn parse_picks_the_volume_with_the_same_isbn() {
+		let book_info = GoogleBooksProvider::new().parse("9784101010014", SEARCH_RESULT).unwrap();
+		assert_eq!(book_info.isbn_13, "9784101010014");
+		assert_eq!(book_info.title, "こころ");
+		assert_eq!(book_info.authors, vec!["夏目漱石".to_string()]);
+		assert_eq!(book_info.publisher, "新潮社");
+		assert_eq!(book_info.published_date, "2004-03");
+		assert!(book_info.description.starts_with("親友を裏切って"));
+		assert!(book_info.image_url.contains("id=2Fo9AQAAIAAJ"));
+		assert_eq!(book_info.page_count, Some(326));
+	}
+
+	#[test]
+	fn parse_rejects_volumes_with_other_isbns() {
+		let err = GoogleBooksProvider::new().parse("9784003101018", SEARCH_RESULT).unwrap_err();
+		assert!(matches!(err, ProviderError::NotFound(_)));
+	}
+
+	#[test]
+	fn parse_matches_a_volume_without_isbn_13() {
+		let book_info = GoogleBooksProvider::new().parse("9784003101018", ISBN_10_ONLY).unwrap();
+		assert_eq!(book_info.title, "坊っちゃん");
+		assert_eq!(book_info.image_url, "");
+		// 0ページは不明として扱う
+		assert_eq!(book_info.page_count, None);
+	}
+
+	#[test]
+	fn parse_handles_empty_results() {
+		let err = GoogleBooksProvider::new()
+			.parse("9784101010014", r#"{"kind": "books#volumes", "totalItems": 0}"#)
+			.unwrap_err();
+		assert!(matches!(err, ProviderError::NotFound(_)));
+	}
+}
@@ -0,0 +1,61 @@
+pub mod cache;
+pub mod chain;
+pub mod fixture;
+pub mod google_books;
+pub mod ndl;
+pub mod openbd;
+
+use axum::{async_trait, http::StatusCode};
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::repos::book::BookInfo;
+
+#[derive(Debug, Error)]
+pub enum ProviderError {
+	#[error("Unexpected Error: [{0}]")]
+	Unexpected(String),
+	#[error("NotFound, isbn is {0}")]
+	NotFound(String),
+}
+
+// 保存済みの応答の使い方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LookupMode {
+	// 期限内の応答があればそれを使う
+	#[default]
+	Cached,
+	// 保存済みの応答を使わずに問い合わせ直す
+	Refresh,
+	// 問い合わせずに、期限切れでも保存済みの応答だけを使う
+	Offline,
+}
+
+// ISBNから書誌情報を取得する
+#[async_trait]
+pub trait BookMetadataProvider: Send + Sync + 'static {
+	async fn lookup_with(&self, isbn_13: &str, mode: LookupMode) -> Result<BookInfo, ProviderError>;
+
+	async fn lookup(&self, isbn_13: &str) -> Result<BookInfo, ProviderError> {
+		self.lookup_with(isbn_13, LookupMode::Cached).await
+	}
+}
+
+// 応答の本文を取り寄せる処理と、書誌情報に変換する処理を分けた取得元
+// 本文はCachedProviderがDBに保存する
+#[async_trait]
+pub trait PayloadProvider: Send + Sync + 'static {
+	// 保存する際のキーになる名前
+	fn name(&self) -> &'static str;
+	async fn fetch(&self, isbn_13: &str) -> Result<String, ProviderError>;
+	fn parse(&self, isbn_13: &str, payload: &str) -> Result<BookInfo, ProviderError>;
+}
+
+pub type SharedProvider = Arc<dyn BookMetadataProvider>;
+
+pub fn handle_provider_error(err: ProviderError) -> StatusCode {
+	match err {
+		ProviderError::NotFound(_) => StatusCode::NOT_FOUND,
+		_ => StatusCode::INTERNAL_SERVER_ERROR,
+	}
+}
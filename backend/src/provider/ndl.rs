@@ -0,0 +1,185 @@
+use axum::async_trait;
+
+use super::{PayloadProvider, ProviderError};
+use crate::repos::book::BookInfo;
+
+const DEFAULT_BASE_URL: &str = "https://ndlsearch.ndl.go.jp";
+
+const DC: &str = "http://purl.org/dc/elements/1.1/";
+const DCTERMS: &str = "http://purl.org/dc/terms/";
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const RDFS: &str = "http://www.w3.org/2000/01/rdf-schema#";
+
+// 国立国会図書館サーチのOpenSearch APIから書誌情報を取得する
+#[derive(Clone)]
+pub struct NdlProvider {
+	client: reqwest::Client,
+	base_url: String,
+}
+
+impl NdlProvider {
+	pub fn new() -> Self {
+		Self::with_base_url(DEFAULT_BASE_URL)
+	}
+
+	pub fn with_base_url(base_url: &str) -> Self {
+		NdlProvider {
+			client: reqwest::Client::new(),
+			base_url: base_url.to_string(),
+		}
+	}
+}
+
+impl Default for NdlProvider {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+// 2017.2 や 2017.2.24 の形式を YYYY-MM / YYYY-MM-DD に揃える
+fn normalize_issued(issued: &str) -> String {
+	let parts: Vec<&str> = issued.trim().split('.').collect();
+	match parts.as_slice() {
+		[year, month] => format!("{}-{:0>2}", year, month),
+		[year, month, day] => format!("{}-{:0>2}-{:0>2}", year, month, day),
+		_ => issued.trim().to_string(),
+	}
+}
+
+// "256p ; 19cm" のような形式からページ数を取り出す
+fn parse_extent(extent: &str) -> Option<i32> {
+	let digits: String = extent.chars().take_while(|c| c.is_ascii_digit()).collect();
+	digits.parse().ok().filter(|page_count| *page_count > 0)
+}
+
+fn texts(item: roxmltree::Node, namespace: &str, name: &str) -> Vec<String> {
+	item
+		.children()
+		.filter(|node| node.has_tag_name((namespace, name)))
+		.filter_map(|node| node.text())
+		.map(|text| text.trim().to_string())
+		.filter(|text| !text.is_empty())
+		.collect()
+}
+
+fn first_text(item: roxmltree::Node, namespace: &str, name: &str) -> String {
+	texts(item, namespace, name).into_iter().next().unwrap_or_default()
+}
+
+// ハイフン付きのisbnが返るため数字だけで比較する
+fn matches_isbn(item: roxmltree::Node, isbn_13: &str) -> bool {
+	texts(item, DC, "identifier").iter().any(|identifier| {
+		let digits: String = identifier.chars().filter(|c| c.is_ascii_digit()).collect();
+		digits == isbn_13 || (digits.len() == 10 && isbn_13.len() == 13 && isbn_13[3..12] == digits[..9])
+	})
+}
+
+// 書影がある本にだけサムネイルへのリンクが付く
+fn thumbnail_url(item: roxmltree::Node) -> String {
+	item
+		.children()
+		.filter(|node| node.has_tag_name((RDFS, "seeAlso")))
+		.filter_map(|node| node.attribute((RDF, "resource")))
+		.find(|resource| resource.contains("/thumbnail/"))
+		.unwrap_or_default()
+		.to_string()
+}
+
+fn parse_opensearch(xml: &str, isbn_13: &str) -> Result<BookInfo, ProviderError> {
+	let doc =
+		roxmltree::Document::parse(xml).map_err(|err| ProviderError::Unexpected(err.to_string()))?;
+
+	// 同じisbnで複数の版が返ることがあるため、一致する最初のitemを使う
+	let item = doc
+		.descendants()
+		.filter(|node| node.has_tag_name("item"))
+		.find(|item| matches_isbn(*item, isbn_13))
+		.ok_or(ProviderError::NotFound(isbn_13.to_string()))?;
+
+	let description = item
+		.children()
+		.find(|node| node.has_tag_name("description"))
+		.and_then(|node| node.text())
+		.map(|text| text.trim().to_string())
+		.unwrap_or_default();
+
+	Ok(BookInfo {
+		isbn_13: isbn_13.to_string(),
+		title: first_text(item, DC, "title"),
+		authors: texts(item, DC, "creator"),
+		publisher: first_text(item, DC, "publisher"),
+		published_date: normalize_issued(&first_text(item, DCTERMS, "issued")),
+		description,
+		image_url: thumbnail_url(item),
+		page_count: parse_extent(&first_text(item, DCTERMS, "extent")),
+		..Default::default()
+	})
+}
+
+#[async_trait]
+impl PayloadProvider for NdlProvider {
+	fn name(&self) -> &'static str {
+		"ndl"
+	}
+
+	async fn fetch(&self, isbn_13: &str) -> Result<String, ProviderError> {
+		self
+			.client
+			.get(format!("{}/api/opensearch?isbn={}", self.base_url, isbn_13))
+			.send()
+			.await
+			.and_then(|response| response.error_for_status())
+			.map_err(|err| ProviderError::Unexpected(err.to_string()))?
+			.text()
+			.await
+			.map_err(|err| ProviderError::Unexpected(err.to_string()))
+	}
+
+	fn parse(&self, isbn_13: &str, payload: &str) -> Result<BookInfo, ProviderError> {
+		parse_opensearch(payload, isbn_13)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PAYLOAD: &str = include_str!("fixtures/ndl.xml");
+	const THUMBNAIL: &str =
+		r#"<rdfs:seeAlso rdf:resource="https://ndlsearch.ndl.go.jp/thumbnail/9784101010014.jpg"/>"#;
+
+	#[test]
+	fn parse_reads_opensearch_item() {
+		let book_info = NdlProvider::new().parse("9784101010014", PAYLOAD).unwrap();
+		assert_eq!(book_info.title, "こころ");
+		assert_eq!(book_info.authors, vec!["夏目, 漱石, 1867-1916".to_string()]);
+		assert_eq!(book_info.publisher, "新潮社");
+		assert_eq!(book_info.published_date, "2004-03");
+		assert!(book_info.description.starts_with("親友を裏切って"));
+		assert_eq!(
+			book_info.image_url,
+			"https://ndlsearch.ndl.go.jp/thumbnail/9784101010014.jpg"
+		);
+		assert_eq!(book_info.page_count, Some(326));
+	}
+
+	#[test]
+	fn parse_matches_any_item() {
+		let book_info = NdlProvider::new().parse("9784003101117", PAYLOAD).unwrap();
+		assert_eq!(book_info.publisher, "岩波書店");
+		assert_eq!(book_info.image_url, "");
+	}
+
+	#[test]
+	fn parse_leaves_image_url_empty_without_thumbnail() {
+		let payload = PAYLOAD.replace(THUMBNAIL, "");
+		let book_info = NdlProvider::new().parse("9784101010014", &payload).unwrap();
+		assert_eq!(book_info.image_url, "");
+	}
+
+	#[test]
+	fn parse_rejects_other_isbns() {
+		let err = NdlProvider::new().parse("9784003101018", PAYLOAD).unwrap_err();
+		assert!(matches!(err, ProviderError::NotFound(_)));
+	}
+}
@@ -0,0 +1,237 @@
+use axum::async_trait;
+use serde::Deserialize;
+
+use super::{PayloadProvider, ProviderError};
+use crate::repos::book::BookInfo;
+
+const DEFAULT_BASE_URL: &str = "https://api.openbd.jp/v1/get";
+
+#[derive(Deserialize, Debug)]
+struct Summary {
+	#[serde(default)]
+	isbn: String,
+	#[serde(default)]
+	title: String,
+	#[serde(default)]
+	publisher: String,
+	#[serde(default)]
+	pubdate: String,
+	#[serde(default)]
+	cover: String,
+	#[serde(default)]
+	author: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PersonName {
+	content: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Contributor {
+	person_name: Option<PersonName>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Extent {
+	extent_type: String,
+	extent_value: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+struct DescriptiveDetail {
+	#[serde(default)]
+	contributor: Vec<Contributor>,
+	#[serde(default)]
+	extent: Vec<Extent>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct TextContent {
+	text_type: String,
+	text: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+struct CollateralDetail {
+	#[serde(default)]
+	text_content: Vec<TextContent>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+struct Onix {
+	#[serde(default)]
+	descriptive_detail: DescriptiveDetail,
+	#[serde(default)]
+	collateral_detail: CollateralDetail,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenBdResult {
+	summary: Summary,
+	#[serde(default)]
+	onix: Onix,
+}
+
+impl OpenBdResult {
+	fn to_book_info(&self, isbn_13: &str) -> BookInfo {
+		// ONIXの著者名を優先し、無ければsummaryの「名前／著」形式を分解する
+		let mut authors: Vec<String> = self
+			.onix
+			.descriptive_detail
+			.contributor
+			.iter()
+			.filter_map(|contributor| contributor.person_name.as_ref())
+			.map(|name| name.content.trim().to_string())
+			.filter(|name| !name.is_empty())
+			.collect();
+		if authors.is_empty() {
+			authors = self
+				.summary
+				.author
+				.split_whitespace()
+				.map(|author| author.split('／').next().unwrap_or_default().to_string())
+				.filter(|author| !author.is_empty())
+				.collect();
+		}
+
+		// TextTypeは03が詳細な内容紹介、02が短い内容紹介
+		let text_contents = &self.onix.collateral_detail.text_content;
+		let description = ["03", "02"]
+			.iter()
+			.find_map(|text_type| {
+				text_contents
+					.iter()
+					.find(|content| content.text_type == *text_type)
+			})
+			.or(text_contents.first())
+			.map(|content| content.text.clone())
+			.unwrap_or_default();
+
+		BookInfo {
+			isbn_13: isbn_13.to_string(),
+			title: self.summary.title.clone(),
+			authors,
+			publisher: self.summary.publisher.clone(),
+			published_date: normalize_pubdate(&self.summary.pubdate),
+			description,
+			image_url: self.summary.cover.clone(),
+			// ExtentTypeの11は本文のページ数
+			page_count: self
+				.onix
+				.descriptive_detail
+				.extent
+				.iter()
+				.find(|extent| extent.extent_type == "11")
+				.and_then(|extent| extent.extent_value.parse().ok()),
+			..Default::default()
+		}
+	}
+}
+
+// 20170224 や 201702 の形式を YYYY-MM-DD / YYYY-MM に揃える
+fn normalize_pubdate(pubdate: &str) -> String {
+	let digits: String = pubdate.chars().filter(|c| c.is_ascii_digit()).collect();
+	match digits.len() {
+		8 => format!("{}-{}-{}", &digits[..4], &digits[4..6], &digits[6..]),
+		6 => format!("{}-{}", &digits[..4], &digits[4..]),
+		_ => pubdate.to_string(),
+	}
+}
+
+#[derive(Clone)]
+pub struct OpenBdProvider {
+	client: reqwest::Client,
+	base_url: String,
+}
+
+impl OpenBdProvider {
+	pub fn new() -> Self {
+		Self::with_base_url(DEFAULT_BASE_URL)
+	}
+
+	pub fn with_base_url(base_url: &str) -> Self {
+		OpenBdProvider {
+			client: reqwest::Client::new(),
+			base_url: base_url.to_string(),
+		}
+	}
+}
+
+impl Default for OpenBdProvider {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[async_trait]
+impl PayloadProvider for OpenBdProvider {
+	fn name(&self) -> &'static str {
+		"openbd"
+	}
+
+	async fn fetch(&self, isbn_13: &str) -> Result<String, ProviderError> {
+		self
+			.client
+			.get(format!("{}?isbn={}", self.base_url, isbn_13))
+			.send()
+			.await
+			.and_then(|response| response.error_for_status())
+			.map_err(|err| ProviderError::Unexpected(err.to_string()))?
+			.text()
+			.await
+			.map_err(|err| ProviderError::Unexpected(err.to_string()))
+	}
+
+	fn parse(&self, isbn_13: &str, payload: &str) -> Result<BookInfo, ProviderError> {
+		// 見つからないisbnにはnullが返る
+		let results = serde_json::from_str::<Vec<Option<OpenBdResult>>>(payload)
+			.map_err(|err| ProviderError::Unexpected(err.to_string()))?;
+
+		results
+			.into_iter()
+			.flatten()
+			.find(|result| result.summary.isbn == isbn_13)
+			.map(|result| result.to_book_info(isbn_13))
+			.ok_or(ProviderError::NotFound(isbn_13.to_string()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PAYLOAD: &str = include_str!("fixtures/openbd.json");
+
+	#[test]
+	fn parse_reads_summary_and_onix() {
+		let book_info = OpenBdProvider::new().parse("9784101010014", PAYLOAD).unwrap();
+		assert_eq!(book_info.title, "こころ");
+		// summaryの「夏目漱石／著」よりONIXの著者名を使う
+		assert_eq!(book_info.authors, vec!["夏目 漱石".to_string()]);
+		assert_eq!(book_info.publisher, "新潮社");
+		assert_eq!(book_info.published_date, "2004-03-01");
+		assert!(book_info.description.ends_with("自らも死を選ぶ先生。"));
+		assert_eq!(book_info.image_url, "https://cover.openbd.jp/9784101010014.jpg");
+		assert_eq!(book_info.page_count, Some(326));
+	}
+
+	#[test]
+	fn parse_treats_null_as_not_found() {
+		let err = OpenBdProvider::new().parse("9784003101018", "[null]").unwrap_err();
+		assert!(matches!(err, ProviderError::NotFound(_)));
+	}
+
+	#[test]
+	fn normalize_pubdate_formats() {
+		assert_eq!(normalize_pubdate("20170224"), "2017-02-24");
+		assert_eq!(normalize_pubdate("201702"), "2017-02");
+		assert_eq!(normalize_pubdate("2017"), "2017");
+	}
+}
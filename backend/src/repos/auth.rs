@@ -15,11 +15,8 @@ pub struct Credentials {
 	pub email: String,
 	#[validate(length(min = 1, max = 255), custom(function = "validate_password"))]
 	pub password: String,
-<<<<<<< Updated upstream
-=======
 	pub next: String,
 	pub failed: String,
->>>>>>> Stashed changes
 }
 
 #[derive(Debug, Clone)]
@@ -1,11 +1,31 @@
-use super::super::repos::RepositoryError;
+use super::super::repos::{
+	reading::ReadingStatus, search::like_pattern, shelf::ShelfMembership, Cursor, Page, PageRequest,
+	RepositoryError, SortOrder,
+};
+use crate::modules::filter_expr::{DateField, FilterExpr, NumberField, TextField};
+use crate::entity::isbn::Isbn13;
 use axum::async_trait;
-use serde::Serialize;
-use sqlx::{Acquire, FromRow, PgPool, Transaction};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, FromRow, PgConnection, PgPool, Transaction};
 use std::{borrow::BorrowMut, sync::Arc};
+use validator::{Validate, ValidationError};
 
-#[derive(Serialize, Debug, FromRow, PartialEq)]
+// 手入力した本の識別子の先頭文字。ISBNは数字のみなので重ならない
+pub const INTERNAL_ID_PREFIX: char = 'M';
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum BookSource {
+	#[default]
+	Provider,
+	Manual,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, FromRow, PartialEq)]
 pub struct BookInfo {
+	// ISBNのない本は内部の識別子になる
 	pub isbn_13: String,
 	pub title: String,
 	pub authors: Vec<String>,
@@ -13,14 +33,342 @@ pub struct BookInfo {
 	pub published_date: String,
 	pub description: String,
 	pub image_url: String,
+	#[serde(default)]
+	pub page_count: Option<i32>,
+	#[serde(default)]
+	#[sqlx(default)]
+	pub tags: Vec<String>,
+	#[serde(default)]
+	pub created_at: Option<DateTime<Utc>>,
+	#[serde(default)]
+	pub updated_at: Option<DateTime<Utc>>,
+	#[serde(default)]
+	pub status: ReadingStatus,
+	#[serde(default)]
+	pub started_on: Option<NaiveDate>,
+	#[serde(default)]
+	pub finished_on: Option<NaiveDate>,
+	#[serde(default)]
+	pub source: BookSource,
+	#[serde(default)]
+	#[sqlx(default)]
+	pub edited_fields: Vec<String>,
+	// 手元に表紙を保存した日時。GET /book/:isbn_13/coverのキャッシュの検証に使う
+	#[serde(default)]
+	#[sqlx(default)]
+	pub cover_updated_at: Option<DateTime<Utc>>,
+	// 5段階の評価
+	#[serde(default)]
+	#[sqlx(default)]
+	pub rating: Option<i16>,
+	// 本の詳細を返すときだけ詰める
+	#[serde(default)]
+	#[sqlx(skip)]
+	pub shelves: Vec<ShelfMembership>,
+}
+
+// 手入力で登録する本の書誌情報。読書状況やタグは登録後にそれぞれのAPIで付ける
+#[derive(Deserialize, Debug, Default, Validate)]
+pub struct CreateManualBook {
+	#[serde(default)]
+	#[validate(custom(function = "validate_book_id"))]
+	pub isbn_13: String,
+	#[validate(length(min = 1))]
+	pub title: String,
+	#[serde(default)]
+	pub authors: Vec<String>,
+	#[serde(default)]
+	pub publisher: String,
+	#[serde(default)]
+	pub published_date: String,
+	#[serde(default)]
+	pub description: String,
+	#[serde(default)]
+	pub image_url: String,
+	#[serde(default)]
+	#[validate(range(min = 1))]
+	pub page_count: Option<i32>,
+}
+
+impl From<CreateManualBook> for BookInfo {
+	fn from(payload: CreateManualBook) -> Self {
+		BookInfo {
+			isbn_13: payload.isbn_13,
+			title: payload.title,
+			authors: payload.authors,
+			publisher: payload.publisher,
+			published_date: payload.published_date,
+			description: payload.description,
+			image_url: payload.image_url,
+			page_count: payload.page_count,
+			source: BookSource::Manual,
+			..Default::default()
+		}
+	}
+}
+
+// 手入力では空のまま（内部の識別子を振る）か正しいISBNのみ受け付ける
+fn validate_book_id(isbn_13: &str) -> Result<(), ValidationError> {
+	if isbn_13.is_empty() {
+		return Ok(());
+	}
+	match Isbn13::parse(isbn_13) {
+		Ok(isbn) if !isbn.is_internal() => Ok(()),
+		_ => Err(ValidationError::new("isbn_13 must be empty or a valid ISBN")),
+	}
+}
+
+// isbn_13の列に収まる13文字の内部の識別子を作る
+pub fn internal_book_id() -> String {
+	let random = uuid::Uuid::new_v4().simple().to_string().to_uppercase();
+	format!("{}{}", INTERNAL_ID_PREFIX, &random[..12])
+}
+
+// 書き換えと再取得の対象になる書誌情報の項目
+pub const METADATA_FIELDS: [&str; 7] = [
+	"title",
+	"authors",
+	"publisher",
+	"published_date",
+	"description",
+	"image_url",
+	"page_count",
+];
+
+fn field_value(book_info: &BookInfo, field: &str) -> serde_json::Value {
+	match field {
+		"title" => serde_json::json!(book_info.title),
+		"authors" => serde_json::json!(book_info.authors),
+		"publisher" => serde_json::json!(book_info.publisher),
+		"published_date" => serde_json::json!(book_info.published_date),
+		"description" => serde_json::json!(book_info.description),
+		"image_url" => serde_json::json!(book_info.image_url),
+		"page_count" => serde_json::json!(book_info.page_count),
+		_ => serde_json::Value::Null,
+	}
+}
+
+fn copy_field(book_info: &mut BookInfo, from: &BookInfo, field: &str) {
+	match field {
+		"title" => book_info.title = from.title.clone(),
+		"authors" => book_info.authors = from.authors.clone(),
+		"publisher" => book_info.publisher = from.publisher.clone(),
+		"published_date" => book_info.published_date = from.published_date.clone(),
+		"description" => book_info.description = from.description.clone(),
+		"image_url" => book_info.image_url = from.image_url.clone(),
+		"page_count" => book_info.page_count = from.page_count,
+		_ => {}
+	}
+}
+
+fn is_empty_value(value: &serde_json::Value) -> bool {
+	match value {
+		serde_json::Value::Null => true,
+		serde_json::Value::String(text) => text.is_empty(),
+		serde_json::Value::Array(items) => items.is_empty(),
+		_ => false,
+	}
+}
+
+#[derive(Deserialize, Debug, Default, Validate)]
+pub struct UpdateBook {
+	#[validate(length(min = 1))]
+	pub title: Option<String>,
+	pub authors: Option<Vec<String>>,
+	pub publisher: Option<String>,
+	pub published_date: Option<String>,
+	pub description: Option<String>,
+	pub image_url: Option<String>,
+	#[validate(range(min = 1))]
+	pub page_count: Option<i32>,
+	// 書き換えをやめて次の再取得で取得元の値に戻す項目
+	#[serde(default)]
+	pub reset: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FieldChange {
+	pub field: String,
+	pub before: serde_json::Value,
+	pub after: serde_json::Value,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RefreshResult {
+	pub book: BookInfo,
+	pub changes: Vec<FieldChange>,
+}
+
+// 一覧取得の絞り込み条件
+#[derive(Debug, Clone, Default)]
+pub struct BookFilter {
+	pub status: Option<ReadingStatus>,
+	pub tag: Option<String>,
+	pub shelf_id: Option<String>,
+	pub expression: Option<FilterExpr>,
+}
+
+// 条件式に埋め込まずにバインドする値
+#[derive(Debug, PartialEq)]
+enum FilterValue {
+	Text(String),
+	Int(i64),
+	Status(ReadingStatus),
+}
+
+// 条件式をbooksの行に対するWHERE句に変換する
+// 値は全てプレースホルダーにし、offsetの次の番号から振る
+fn expression_sql(expr: &FilterExpr, values: &mut Vec<FilterValue>, offset: usize) -> String {
+	let mut placeholder = |value: FilterValue| {
+		values.push(value);
+		format!("${}", offset + values.len())
+	};
+	match expr {
+		FilterExpr::And(left, right) => format!(
+			"({} AND {})",
+			expression_sql(left, values, offset),
+			expression_sql(right, values, offset)
+		),
+		FilterExpr::Or(left, right) => format!(
+			"({} OR {})",
+			expression_sql(left, values, offset),
+			expression_sql(right, values, offset)
+		),
+		FilterExpr::Not(inner) => format!("(NOT {})", expression_sql(inner, values, offset)),
+		FilterExpr::Tag(name) => format!(
+			r#"EXISTS (
+				SELECT 1 FROM book_tags JOIN tags ON tags.id = book_tags.tag_id
+				WHERE book_tags.user_id = books.user_id AND book_tags.isbn_13 = books.isbn_13
+				AND tags.name = {}
+			)"#,
+			placeholder(FilterValue::Text(name.clone()))
+		),
+		FilterExpr::Status(status) => format!("books.status = {}", placeholder(FilterValue::Status(*status))),
+		FilterExpr::Contains(TextField::Title, text) => format!(
+			"books.title ILIKE {}",
+			placeholder(FilterValue::Text(like_pattern(text)))
+		),
+		FilterExpr::Contains(TextField::Publisher, text) => format!(
+			"books.publisher ILIKE {}",
+			placeholder(FilterValue::Text(like_pattern(text)))
+		),
+		FilterExpr::Contains(TextField::Author, text) => format!(
+			r#"EXISTS (
+				SELECT 1 FROM authors WHERE authors.user_id = books.user_id AND authors.isbn_13 = books.isbn_13
+				AND authors.author_name ILIKE {}
+			)"#,
+			placeholder(FilterValue::Text(like_pattern(text)))
+		),
+		FilterExpr::Year(DateField::Added, year) => format!(
+			"EXTRACT(YEAR FROM books.created_at) = {}",
+			placeholder(FilterValue::Int(*year as i64))
+		),
+		FilterExpr::Year(DateField::Started, year) => format!(
+			"EXTRACT(YEAR FROM books.started_on) = {}",
+			placeholder(FilterValue::Int(*year as i64))
+		),
+		// 再読した本は過去のサイクルで読み終えた年でも一致させる
+		FilterExpr::Year(DateField::Finished, year) => {
+			let year = placeholder(FilterValue::Int(*year as i64));
+			format!(
+				r#"(EXTRACT(YEAR FROM books.finished_on) = {year} OR EXISTS (
+					SELECT 1 FROM reading_cycles
+					WHERE reading_cycles.user_id = books.user_id AND reading_cycles.isbn_13 = books.isbn_13
+					AND reading_cycles.outcome = 'finished' AND EXTRACT(YEAR FROM reading_cycles.finished_on) = {year}
+				))"#
+			)
+		}
+		// 出版日は書誌情報の文字列のまま持っているので先頭の年で比べる
+		FilterExpr::Year(DateField::Published, year) => format!(
+			"left(books.published_date, 4) = {}",
+			placeholder(FilterValue::Text(year.to_string()))
+		),
+		FilterExpr::Compare(NumberField::Pages, op, number) => format!(
+			"books.page_count {} {}",
+			op.operator(),
+			placeholder(FilterValue::Int(*number))
+		),
+		// 評価の無い本はどの比較にも一致しない
+		FilterExpr::Compare(NumberField::Rating, op, number) => format!(
+			"books.rating {} {}",
+			op.operator(),
+			placeholder(FilterValue::Int(*number))
+		),
+	}
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BookSort {
+	#[default]
+	Added,
+	Title,
+	Publisher,
+	PublishedDate,
+}
+
+impl BookSort {
+	fn column(&self) -> &'static str {
+		match self {
+			BookSort::Added => "created_at",
+			BookSort::Title => "title",
+			BookSort::Publisher => "publisher",
+			BookSort::PublishedDate => "published_date",
+		}
+	}
+
+	fn cast(&self) -> &'static str {
+		match self {
+			BookSort::Added => "timestamptz",
+			_ => "text",
+		}
+	}
+
+	fn key(&self, book_info: &BookInfo) -> String {
+		match self {
+			BookSort::Added => book_info
+				.created_at
+				.map(|created_at| created_at.to_rfc3339())
+				.unwrap_or_default(),
+			BookSort::Title => book_info.title.clone(),
+			BookSort::Publisher => book_info.publisher.clone(),
+			BookSort::PublishedDate => book_info.published_date.clone(),
+		}
+	}
 }
 
 #[async_trait]
 pub trait BookRepository: Clone + Send + Sync + 'static {
-	async fn find(&self, isbn_13: &str) -> Result<BookInfo, RepositoryError>;
-	async fn find_all(&self) -> Result<Vec<BookInfo>, RepositoryError>;
-	async fn create(&self, payload: BookInfo) -> Result<BookInfo, RepositoryError>;
-	async fn delete(&self, isbn_13: &str) -> Result<(), RepositoryError>;
+	async fn find(&self, user_id: &str, isbn_13: &str) -> Result<BookInfo, RepositoryError>;
+	async fn find_all(
+		&self,
+		user_id: &str,
+		filter: &BookFilter,
+		sort: BookSort,
+		order: SortOrder,
+		page: &PageRequest,
+	) -> Result<Page<BookInfo>, RepositoryError>;
+	async fn create(&self, user_id: &str, payload: BookInfo) -> Result<BookInfo, RepositoryError>;
+	async fn update(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		payload: UpdateBook,
+	) -> Result<BookInfo, RepositoryError>;
+	async fn refresh(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		fetched: BookInfo,
+	) -> Result<RefreshResult, RepositoryError>;
+	async fn delete(&self, user_id: &str, isbn_13: &str) -> Result<(), RepositoryError>;
+	async fn update_cover(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		cover_updated_at: Option<DateTime<Utc>>,
+	) -> Result<(), RepositoryError>;
+	async fn update_rating(&self, user_id: &str, isbn_13: &str, rating: Option<i16>) -> Result<(), RepositoryError>;
 }
 
 #[derive(Clone)]
@@ -44,68 +392,279 @@ impl BookRepositoryForPg {
 	}
 }
 
-#[async_trait]
-impl BookRepository for BookRepositoryForPg {
-	async fn find(&self, isbn_13: &str) -> Result<BookInfo, RepositoryError> {
-		let mut tx = self.start_transaction().await?;
-		let conn = tx
-			.acquire()
+// 書誌情報の項目と書き換えた項目の一覧を保存する
+async fn write_metadata(
+	conn: &mut PgConnection,
+	user_id: &str,
+	book_info: &BookInfo,
+	authors_changed: bool,
+) -> Result<(), RepositoryError> {
+	sqlx::query(
+		r#"
+			UPDATE books SET title = $3, description = $4, publisher = $5, published_date = $6,
+			image_url = $7, page_count = $8, edited_fields = $9, updated_at = now()
+			WHERE user_id = $1 AND isbn_13 = $2;
+      "#,
+	)
+	.bind(user_id)
+	.bind(&book_info.isbn_13)
+	.bind(&book_info.title)
+	.bind(&book_info.description)
+	.bind(&book_info.publisher)
+	.bind(&book_info.published_date)
+	.bind(&book_info.image_url)
+	.bind(book_info.page_count)
+	.bind(&book_info.edited_fields)
+	.execute(conn.borrow_mut())
+	.await
+	.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+	if authors_changed {
+		sqlx::query(r#"DELETE FROM authors WHERE user_id = $1 AND isbn_13 = $2;"#)
+			.bind(user_id)
+			.bind(&book_info.isbn_13)
+			.execute(conn.borrow_mut())
 			.await
 			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		for author in &book_info.authors {
+			sqlx::query(r#"INSERT INTO authors (user_id, isbn_13, author_name) VALUES ($1, $2, $3);"#)
+				.bind(user_id)
+				.bind(&book_info.isbn_13)
+				.bind(author)
+				.execute(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		}
+	}
 
-		let book_info = sqlx::query_as::<_, BookInfo>(
-			r#"
-				SELECT *, ARRAY (
-						SELECT author_name FROM authors WHERE isbn_13 = $1
-				) as authors FROM books WHERE isbn_13 = $1;
-      "#,
-		)
+	Ok(())
+}
+
+// 本を1件、著者とタグを付けて読む
+async fn select_book(conn: &mut PgConnection, user_id: &str, isbn_13: &str) -> Result<BookInfo, RepositoryError> {
+	sqlx::query_as::<_, BookInfo>(
+		r#"
+			SELECT *, ARRAY (
+					SELECT author_name FROM authors WHERE user_id = $1 AND isbn_13 = $2
+			) as authors, ARRAY (
+					SELECT tags.name FROM book_tags JOIN tags ON tags.id = book_tags.tag_id
+					WHERE book_tags.user_id = $1 AND book_tags.isbn_13 = $2 ORDER BY tags.name
+			) as tags FROM books WHERE user_id = $1 AND isbn_13 = $2;
+		"#,
+	)
+	.bind(user_id)
+	.bind(isbn_13)
+	.fetch_one(conn)
+	.await
+	.map_err(|err| match err {
+		sqlx::Error::RowNotFound => RepositoryError::NotFound(isbn_13.to_string()),
+		_ => RepositoryError::Unexpected(err.to_string()),
+	})
+}
+
+// 読んでから書き戻すまでの間に他の更新が割り込まないように、本の行をロックしてから読む
+async fn select_book_for_update(
+	conn: &mut PgConnection,
+	user_id: &str,
+	isbn_13: &str,
+) -> Result<BookInfo, RepositoryError> {
+	sqlx::query(r#"SELECT 1 FROM books WHERE user_id = $1 AND isbn_13 = $2 FOR UPDATE;"#)
+		.bind(user_id)
 		.bind(isbn_13)
-		.fetch_one(conn)
+		.fetch_one(conn.borrow_mut())
 		.await
 		.map_err(|err| match err {
 			sqlx::Error::RowNotFound => RepositoryError::NotFound(isbn_13.to_string()),
 			_ => RepositoryError::Unexpected(err.to_string()),
 		})?;
 
-		Ok(book_info)
+	select_book(conn, user_id, isbn_13).await
+}
+
+#[async_trait]
+impl BookRepository for BookRepositoryForPg {
+	async fn find(&self, user_id: &str, isbn_13: &str) -> Result<BookInfo, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		select_book(conn, user_id, isbn_13).await
 	}
 
-	async fn find_all(&self) -> Result<Vec<BookInfo>, RepositoryError> {
+	async fn find_all(
+		&self,
+		user_id: &str,
+		filter: &BookFilter,
+		sort: BookSort,
+		order: SortOrder,
+		page: &PageRequest,
+	) -> Result<Page<BookInfo>, RepositoryError> {
 		let mut tx = self.start_transaction().await?;
 		let conn = tx
 			.acquire()
 			.await
 			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
 
-		let book_info = sqlx::query_as::<_, BookInfo>(
+		let sort_name = format!("{:?}:{:?}", sort, order);
+		let cursor = page
+			.cursor
+			.as_deref()
+			.map(|cursor| Cursor::decode(cursor, &sort_name))
+			.transpose()?;
+		let limit = page.limit();
+
+		let mut values = Vec::new();
+		let expression = filter
+			.expression
+			.as_ref()
+			.map(|expr| expression_sql(expr, &mut values, 7))
+			.unwrap_or("TRUE".to_string());
+
+		// 並び替えの列と向きはenumから決まる固定の文字列のみを埋め込む
+		// 著者の配列は絞り込んだページの行に対してだけ集める
+		let query = format!(
 			r#"
+				WITH page AS (
+					SELECT * FROM books WHERE user_id = $1
+					AND ($5::text IS NULL OR status = $5)
+					AND ($6::text IS NULL OR EXISTS (
+						SELECT 1 FROM book_tags JOIN tags ON tags.id = book_tags.tag_id
+						WHERE book_tags.user_id = books.user_id AND book_tags.isbn_13 = books.isbn_13
+						AND tags.name = $6
+					))
+					AND ($7::text IS NULL OR EXISTS (
+						SELECT 1 FROM shelf_books
+						WHERE shelf_books.user_id = books.user_id AND shelf_books.isbn_13 = books.isbn_13
+						AND shelf_books.shelf_id = $7
+					))
+					AND {expression}
+					AND ($2::text IS NULL OR ({column}, isbn_13) {comparison} ($2::{cast}, $3))
+					ORDER BY {column} {order}, isbn_13 {order}
+					LIMIT $4
+				)
 				SELECT *, ARRAY (
-						SELECT author_name FROM authors WHERE authors.isbn_13 = books.isbn_13
-				) as authors FROM books;
+						SELECT author_name FROM authors
+						WHERE authors.user_id = page.user_id AND authors.isbn_13 = page.isbn_13
+				) as authors, ARRAY (
+						SELECT tags.name FROM book_tags JOIN tags ON tags.id = book_tags.tag_id
+						WHERE book_tags.user_id = page.user_id AND book_tags.isbn_13 = page.isbn_13
+						ORDER BY tags.name
+				) as tags FROM page
+				ORDER BY {column} {order}, isbn_13 {order};
       "#,
-		)
-		.fetch_all(conn)
-		.await
-		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+			expression = expression,
+			column = sort.column(),
+			cast = sort.cast(),
+			comparison = order.comparison(),
+			order = order.keyword(),
+		);
+		let mut book_query = sqlx::query_as::<_, BookInfo>(&query)
+			.bind(user_id)
+			.bind(cursor.as_ref().map(|cursor| cursor.key.clone()))
+			.bind(cursor.as_ref().map(|cursor| cursor.id.clone()))
+			// 次のページがあるかを知るために1件多く取る
+			.bind(limit + 1)
+			.bind(filter.status)
+			.bind(&filter.tag)
+			.bind(&filter.shelf_id);
+		for value in &values {
+			book_query = match value {
+				FilterValue::Text(text) => book_query.bind(text),
+				FilterValue::Int(number) => book_query.bind(number),
+				FilterValue::Status(status) => book_query.bind(status),
+			};
+		}
+		let mut book_info = book_query
+			.fetch_all(conn.borrow_mut())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
 
-		Ok(book_info)
+		let next_cursor = if book_info.len() as i64 > limit {
+			book_info.truncate(limit as usize);
+			book_info.last().map(|last| {
+				Cursor {
+					sort: sort_name.clone(),
+					key: sort.key(last),
+					id: last.isbn_13.clone(),
+				}
+				.encode()
+			})
+		} else {
+			None
+		};
+
+		let total = if page.with_total {
+			let mut values = Vec::new();
+			let expression = filter
+				.expression
+				.as_ref()
+				.map(|expr| expression_sql(expr, &mut values, 4))
+				.unwrap_or("TRUE".to_string());
+			let query = format!(
+				r#"
+					SELECT count(*) FROM books WHERE user_id = $1
+					AND ($2::text IS NULL OR status = $2)
+					AND ($3::text IS NULL OR EXISTS (
+						SELECT 1 FROM book_tags JOIN tags ON tags.id = book_tags.tag_id
+						WHERE book_tags.user_id = books.user_id AND book_tags.isbn_13 = books.isbn_13
+						AND tags.name = $3
+					))
+					AND ($4::text IS NULL OR EXISTS (
+						SELECT 1 FROM shelf_books
+						WHERE shelf_books.user_id = books.user_id AND shelf_books.isbn_13 = books.isbn_13
+						AND shelf_books.shelf_id = $4
+					))
+					AND {expression};
+        "#,
+				expression = expression,
+			);
+			let mut count_query = sqlx::query_scalar(&query)
+				.bind(user_id)
+				.bind(filter.status)
+				.bind(&filter.tag)
+				.bind(&filter.shelf_id);
+			for value in &values {
+				count_query = match value {
+					FilterValue::Text(text) => count_query.bind(text),
+					FilterValue::Int(number) => count_query.bind(number),
+					FilterValue::Status(status) => count_query.bind(status),
+				};
+			}
+			let total: i64 = count_query
+				.fetch_one(conn)
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+			Some(total)
+		} else {
+			None
+		};
+
+		Ok(Page {
+			items: book_info,
+			next_cursor,
+			total,
+		})
 	}
 
-	async fn create(&self, payload: BookInfo) -> Result<BookInfo, RepositoryError> {
+	async fn create(&self, user_id: &str, payload: BookInfo) -> Result<BookInfo, RepositoryError> {
 		let mut tx = self.start_transaction().await?;
 		let conn = tx
 			.acquire()
 			.await
 			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
 
-		sqlx::query(r#"INSERT INTO books (isbn_13, title, description, publisher, published_date, image_url) VALUES ($1, $2, $3, $4, $5, $6);"#)
+		sqlx::query(r#"INSERT INTO books (user_id, isbn_13, title, description, publisher, published_date, image_url, page_count, source) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9);"#)
+			.bind(user_id)
 			.bind(&payload.isbn_13)
 			.bind(&payload.title)
 			.bind(&payload.description)
 			.bind(&payload.publisher)
 			.bind(&payload.published_date)
 			.bind(&payload.image_url)
+			.bind(payload.page_count)
+			.bind(payload.source)
 			.execute(conn.borrow_mut())
 			.await
 			.map_err(|err| match err.as_database_error() {
@@ -117,7 +676,8 @@ impl BookRepository for BookRepositoryForPg {
 			})?;
 
 		for author in payload.authors {
-			sqlx::query(r#"INSERT INTO authors (isbn_13, author_name) VALUES ($1, $2);"#)
+			sqlx::query(r#"INSERT INTO authors (user_id, isbn_13, author_name) VALUES ($1, $2, $3);"#)
+				.bind(user_id)
 				.bind(&payload.isbn_13)
 				.bind(author)
 				.execute(conn.borrow_mut())
@@ -129,12 +689,12 @@ impl BookRepository for BookRepositoryForPg {
 			.await
 			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
 
-		let book_info = self.find(&payload.isbn_13).await?;
+		let book_info = self.find(user_id, &payload.isbn_13).await?;
 
 		Ok(book_info)
 	}
 
-	async fn delete(&self, isbn_13: &str) -> Result<(), RepositoryError> {
+	async fn delete(&self, user_id: &str, isbn_13: &str) -> Result<(), RepositoryError> {
 		let mut tx = self
 			.start_transaction()
 			.await
@@ -144,7 +704,8 @@ impl BookRepository for BookRepositoryForPg {
 			.await
 			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
 
-		sqlx::query(r#"DELETE FROM memo WHERE isbn_13 = $1"#)
+		sqlx::query(r#"DELETE FROM memo WHERE user_id = $1 AND isbn_13 = $2"#)
+			.bind(user_id)
 			.bind(isbn_13)
 			.execute(conn.borrow_mut())
 			.await
@@ -153,7 +714,8 @@ impl BookRepository for BookRepositoryForPg {
 				_ => RepositoryError::Unexpected(err.to_string()),
 			})?;
 
-		sqlx::query(r#"DELETE FROM authors WHERE isbn_13 = $1"#)
+		sqlx::query(r#"DELETE FROM authors WHERE user_id = $1 AND isbn_13 = $2"#)
+			.bind(user_id)
 			.bind(isbn_13)
 			.execute(conn.borrow_mut())
 			.await
@@ -162,7 +724,8 @@ impl BookRepository for BookRepositoryForPg {
 				_ => RepositoryError::Unexpected(err.to_string()),
 			})?;
 
-		sqlx::query(r#"DELETE FROM books WHERE isbn_13 = $1"#)
+		sqlx::query(r#"DELETE FROM books WHERE user_id = $1 AND isbn_13 = $2"#)
+			.bind(user_id)
 			.bind(isbn_13)
 			.execute(conn.borrow_mut())
 			.await
@@ -177,4 +740,180 @@ impl BookRepository for BookRepositoryForPg {
 
 		Ok(())
 	}
+
+	async fn update_cover(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		cover_updated_at: Option<DateTime<Utc>>,
+	) -> Result<(), RepositoryError> {
+		let result = sqlx::query(r#"UPDATE books SET cover_updated_at = $3 WHERE user_id = $1 AND isbn_13 = $2;"#)
+			.bind(user_id)
+			.bind(isbn_13)
+			.bind(cover_updated_at)
+			.execute(self.pool.as_ref())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if result.rows_affected() == 0 {
+			return Err(RepositoryError::NotFound(isbn_13.to_string()));
+		}
+
+		Ok(())
+	}
+
+	async fn update_rating(&self, user_id: &str, isbn_13: &str, rating: Option<i16>) -> Result<(), RepositoryError> {
+		let result = sqlx::query(r#"UPDATE books SET rating = $3, updated_at = now() WHERE user_id = $1 AND isbn_13 = $2;"#)
+			.bind(user_id)
+			.bind(isbn_13)
+			.bind(rating)
+			.execute(self.pool.as_ref())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if result.rows_affected() == 0 {
+			return Err(RepositoryError::NotFound(isbn_13.to_string()));
+		}
+
+		Ok(())
+	}
+
+	async fn update(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		payload: UpdateBook,
+	) -> Result<BookInfo, RepositoryError> {
+		if let Some(field) = payload.reset.iter().find(|field| !METADATA_FIELDS.contains(&field.as_str())) {
+			return Err(RepositoryError::InvalidInput(format!("unknown field {}", field)));
+		}
+
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let mut book_info = select_book_for_update(conn, user_id, isbn_13).await?;
+		let mut edited: Vec<&str> = Vec::new();
+		let authors_changed = payload.authors.is_some();
+		if let Some(title) = payload.title {
+			book_info.title = title;
+			edited.push("title");
+		}
+		if let Some(authors) = payload.authors {
+			book_info.authors = authors;
+			edited.push("authors");
+		}
+		if let Some(publisher) = payload.publisher {
+			book_info.publisher = publisher;
+			edited.push("publisher");
+		}
+		if let Some(published_date) = payload.published_date {
+			book_info.published_date = published_date;
+			edited.push("published_date");
+		}
+		if let Some(description) = payload.description {
+			book_info.description = description;
+			edited.push("description");
+		}
+		if let Some(image_url) = payload.image_url {
+			book_info.image_url = image_url;
+			edited.push("image_url");
+		}
+		if let Some(page_count) = payload.page_count {
+			book_info.page_count = Some(page_count);
+			edited.push("page_count");
+		}
+
+		for field in edited {
+			if !book_info.edited_fields.iter().any(|edited_field| edited_field == field) {
+				book_info.edited_fields.push(field.to_string());
+			}
+		}
+		book_info
+			.edited_fields
+			.retain(|field| !payload.reset.contains(field));
+
+		write_metadata(conn, user_id, &book_info, authors_changed).await?;
+		let book_info = select_book(conn, user_id, isbn_13).await?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(book_info)
+	}
+
+	async fn refresh(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		fetched: BookInfo,
+	) -> Result<RefreshResult, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let mut book_info = select_book_for_update(conn, user_id, isbn_13).await?;
+
+		// 書き換えた項目と、取得元が値を持っていない項目はそのまま残す
+		let mut changes = Vec::new();
+		for field in METADATA_FIELDS {
+			if book_info.edited_fields.iter().any(|edited_field| edited_field == field) {
+				continue;
+			}
+			let before = field_value(&book_info, field);
+			let after = field_value(&fetched, field);
+			if is_empty_value(&after) || before == after {
+				continue;
+			}
+			copy_field(&mut book_info, &fetched, field);
+			changes.push(FieldChange {
+				field: field.to_string(),
+				before,
+				after,
+			});
+		}
+
+		if !changes.is_empty() {
+			let authors_changed = changes.iter().any(|change| change.field == "authors");
+			write_metadata(conn, user_id, &book_info, authors_changed).await?;
+		}
+		let book = select_book(conn, user_id, isbn_13).await?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(RefreshResult { book, changes })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::modules::filter_expr;
+
+	#[test]
+	fn expression_sql_binds_values() {
+		let expr = filter_expr::parse(r#"title:"x' OR 1=1 --" AND status:reading AND pages>=300"#).unwrap();
+		let mut values = Vec::new();
+		let sql = expression_sql(&expr, &mut values, 7);
+
+		assert!(!sql.contains("1=1"), "{}", sql);
+		assert!(!sql.contains("reading"), "{}", sql);
+		assert!(!sql.contains("300"), "{}", sql);
+		assert!(sql.contains("books.title ILIKE $8"), "{}", sql);
+		assert!(sql.contains("books.status = $9"), "{}", sql);
+		assert!(sql.contains("books.page_count >= $10"), "{}", sql);
+		assert_eq!(
+			values,
+			vec![
+				FilterValue::Text("%x' OR 1=1 --%".to_string()),
+				FilterValue::Status(ReadingStatus::Reading),
+				FilterValue::Int(300),
+			]
+		);
+	}
 }
@@ -0,0 +1,192 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
+
+use super::RepositoryError;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum JobKind {
+	// 取得元から書誌情報を取り寄せて本を登録する
+	FetchMetadata,
+	// 登録した本の表紙を取り寄せて手元に保存する
+	FetchCover,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum JobStatus {
+	Pending,
+	Running,
+	Succeeded,
+	Failed,
+}
+
+#[derive(Serialize, Debug, Clone, FromRow, PartialEq)]
+pub struct Job {
+	pub id: String,
+	#[serde(skip)]
+	pub user_id: String,
+	pub kind: JobKind,
+	pub isbn_13: String,
+	pub status: JobStatus,
+	pub attempts: i32,
+	pub max_attempts: i32,
+	// 次に実行してよい時刻。失敗した後は待ってから再試行する
+	pub run_at: DateTime<Utc>,
+	pub last_error: Option<String>,
+	pub created_at: DateTime<Utc>,
+	pub updated_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait JobRepository: Clone + Send + Sync + 'static {
+	async fn find(&self, user_id: &str, id: &str) -> Result<Job, RepositoryError>;
+	async fn enqueue(&self, user_id: &str, kind: JobKind, isbn_13: &str) -> Result<Job, RepositoryError>;
+	async fn claim(&self) -> Result<Option<Job>, RepositoryError>;
+	async fn succeed(&self, id: &str) -> Result<(), RepositoryError>;
+	async fn retry(&self, id: &str, error: &str, run_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+	async fn fail(&self, id: &str, error: &str) -> Result<(), RepositoryError>;
+}
+
+#[derive(Clone)]
+pub struct JobRepositoryForPg {
+	pool: Arc<PgPool>,
+}
+
+impl JobRepositoryForPg {
+	pub fn new(pool: PgPool) -> Self {
+		JobRepositoryForPg {
+			pool: Arc::new(pool),
+		}
+	}
+}
+
+#[async_trait]
+impl JobRepository for JobRepositoryForPg {
+	async fn find(&self, user_id: &str, id: &str) -> Result<Job, RepositoryError> {
+		let job = sqlx::query_as::<_, Job>(r#"SELECT * FROM jobs WHERE user_id = $1 AND id = $2;"#)
+			.bind(user_id)
+			.bind(id)
+			.fetch_one(self.pool.as_ref())
+			.await
+			.map_err(|err| match err {
+				sqlx::Error::RowNotFound => RepositoryError::NotFound(id.to_string()),
+				_ => RepositoryError::Unexpected(err.to_string()),
+			})?;
+
+		Ok(job)
+	}
+
+	async fn enqueue(&self, user_id: &str, kind: JobKind, isbn_13: &str) -> Result<Job, RepositoryError> {
+		// 同じ本のジョブが終わっていなければ、新しく積まずにそれを返す
+		// 一意のインデックスで重複を防ぎ、ぶつかったら積まれているものを取り直す
+		loop {
+			let inserted = sqlx::query_as::<_, Job>(
+				r#"
+					INSERT INTO jobs (id, user_id, kind, isbn_13)
+					VALUES ($1, $2, $3, $4)
+					ON CONFLICT (user_id, kind, isbn_13) WHERE status IN ('pending', 'running') DO NOTHING
+					RETURNING *;
+        "#,
+			)
+			.bind(uuid::Uuid::new_v4().to_string())
+			.bind(user_id)
+			.bind(kind)
+			.bind(isbn_13)
+			.fetch_optional(self.pool.as_ref())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+			if let Some(job) = inserted {
+				return Ok(job);
+			}
+
+			let queued = sqlx::query_as::<_, Job>(
+				r#"
+					SELECT * FROM jobs
+					WHERE user_id = $1 AND kind = $2 AND isbn_13 = $3 AND status IN ('pending', 'running');
+        "#,
+			)
+			.bind(user_id)
+			.bind(kind)
+			.bind(isbn_13)
+			.fetch_optional(self.pool.as_ref())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+			// 取り直す間に終わっていたら、もう一度積む
+			if let Some(job) = queued {
+				return Ok(job);
+			}
+		}
+	}
+
+	async fn claim(&self) -> Result<Option<Job>, RepositoryError> {
+		// 複数のワーカーが同じジョブを取らないように、ロック中の行は飛ばす
+		// 実行中のまま長く止まっているジョブはワーカーが落ちたとみなして取り直す
+		let job = sqlx::query_as::<_, Job>(
+			r#"
+				UPDATE jobs SET status = 'running', attempts = attempts + 1, locked_at = now(), updated_at = now()
+				WHERE id = (
+					SELECT id FROM jobs
+					WHERE (status = 'pending' AND run_at <= now())
+						OR (status = 'running' AND locked_at < now() - interval '10 minutes')
+					ORDER BY run_at
+					LIMIT 1
+					FOR UPDATE SKIP LOCKED
+				)
+				RETURNING *;
+      "#,
+		)
+		.fetch_optional(self.pool.as_ref())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(job)
+	}
+
+	async fn succeed(&self, id: &str) -> Result<(), RepositoryError> {
+		sqlx::query(
+			r#"UPDATE jobs SET status = 'succeeded', locked_at = NULL, last_error = NULL, updated_at = now() WHERE id = $1;"#,
+		)
+		.bind(id)
+		.execute(self.pool.as_ref())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(())
+	}
+
+	async fn retry(&self, id: &str, error: &str, run_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+		sqlx::query(
+			r#"
+				UPDATE jobs SET status = 'pending', locked_at = NULL, last_error = $2, run_at = $3, updated_at = now()
+				WHERE id = $1;
+      "#,
+		)
+		.bind(id)
+		.bind(error)
+		.bind(run_at)
+		.execute(self.pool.as_ref())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(())
+	}
+
+	async fn fail(&self, id: &str, error: &str) -> Result<(), RepositoryError> {
+		sqlx::query(
+			r#"UPDATE jobs SET status = 'failed', locked_at = NULL, last_error = $2, updated_at = now() WHERE id = $1;"#,
+		)
+		.bind(id)
+		.bind(error)
+		.execute(self.pool.as_ref())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(())
+	}
+}
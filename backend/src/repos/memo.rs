@@ -1,15 +1,50 @@
 use axum::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{Acquire, FromRow, PgPool, Postgres, Transaction};
+use sqlx::{Acquire, FromRow, PgConnection, PgPool, Postgres, Transaction};
 use std::{borrow::BorrowMut, sync::Arc};
 
-use super::RepositoryError;
+use super::{Cursor, Page, PageRequest, RepositoryError, SortOrder};
 
 #[derive(Serialize, Debug, FromRow, PartialEq)]
 pub struct Memo {
 	pub id: String,
 	pub isbn_13: String,
 	pub text: String,
+	pub created_at: DateTime<Utc>,
+	pub updated_at: DateTime<Utc>,
+	#[sqlx(default)]
+	pub tags: Vec<String>,
+}
+
+// 一覧取得の絞り込み条件
+#[derive(Debug, Clone, Default)]
+pub struct MemoFilter {
+	pub tag: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoSort {
+	#[default]
+	Added,
+	Updated,
+}
+
+impl MemoSort {
+	fn column(&self) -> &'static str {
+		match self {
+			MemoSort::Added => "created_at",
+			MemoSort::Updated => "updated_at",
+		}
+	}
+
+	fn key(&self, memo: &Memo) -> String {
+		match self {
+			MemoSort::Added => memo.created_at.to_rfc3339(),
+			MemoSort::Updated => memo.updated_at.to_rfc3339(),
+		}
+	}
 }
 
 #[derive(Deserialize, Debug)]
@@ -17,12 +52,71 @@ pub struct CreateMemo {
 	pub text: String,
 }
 
+// 外部のサービスから取り込むメモ
+#[derive(Debug)]
+pub struct ImportMemo {
+	pub text: String,
+	// 取り込み元でのメモの識別子。同じ値のメモは一度しか登録しない
+	pub source_key: String,
+	// 取り込み元でメモを書いた日時
+	pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateMemo {
+	pub text: String,
+}
+
+#[derive(Serialize, Debug, FromRow, PartialEq)]
+pub struct MemoRevision {
+	pub id: String,
+	pub memo_id: String,
+	pub text: String,
+	pub created_at: DateTime<Utc>,
+}
+
 #[async_trait]
 pub trait MemoRepository: Clone + Send + Sync + 'static {
-	async fn find(&self, id: &str) -> Result<Memo, RepositoryError>;
-	async fn find_all(&self, isbn_13: &str) -> Result<Vec<Memo>, RepositoryError>;
-	async fn create(&self, payload: CreateMemo, isbn_13: &str) -> Result<Memo, RepositoryError>;
-	async fn delete(&self, id: &str) -> Result<(), RepositoryError>;
+	async fn find(&self, user_id: &str, id: &str) -> Result<Memo, RepositoryError>;
+	async fn find_all(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		filter: &MemoFilter,
+		sort: MemoSort,
+		order: SortOrder,
+		page: &PageRequest,
+	) -> Result<Page<Memo>, RepositoryError>;
+	async fn create(
+		&self,
+		user_id: &str,
+		payload: CreateMemo,
+		isbn_13: &str,
+	) -> Result<Memo, RepositoryError>;
+	async fn import(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		payload: ImportMemo,
+	) -> Result<Option<Memo>, RepositoryError>;
+	async fn update(
+		&self,
+		user_id: &str,
+		id: &str,
+		payload: UpdateMemo,
+	) -> Result<Memo, RepositoryError>;
+	async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError>;
+	async fn find_revisions(
+		&self,
+		user_id: &str,
+		id: &str,
+	) -> Result<Vec<MemoRevision>, RepositoryError>;
+	async fn restore(
+		&self,
+		user_id: &str,
+		id: &str,
+		revision_id: &str,
+	) -> Result<Memo, RepositoryError>;
 }
 
 #[derive(Clone)]
@@ -48,53 +142,119 @@ impl MemoRepositoryForPg {
 
 #[async_trait]
 impl MemoRepository for MemoRepositoryForPg {
-	async fn find(&self, id: &str) -> Result<Memo, RepositoryError> {
+	async fn find(&self, user_id: &str, id: &str) -> Result<Memo, RepositoryError> {
 		let mut tx = self.start_transaction().await?;
 		let conn = tx
 			.acquire()
 			.await
 			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
 
-		let memo = sqlx::query_as::<_, Memo>(
-			r#"
-				SELECT * FROM memo WHERE id = $1;
-      "#,
-		)
-		.bind(id)
-		.fetch_one(conn)
-		.await
-		.map_err(|err| match err {
-			sqlx::Error::RowNotFound => RepositoryError::NotFound(id.to_string()),
-			_ => RepositoryError::Unexpected(err.to_string()),
-		})?;
-
-		Ok(memo)
+		select_memo(conn, user_id, id).await
 	}
 
-	async fn find_all(&self, isbn_13: &str) -> Result<Vec<Memo>, RepositoryError> {
+	async fn find_all(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		filter: &MemoFilter,
+		sort: MemoSort,
+		order: SortOrder,
+		page: &PageRequest,
+	) -> Result<Page<Memo>, RepositoryError> {
 		let mut tx = self.start_transaction().await?;
 		let conn = tx
 			.acquire()
 			.await
 			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
 
-		let memo = sqlx::query_as::<_, Memo>(
+		let sort_name = format!("{:?}:{:?}", sort, order);
+		let cursor = page
+			.cursor
+			.as_deref()
+			.map(|cursor| Cursor::decode(cursor, &sort_name))
+			.transpose()?;
+		let limit = page.limit();
+
+		// 並び替えの列と向きはenumから決まる固定の文字列のみを埋め込む
+		let query = format!(
 			r#"
-				SELECT * FROM memo WHERE isbn_13 = $1;
+				SELECT *, ARRAY (
+						SELECT tags.name FROM memo_tags JOIN tags ON tags.id = memo_tags.tag_id
+						WHERE memo_tags.memo_id = memo.id ORDER BY tags.name
+				) as tags FROM memo WHERE user_id = $1 AND isbn_13 = $2
+				AND ($6::text IS NULL OR EXISTS (
+					SELECT 1 FROM memo_tags JOIN tags ON tags.id = memo_tags.tag_id
+					WHERE memo_tags.memo_id = memo.id AND tags.name = $6
+				))
+				AND ($3::text IS NULL OR ({column}, id) {comparison} ($3::timestamptz, $4))
+				ORDER BY {column} {order}, id {order}
+				LIMIT $5;
       "#,
-		)
-		.bind(isbn_13)
-		.fetch_all(conn)
-		.await
-		.map_err(|err| match err {
-			sqlx::Error::RowNotFound => RepositoryError::NotFound(isbn_13.to_string()),
-			_ => RepositoryError::Unexpected(err.to_string()),
-		})?;
+			column = sort.column(),
+			comparison = order.comparison(),
+			order = order.keyword(),
+		);
+		let mut memo = sqlx::query_as::<_, Memo>(&query)
+			.bind(user_id)
+			.bind(isbn_13)
+			.bind(cursor.as_ref().map(|cursor| cursor.key.clone()))
+			.bind(cursor.as_ref().map(|cursor| cursor.id.clone()))
+			// 次のページがあるかを知るために1件多く取る
+			.bind(limit + 1)
+			.bind(&filter.tag)
+			.fetch_all(conn.borrow_mut())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
 
-		Ok(memo)
+		let next_cursor = if memo.len() as i64 > limit {
+			memo.truncate(limit as usize);
+			memo.last().map(|last| {
+				Cursor {
+					sort: sort_name.clone(),
+					key: sort.key(last),
+					id: last.id.clone(),
+				}
+				.encode()
+			})
+		} else {
+			None
+		};
+
+		let total = if page.with_total {
+			let total: i64 =
+				sqlx::query_scalar(
+					r#"
+						SELECT count(*) FROM memo WHERE user_id = $1 AND isbn_13 = $2
+						AND ($3::text IS NULL OR EXISTS (
+							SELECT 1 FROM memo_tags JOIN tags ON tags.id = memo_tags.tag_id
+							WHERE memo_tags.memo_id = memo.id AND tags.name = $3
+						));
+          "#,
+				)
+				.bind(user_id)
+				.bind(isbn_13)
+				.bind(&filter.tag)
+				.fetch_one(conn)
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+			Some(total)
+		} else {
+			None
+		};
+
+		Ok(Page {
+			items: memo,
+			next_cursor,
+			total,
+		})
 	}
 
-	async fn create(&self, payload: CreateMemo, isbn_13: &str) -> Result<Memo, RepositoryError> {
+	async fn create(
+		&self,
+		user_id: &str,
+		payload: CreateMemo,
+		isbn_13: &str,
+	) -> Result<Memo, RepositoryError> {
 		let mut tx = self.start_transaction().await?;
 		let conn = tx
 			.acquire()
@@ -103,7 +263,8 @@ impl MemoRepository for MemoRepositoryForPg {
 
 		// メモを登録したい本が存在しているかを探す
 		let book_exist: bool =
-			sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM books WHERE isbn_13 = $1);"#)
+			sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM books WHERE user_id = $1 AND isbn_13 = $2);"#)
+				.bind(user_id)
 				.bind(isbn_13)
 				.fetch_one(conn.borrow_mut())
 				.await
@@ -114,13 +275,62 @@ impl MemoRepository for MemoRepositoryForPg {
 
 		let memo_id = uuid::Uuid::new_v4().to_string();
 
-		let created_memo = sqlx::query_as::<_, Memo>(
-			r#"INSERT INTO memo (id, isbn_13, text) VALUES ($1, $2, $3) RETURNING *;"#,
+		sqlx::query(r#"INSERT INTO memo (id, user_id, isbn_13, text) VALUES ($1, $2, $3, $4);"#)
+			.bind(&memo_id)
+			.bind(user_id)
+			.bind(isbn_13)
+			.bind(&payload.text)
+			.execute(conn.borrow_mut())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		let created_memo = select_memo(conn, user_id, &memo_id).await?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(created_memo)
+	}
+
+	async fn import(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		payload: ImportMemo,
+	) -> Result<Option<Memo>, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let book_exist: bool =
+			sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM books WHERE user_id = $1 AND isbn_13 = $2);"#)
+				.bind(user_id)
+				.bind(isbn_13)
+				.fetch_one(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if !book_exist {
+			return Err(RepositoryError::NotFound(isbn_13.to_string()));
+		};
+
+		// 取り込み済みのメモは登録せずにNoneを返す
+		let imported_memo = sqlx::query_as::<_, Memo>(
+			r#"
+				INSERT INTO memo (id, user_id, isbn_13, text, source_key, created_at, updated_at)
+				VALUES ($1, $2, $3, $4, $5, COALESCE($6, now()), COALESCE($6, now()))
+				ON CONFLICT (user_id, isbn_13, md5(source_key)) WHERE source_key IS NOT NULL DO NOTHING
+				RETURNING *;
+      "#,
 		)
-		.bind(&memo_id)
+		.bind(uuid::Uuid::new_v4().to_string())
+		.bind(user_id)
 		.bind(isbn_13)
 		.bind(&payload.text)
-		.fetch_one(conn)
+		.bind(&payload.source_key)
+		.bind(payload.created_at)
+		.fetch_optional(conn)
 		.await
 		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
 
@@ -128,10 +338,10 @@ impl MemoRepository for MemoRepositoryForPg {
 			.await
 			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
 
-		Ok(created_memo)
+		Ok(imported_memo)
 	}
 
-	async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+	async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError> {
 		let mut tx = self
 			.start_transaction()
 			.await
@@ -143,7 +353,8 @@ impl MemoRepository for MemoRepositoryForPg {
 
 		// 削除したいメモが存在しているかを探す
 		let memo_exist: bool =
-			sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM memo WHERE id = $1);"#)
+			sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM memo WHERE user_id = $1 AND id = $2);"#)
+				.bind(user_id)
 				.bind(id)
 				.fetch_one(conn.borrow_mut())
 				.await
@@ -152,7 +363,8 @@ impl MemoRepository for MemoRepositoryForPg {
 			return Err(RepositoryError::NotFound(id.to_string()));
 		};
 
-		sqlx::query(r#"DELETE FROM memo WHERE id = $1 returning *;"#)
+		sqlx::query(r#"DELETE FROM memo WHERE user_id = $1 AND id = $2 returning *;"#)
+			.bind(user_id)
 			.bind(id)
 			.execute(conn)
 			.await
@@ -164,4 +376,161 @@ impl MemoRepository for MemoRepositoryForPg {
 
 		Ok(())
 	}
+
+	async fn update(
+		&self,
+		user_id: &str,
+		id: &str,
+		payload: UpdateMemo,
+	) -> Result<Memo, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let memo = replace_text(conn, user_id, id, &payload.text).await?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(memo)
+	}
+
+	async fn find_revisions(
+		&self,
+		user_id: &str,
+		id: &str,
+	) -> Result<Vec<MemoRevision>, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		// 他のユーザーのメモの履歴は返さない
+		let memo_exist: bool =
+			sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM memo WHERE user_id = $1 AND id = $2);"#)
+				.bind(user_id)
+				.bind(id)
+				.fetch_one(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if !memo_exist {
+			return Err(RepositoryError::NotFound(id.to_string()));
+		};
+
+		let revisions = sqlx::query_as::<_, MemoRevision>(
+			r#"
+				SELECT * FROM memo_revisions WHERE memo_id = $1 ORDER BY created_at DESC, id;
+      "#,
+		)
+		.bind(id)
+		.fetch_all(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(revisions)
+	}
+
+	async fn restore(
+		&self,
+		user_id: &str,
+		id: &str,
+		revision_id: &str,
+	) -> Result<Memo, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let text: String = sqlx::query_scalar(
+			r#"
+				SELECT memo_revisions.text FROM memo_revisions
+				JOIN memo ON memo.id = memo_revisions.memo_id
+				WHERE memo.user_id = $1 AND memo.id = $2 AND memo_revisions.id = $3;
+      "#,
+		)
+		.bind(user_id)
+		.bind(id)
+		.bind(revision_id)
+		.fetch_one(conn.borrow_mut())
+		.await
+		.map_err(|err| match err {
+			sqlx::Error::RowNotFound => RepositoryError::NotFound(revision_id.to_string()),
+			_ => RepositoryError::Unexpected(err.to_string()),
+		})?;
+
+		// 復元も1回の更新として扱い、復元前の本文を履歴に残す
+		let memo = replace_text(conn, user_id, id, &text).await?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(memo)
+	}
+}
+
+// 現在の本文を履歴に移してからメモを書き換える
+async fn replace_text(
+	conn: &mut PgConnection,
+	user_id: &str,
+	id: &str,
+	text: &str,
+) -> Result<Memo, RepositoryError> {
+	let current_text: String =
+		sqlx::query_scalar(r#"SELECT text FROM memo WHERE user_id = $1 AND id = $2 FOR UPDATE;"#)
+			.bind(user_id)
+			.bind(id)
+			.fetch_one(conn.borrow_mut())
+			.await
+			.map_err(|err| match err {
+				sqlx::Error::RowNotFound => RepositoryError::NotFound(id.to_string()),
+				_ => RepositoryError::Unexpected(err.to_string()),
+			})?;
+	// 本文が変わらない保存では版を増やさず、更新日時もそのままにする
+	if current_text == text {
+		return select_memo(conn, user_id, id).await;
+	}
+
+	sqlx::query(r#"INSERT INTO memo_revisions (id, memo_id, text) VALUES ($1, $2, $3);"#)
+		.bind(uuid::Uuid::new_v4().to_string())
+		.bind(id)
+		.bind(&current_text)
+		.execute(conn.borrow_mut())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+	sqlx::query(r#"UPDATE memo SET text = $3, updated_at = now() WHERE user_id = $1 AND id = $2;"#)
+		.bind(user_id)
+		.bind(id)
+		.bind(text)
+		.execute(conn.borrow_mut())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+	select_memo(conn, user_id, id).await
+}
+
+// タグ付きでメモを1件読む
+async fn select_memo(conn: &mut PgConnection, user_id: &str, id: &str) -> Result<Memo, RepositoryError> {
+	sqlx::query_as::<_, Memo>(
+		r#"
+			SELECT *, ARRAY (
+					SELECT tags.name FROM memo_tags JOIN tags ON tags.id = memo_tags.tag_id
+					WHERE memo_tags.memo_id = memo.id ORDER BY tags.name
+			) as tags FROM memo WHERE user_id = $1 AND id = $2;
+		"#,
+	)
+	.bind(user_id)
+	.bind(id)
+	.fetch_one(conn)
+	.await
+	.map_err(|err| match err {
+		sqlx::Error::RowNotFound => RepositoryError::NotFound(id.to_string()),
+		_ => RepositoryError::Unexpected(err.to_string()),
+	})
 }
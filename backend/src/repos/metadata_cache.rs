@@ -0,0 +1,120 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
+
+use super::RepositoryError;
+
+#[derive(Serialize, Debug, Clone, FromRow, PartialEq)]
+pub struct CachedPayload {
+	pub provider: String,
+	pub isbn_13: String,
+	// 取得元が返した本文そのもの
+	pub payload: String,
+	pub fetched_at: DateTime<Utc>,
+	pub expires_at: DateTime<Utc>,
+}
+
+impl CachedPayload {
+	pub fn is_expired(&self) -> bool {
+		self.expires_at <= Utc::now()
+	}
+}
+
+// 指定しなかった条件では絞り込まない
+#[derive(Deserialize, Debug, Default)]
+pub struct PurgeCache {
+	pub provider: Option<String>,
+	pub isbn_13: Option<String>,
+	// trueなら期限切れのものだけ消す
+	#[serde(default)]
+	pub expired: bool,
+}
+
+#[async_trait]
+pub trait MetadataCacheRepository: Clone + Send + Sync + 'static {
+	async fn find(&self, provider: &str, isbn_13: &str) -> Result<Option<CachedPayload>, RepositoryError>;
+	async fn store(
+		&self,
+		provider: &str,
+		isbn_13: &str,
+		payload: &str,
+		expires_at: DateTime<Utc>,
+	) -> Result<(), RepositoryError>;
+	async fn purge(&self, filter: &PurgeCache) -> Result<u64, RepositoryError>;
+}
+
+#[derive(Clone)]
+pub struct MetadataCacheRepositoryForPg {
+	pool: Arc<PgPool>,
+}
+
+impl MetadataCacheRepositoryForPg {
+	pub fn new(pool: PgPool) -> Self {
+		MetadataCacheRepositoryForPg {
+			pool: Arc::new(pool),
+		}
+	}
+}
+
+#[async_trait]
+impl MetadataCacheRepository for MetadataCacheRepositoryForPg {
+	async fn find(&self, provider: &str, isbn_13: &str) -> Result<Option<CachedPayload>, RepositoryError> {
+		let cached = sqlx::query_as::<_, CachedPayload>(
+			r#"SELECT * FROM metadata_cache WHERE provider = $1 AND isbn_13 = $2;"#,
+		)
+		.bind(provider)
+		.bind(isbn_13)
+		.fetch_optional(self.pool.as_ref())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(cached)
+	}
+
+	async fn store(
+		&self,
+		provider: &str,
+		isbn_13: &str,
+		payload: &str,
+		expires_at: DateTime<Utc>,
+	) -> Result<(), RepositoryError> {
+		sqlx::query(
+			r#"
+				INSERT INTO metadata_cache (provider, isbn_13, payload, expires_at)
+				VALUES ($1, $2, $3, $4)
+				ON CONFLICT (provider, isbn_13)
+				DO UPDATE SET payload = EXCLUDED.payload, fetched_at = now(), expires_at = EXCLUDED.expires_at;
+      "#,
+		)
+		.bind(provider)
+		.bind(isbn_13)
+		.bind(payload)
+		.bind(expires_at)
+		.execute(self.pool.as_ref())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(())
+	}
+
+	async fn purge(&self, filter: &PurgeCache) -> Result<u64, RepositoryError> {
+		let result = sqlx::query(
+			r#"
+				DELETE FROM metadata_cache
+				WHERE ($1::TEXT IS NULL OR provider = $1)
+					AND ($2::TEXT IS NULL OR isbn_13 = $2)
+					AND (NOT $3 OR expires_at <= now());
+      "#,
+		)
+		.bind(&filter.provider)
+		.bind(&filter.isbn_13)
+		.bind(filter.expired)
+		.execute(self.pool.as_ref())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(result.rows_affected())
+	}
+}
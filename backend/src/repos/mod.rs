@@ -1,8 +1,16 @@
 pub mod book;
 pub mod memo;
 pub mod auth;
+pub mod reading;
+pub mod search;
+pub mod tag;
+pub mod shelf;
+pub mod job;
+pub mod metadata_cache;
 
 use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -13,12 +21,99 @@ pub enum RepositoryError {
 	NotFound(String),
 	#[error("Registered, isbn is {0}")]
 	Registered(String),
+	#[error("InvalidCursor, cursor is {0}")]
+	InvalidCursor(String),
+	#[error("InvalidTransition, {0}")]
+	InvalidTransition(String),
+	#[error("InvalidInput, {0}")]
+	InvalidInput(String),
 }
 
 pub fn handle_repository_error(err: RepositoryError) -> StatusCode {
 	match err {
 		RepositoryError::NotFound(_) => StatusCode::NOT_FOUND,
 		RepositoryError::Registered(_) => StatusCode::BAD_REQUEST,
+		RepositoryError::InvalidCursor(_) => StatusCode::BAD_REQUEST,
+		RepositoryError::InvalidTransition(_) => StatusCode::CONFLICT,
+		RepositoryError::InvalidInput(_) => StatusCode::BAD_REQUEST,
 		_ => StatusCode::INTERNAL_SERVER_ERROR,
 	}
 }
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+	#[default]
+	Asc,
+	Desc,
+}
+
+impl SortOrder {
+	pub fn keyword(&self) -> &'static str {
+		match self {
+			SortOrder::Asc => "ASC",
+			SortOrder::Desc => "DESC",
+		}
+	}
+
+	// キーセットページネーションで次のページを取るための比較演算子
+	pub fn comparison(&self) -> &'static str {
+		match self {
+			SortOrder::Asc => ">",
+			SortOrder::Desc => "<",
+		}
+	}
+}
+
+const DEFAULT_PAGE_LIMIT: i64 = 50;
+const MAX_PAGE_LIMIT: i64 = 200;
+
+// 一覧取得の1ページ分の指定
+#[derive(Debug, Clone, Default)]
+pub struct PageRequest {
+	pub limit: Option<i64>,
+	pub cursor: Option<String>,
+	pub with_total: bool,
+}
+
+impl PageRequest {
+	pub fn limit(&self) -> i64 {
+		self
+			.limit
+			.unwrap_or(DEFAULT_PAGE_LIMIT)
+			.clamp(1, MAX_PAGE_LIMIT)
+	}
+}
+
+#[derive(Serialize, Debug)]
+pub struct Page<T> {
+	pub items: Vec<T>,
+	pub next_cursor: Option<String>,
+	pub total: Option<i64>,
+}
+
+// 前のページの最後の行の並び替えキーと主キーを持つ不透明なカーソル
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Cursor {
+	pub sort: String,
+	pub key: String,
+	pub id: String,
+}
+
+impl Cursor {
+	pub fn encode(&self) -> String {
+		URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+	}
+
+	// 別の並び順で発行されたカーソルは受け付けない
+	pub fn decode(cursor: &str, sort: &str) -> Result<Self, RepositoryError> {
+		let invalid = || RepositoryError::InvalidCursor(cursor.to_string());
+		let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+		let decoded = serde_json::from_slice::<Cursor>(&bytes).map_err(|_| invalid())?;
+		if decoded.sort != sort {
+			return Err(invalid());
+		}
+
+		Ok(decoded)
+	}
+}
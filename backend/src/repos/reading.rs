@@ -0,0 +1,539 @@
+use axum::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, FromRow, PgPool, Postgres, Transaction};
+use std::{borrow::BorrowMut, sync::Arc};
+use validator::{Validate, ValidationError};
+
+use super::RepositoryError;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ReadingStatus {
+	#[default]
+	WantToRead,
+	Reading,
+	Finished,
+	Abandoned,
+}
+
+impl ReadingStatus {
+	// 再読は読了・中断からreadingに戻すことで新しいサイクルとして記録する
+	fn can_transition_to(&self, to: ReadingStatus) -> bool {
+		use ReadingStatus::*;
+		matches!(
+			(self, to),
+			(WantToRead, Reading)
+				| (WantToRead, Finished)
+				| (Reading, Finished)
+				| (Reading, Abandoned)
+				| (Finished, Reading)
+				| (Finished, WantToRead)
+				| (Abandoned, Reading)
+				| (Abandoned, WantToRead)
+		)
+	}
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChangeStatus {
+	pub status: ReadingStatus,
+	// 省略した場合は今日の日付
+	pub date: Option<NaiveDate>,
+	pub note: Option<String>,
+}
+
+#[derive(Serialize, Debug, FromRow, PartialEq)]
+pub struct ReadingState {
+	pub status: ReadingStatus,
+	pub started_on: Option<NaiveDate>,
+	pub finished_on: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Debug, FromRow, PartialEq)]
+pub struct ReadingCycle {
+	pub id: String,
+	pub started_on: Option<NaiveDate>,
+	pub finished_on: Option<NaiveDate>,
+	pub outcome: Option<ReadingStatus>,
+	pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, FromRow, PartialEq)]
+pub struct ReadingStatusEvent {
+	pub id: String,
+	pub cycle_id: Option<String>,
+	pub from_status: ReadingStatus,
+	pub to_status: ReadingStatus,
+	pub occurred_on: NaiveDate,
+	pub note: Option<String>,
+	pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReadingHistory {
+	#[serde(flatten)]
+	pub state: ReadingState,
+	pub cycles: Vec<ReadingCycle>,
+	pub events: Vec<ReadingStatusEvent>,
+}
+
+// 読書ペースの計算に使う直近の期間
+const PACE_WINDOW_DAYS: i64 = 14;
+
+#[derive(Deserialize, Debug, Validate)]
+#[validate(schema(function = "validate_progress"))]
+pub struct CreateProgress {
+	#[validate(range(min = 0))]
+	pub page: Option<i32>,
+	#[validate(range(min = 0.0, max = 100.0))]
+	pub percent: Option<f64>,
+	pub note: Option<String>,
+}
+
+fn validate_progress(payload: &CreateProgress) -> Result<(), ValidationError> {
+	if payload.page.is_none() && payload.percent.is_none() {
+		return Err(ValidationError::new("page or percent is required"));
+	}
+
+	Ok(())
+}
+
+#[derive(Serialize, Debug, FromRow, PartialEq)]
+pub struct ReadingProgress {
+	pub id: String,
+	pub page: Option<i32>,
+	pub percent: Option<f64>,
+	pub note: Option<String>,
+	pub recorded_at: DateTime<Utc>,
+}
+
+impl ReadingProgress {
+	// ページ数が分かっている場合はページからも割合を求める
+	fn to_percent(&self, page_count: Option<i32>) -> Option<f64> {
+		self.percent.or_else(|| match (self.page, page_count) {
+			(Some(page), Some(page_count)) if page_count > 0 => {
+				Some((page as f64 / page_count as f64 * 100.0).min(100.0))
+			}
+			_ => None,
+		})
+	}
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProgressHistory {
+	pub page_count: Option<i32>,
+	pub current_percent: Option<f64>,
+	// 直近の記録から求めた1日あたりの進捗（％）
+	pub percent_per_day: Option<f64>,
+	pub estimated_finish_on: Option<NaiveDate>,
+	pub entries: Vec<ReadingProgress>,
+}
+
+// 直近PACE_WINDOW_DAYS日の記録の最初と最後の差から読了日を見積もる
+fn estimate(entries: &[ReadingProgress], page_count: Option<i32>) -> ProgressHistory {
+	let points: Vec<(DateTime<Utc>, f64)> = entries
+		.iter()
+		.filter_map(|entry| {
+			entry
+				.to_percent(page_count)
+				.map(|percent| (entry.recorded_at, percent))
+		})
+		.collect();
+	let current = points.last().copied();
+
+	let percent_per_day = current.and_then(|(latest_at, latest)| {
+		let window_start = latest_at - Duration::days(PACE_WINDOW_DAYS);
+		let (earliest_at, earliest) = points
+			.iter()
+			.copied()
+			.find(|(recorded_at, _)| *recorded_at >= window_start)?;
+		let days = (latest_at - earliest_at).num_seconds() as f64 / 86400.0;
+		if days <= 0.0 || latest <= earliest {
+			return None;
+		}
+		Some((latest - earliest) / days)
+	});
+
+	let estimated_finish_on = match (current, percent_per_day) {
+		// 進捗がほとんど無いと読了日が表せる範囲を超えるので、見積もらない
+		(Some((latest_at, latest)), Some(pace)) if latest < 100.0 && pace > f64::EPSILON => {
+			let days_left = ((100.0 - latest) / pace).ceil() as i64;
+			Duration::try_days(days_left)
+				.and_then(|days_left| latest_at.checked_add_signed(days_left))
+				.map(|finish_at| finish_at.date_naive())
+		}
+		_ => None,
+	};
+
+	ProgressHistory {
+		page_count,
+		current_percent: current.map(|(_, percent)| percent),
+		percent_per_day,
+		estimated_finish_on,
+		entries: Vec::new(),
+	}
+}
+
+#[async_trait]
+pub trait ReadingRepository: Clone + Send + Sync + 'static {
+	async fn change_status(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		payload: ChangeStatus,
+	) -> Result<ReadingState, RepositoryError>;
+	async fn find_history(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+	) -> Result<ReadingHistory, RepositoryError>;
+	async fn add_progress(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		payload: CreateProgress,
+	) -> Result<ReadingProgress, RepositoryError>;
+	async fn find_progress(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+	) -> Result<ProgressHistory, RepositoryError>;
+}
+
+#[derive(Clone)]
+pub struct ReadingRepositoryForPg {
+	pool: Arc<PgPool>,
+}
+
+impl ReadingRepositoryForPg {
+	pub fn new(pool: PgPool) -> Self {
+		ReadingRepositoryForPg {
+			pool: Arc::new(pool),
+		}
+	}
+
+	async fn start_transaction(&self) -> Result<Transaction<'_, Postgres>, RepositoryError> {
+		self
+			.pool
+			.begin()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))
+	}
+}
+
+#[async_trait]
+impl ReadingRepository for ReadingRepositoryForPg {
+	async fn change_status(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		payload: ChangeStatus,
+	) -> Result<ReadingState, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let current = sqlx::query_as::<_, ReadingState>(
+			r#"
+				SELECT status, started_on, finished_on FROM books
+				WHERE user_id = $1 AND isbn_13 = $2 FOR UPDATE;
+      "#,
+		)
+		.bind(user_id)
+		.bind(isbn_13)
+		.fetch_one(conn.borrow_mut())
+		.await
+		.map_err(|err| match err {
+			sqlx::Error::RowNotFound => RepositoryError::NotFound(isbn_13.to_string()),
+			_ => RepositoryError::Unexpected(err.to_string()),
+		})?;
+
+		if !current.status.can_transition_to(payload.status) {
+			return Err(RepositoryError::InvalidTransition(format!(
+				"{:?} -> {:?}",
+				current.status, payload.status
+			)));
+		}
+
+		let date = payload.date.unwrap_or_else(|| Utc::now().date_naive());
+		let (started_on, finished_on, cycle_id) = match payload.status {
+			// 読み始めるたびに新しいサイクルを作る
+			ReadingStatus::Reading => {
+				let cycle_id = uuid::Uuid::new_v4().to_string();
+				sqlx::query(
+					r#"INSERT INTO reading_cycles (id, user_id, isbn_13, started_on) VALUES ($1, $2, $3, $4);"#,
+				)
+				.bind(&cycle_id)
+				.bind(user_id)
+				.bind(isbn_13)
+				.bind(date)
+				.execute(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+				(Some(date), None, Some(cycle_id))
+			}
+			ReadingStatus::Finished | ReadingStatus::Abandoned => {
+				// 読書中のサイクルを閉じる。読みたい本から直接読了にした場合は開始日不明のサイクルを作る
+				let cycle_id: Option<String> = sqlx::query_scalar(
+					r#"
+						UPDATE reading_cycles SET finished_on = $3, outcome = $4
+						WHERE user_id = $1 AND isbn_13 = $2 AND outcome IS NULL
+						RETURNING id;
+          "#,
+				)
+				.bind(user_id)
+				.bind(isbn_13)
+				.bind(date)
+				.bind(payload.status)
+				.fetch_optional(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+				let cycle_id = match cycle_id {
+					Some(cycle_id) => cycle_id,
+					None => {
+						let cycle_id = uuid::Uuid::new_v4().to_string();
+						sqlx::query(
+							r#"
+								INSERT INTO reading_cycles (id, user_id, isbn_13, finished_on, outcome)
+								VALUES ($1, $2, $3, $4, $5);
+              "#,
+						)
+						.bind(&cycle_id)
+						.bind(user_id)
+						.bind(isbn_13)
+						.bind(date)
+						.bind(payload.status)
+						.execute(conn.borrow_mut())
+						.await
+						.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+						cycle_id
+					}
+				};
+				(current.started_on, Some(date), Some(cycle_id))
+			}
+			ReadingStatus::WantToRead => (None, None, None),
+		};
+
+		let state = sqlx::query_as::<_, ReadingState>(
+			r#"
+				UPDATE books SET status = $3, started_on = $4, finished_on = $5, updated_at = now()
+				WHERE user_id = $1 AND isbn_13 = $2
+				RETURNING status, started_on, finished_on;
+      "#,
+		)
+		.bind(user_id)
+		.bind(isbn_13)
+		.bind(payload.status)
+		.bind(started_on)
+		.bind(finished_on)
+		.fetch_one(conn.borrow_mut())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		sqlx::query(
+			r#"
+				INSERT INTO reading_status_events (id, user_id, isbn_13, cycle_id, from_status, to_status, occurred_on, note)
+				VALUES ($1, $2, $3, $4, $5, $6, $7, $8);
+      "#,
+		)
+		.bind(uuid::Uuid::new_v4().to_string())
+		.bind(user_id)
+		.bind(isbn_13)
+		.bind(cycle_id)
+		.bind(current.status)
+		.bind(payload.status)
+		.bind(date)
+		.bind(&payload.note)
+		.execute(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(state)
+	}
+
+	async fn find_history(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+	) -> Result<ReadingHistory, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let state = sqlx::query_as::<_, ReadingState>(
+			r#"
+				SELECT status, started_on, finished_on FROM books WHERE user_id = $1 AND isbn_13 = $2;
+      "#,
+		)
+		.bind(user_id)
+		.bind(isbn_13)
+		.fetch_one(conn.borrow_mut())
+		.await
+		.map_err(|err| match err {
+			sqlx::Error::RowNotFound => RepositoryError::NotFound(isbn_13.to_string()),
+			_ => RepositoryError::Unexpected(err.to_string()),
+		})?;
+
+		let cycles = sqlx::query_as::<_, ReadingCycle>(
+			r#"
+				SELECT * FROM reading_cycles WHERE user_id = $1 AND isbn_13 = $2 ORDER BY created_at, id;
+      "#,
+		)
+		.bind(user_id)
+		.bind(isbn_13)
+		.fetch_all(conn.borrow_mut())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let events = sqlx::query_as::<_, ReadingStatusEvent>(
+			r#"
+				SELECT * FROM reading_status_events WHERE user_id = $1 AND isbn_13 = $2 ORDER BY created_at, id;
+      "#,
+		)
+		.bind(user_id)
+		.bind(isbn_13)
+		.fetch_all(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(ReadingHistory {
+			state,
+			cycles,
+			events,
+		})
+	}
+
+	async fn add_progress(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+		payload: CreateProgress,
+	) -> Result<ReadingProgress, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		// 進捗を記録したい本が存在しているかを探す
+		let book_exist: bool =
+			sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM books WHERE user_id = $1 AND isbn_13 = $2);"#)
+				.bind(user_id)
+				.bind(isbn_13)
+				.fetch_one(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if !book_exist {
+			return Err(RepositoryError::NotFound(isbn_13.to_string()));
+		};
+
+		let progress = sqlx::query_as::<_, ReadingProgress>(
+			r#"
+				INSERT INTO reading_progress (id, user_id, isbn_13, page, percent, note)
+				VALUES ($1, $2, $3, $4, $5, $6)
+				RETURNING id, page, percent, note, recorded_at;
+      "#,
+		)
+		.bind(uuid::Uuid::new_v4().to_string())
+		.bind(user_id)
+		.bind(isbn_13)
+		.bind(payload.page)
+		.bind(payload.percent)
+		.bind(&payload.note)
+		.fetch_one(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(progress)
+	}
+
+	async fn find_progress(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+	) -> Result<ProgressHistory, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let page_count: Option<i32> =
+			sqlx::query_scalar(r#"SELECT page_count FROM books WHERE user_id = $1 AND isbn_13 = $2;"#)
+				.bind(user_id)
+				.bind(isbn_13)
+				.fetch_one(conn.borrow_mut())
+				.await
+				.map_err(|err| match err {
+					sqlx::Error::RowNotFound => RepositoryError::NotFound(isbn_13.to_string()),
+					_ => RepositoryError::Unexpected(err.to_string()),
+				})?;
+
+		let entries = sqlx::query_as::<_, ReadingProgress>(
+			r#"
+				SELECT id, page, percent, note, recorded_at FROM reading_progress
+				WHERE user_id = $1 AND isbn_13 = $2 ORDER BY recorded_at, id;
+      "#,
+		)
+		.bind(user_id)
+		.bind(isbn_13)
+		.fetch_all(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let mut history = estimate(&entries, page_count);
+		history.entries = entries;
+
+		Ok(history)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry(recorded_at: DateTime<Utc>, percent: f64) -> ReadingProgress {
+		ReadingProgress {
+			id: recorded_at.to_rfc3339(),
+			page: None,
+			percent: Some(percent),
+			note: None,
+			recorded_at,
+		}
+	}
+
+	#[test]
+	fn estimate_finish_date_from_pace() {
+		let started_at = Utc::now();
+		let entries = [entry(started_at, 10.0), entry(started_at + Duration::days(2), 30.0)];
+		let history = estimate(&entries, None);
+		assert_eq!(history.percent_per_day, Some(10.0));
+		assert_eq!(
+			history.estimated_finish_on,
+			Some((started_at + Duration::days(9)).date_naive())
+		);
+	}
+
+	#[test]
+	fn estimate_gives_up_when_pace_is_tiny() {
+		let started_at = Utc::now();
+		let entries = [entry(started_at, 10.0), entry(started_at + Duration::days(1), 10.00000000001)];
+		let history = estimate(&entries, None);
+		assert_eq!(history.estimated_finish_on, None);
+	}
+}
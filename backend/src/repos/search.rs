@@ -0,0 +1,211 @@
+use axum::async_trait;
+use serde::Serialize;
+use sqlx::{Acquire, FromRow, PgPool, Postgres, Transaction};
+use std::{borrow::BorrowMut, collections::HashMap, sync::Arc};
+
+use super::book::BookInfo;
+use super::memo::Memo;
+use super::RepositoryError;
+use crate::modules::snippet::{build_snippet, count_matches, Snippet};
+
+// 検索語ごとに取り出す候補の上限
+const CANDIDATE_LIMIT: i64 = 500;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+	MemoText,
+	Title,
+	Authors,
+	Description,
+}
+
+impl SearchField {
+	// 同じ一致回数ならタイトルや著者での一致を上位にする
+	fn weight(&self) -> f64 {
+		match self {
+			SearchField::Title => 4.0,
+			SearchField::Authors => 3.0,
+			SearchField::MemoText => 2.0,
+			SearchField::Description => 1.0,
+		}
+	}
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchHit {
+	pub field: SearchField,
+	pub score: f64,
+	pub snippet: Snippet,
+	pub memo: Option<Memo>,
+	pub book: BookInfo,
+}
+
+#[derive(FromRow)]
+struct BookFieldMatch {
+	isbn_13: String,
+	field: String,
+	matched_text: String,
+}
+
+#[async_trait]
+pub trait SearchRepository: Clone + Send + Sync + 'static {
+	async fn search(
+		&self,
+		user_id: &str,
+		terms: &[String],
+		limit: usize,
+	) -> Result<Vec<SearchHit>, RepositoryError>;
+}
+
+#[derive(Clone)]
+pub struct SearchRepositoryForPg {
+	pool: Arc<PgPool>,
+}
+
+impl SearchRepositoryForPg {
+	pub fn new(pool: PgPool) -> Self {
+		SearchRepositoryForPg {
+			pool: Arc::new(pool),
+		}
+	}
+
+	async fn start_transaction(&self) -> Result<Transaction<'_, Postgres>, RepositoryError> {
+		self
+			.pool
+			.begin()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))
+	}
+}
+
+// LIKEの特殊文字をエスケープして部分一致のパターンにする
+pub fn like_pattern(term: &str) -> String {
+	let escaped = term
+		.replace('\\', "\\\\")
+		.replace('%', "\\%")
+		.replace('_', "\\_");
+	format!("%{}%", escaped)
+}
+
+fn score(field: SearchField, text: &str, terms: &[String]) -> f64 {
+	let matches = count_matches(text, terms).max(1) as f64;
+	field.weight() * (1.0 + matches.ln())
+}
+
+#[async_trait]
+impl SearchRepository for SearchRepositoryForPg {
+	async fn search(
+		&self,
+		user_id: &str,
+		terms: &[String],
+		limit: usize,
+	) -> Result<Vec<SearchHit>, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let patterns: Vec<String> = terms.iter().map(|term| like_pattern(term)).collect();
+
+		// 全ての検索語を含むメモ
+		let memo_list = sqlx::query_as::<_, Memo>(
+			r#"
+				SELECT * FROM memo WHERE user_id = $1 AND text ILIKE ALL($2) LIMIT $3;
+      "#,
+		)
+		.bind(user_id)
+		.bind(&patterns)
+		.bind(CANDIDATE_LIMIT)
+		.fetch_all(conn.borrow_mut())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		// 全ての検索語を含む本のタイトル・著者・紹介文
+		let book_matches = sqlx::query_as::<_, BookFieldMatch>(
+			r#"
+				SELECT isbn_13, 'title' AS field, title AS matched_text FROM books
+				WHERE user_id = $1 AND title ILIKE ALL($2)
+				UNION ALL
+				SELECT isbn_13, 'description' AS field, description AS matched_text FROM books
+				WHERE user_id = $1 AND description ILIKE ALL($2)
+				UNION ALL
+				SELECT isbn_13, 'authors' AS field, string_agg(author_name, ', ') AS matched_text FROM authors
+				WHERE user_id = $1 GROUP BY isbn_13
+				HAVING string_agg(author_name, ', ') ILIKE ALL($2)
+				LIMIT $3;
+      "#,
+		)
+		.bind(user_id)
+		.bind(&patterns)
+		.bind(CANDIDATE_LIMIT)
+		.fetch_all(conn.borrow_mut())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		// 一致した行が属する本をまとめて取得する
+		let mut isbn_list: Vec<String> = memo_list
+			.iter()
+			.map(|memo| memo.isbn_13.clone())
+			.chain(book_matches.iter().map(|book_match| book_match.isbn_13.clone()))
+			.collect();
+		isbn_list.sort();
+		isbn_list.dedup();
+
+		let books: HashMap<String, BookInfo> = sqlx::query_as::<_, BookInfo>(
+			r#"
+				SELECT *, ARRAY (
+						SELECT author_name FROM authors
+						WHERE authors.user_id = books.user_id AND authors.isbn_13 = books.isbn_13
+				) as authors, ARRAY (
+						SELECT tags.name FROM book_tags JOIN tags ON tags.id = book_tags.tag_id
+						WHERE book_tags.user_id = books.user_id AND book_tags.isbn_13 = books.isbn_13
+						ORDER BY tags.name
+				) as tags FROM books WHERE user_id = $1 AND isbn_13 = ANY($2);
+      "#,
+		)
+		.bind(user_id)
+		.bind(&isbn_list)
+		.fetch_all(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?
+		.into_iter()
+		.map(|book_info| (book_info.isbn_13.clone(), book_info))
+		.collect();
+
+		let mut hits: Vec<SearchHit> = Vec::new();
+		for memo in memo_list {
+			if let Some(book_info) = books.get(&memo.isbn_13) {
+				hits.push(SearchHit {
+					field: SearchField::MemoText,
+					score: score(SearchField::MemoText, &memo.text, terms),
+					snippet: build_snippet(&memo.text, terms),
+					memo: Some(memo),
+					book: book_info.clone(),
+				});
+			}
+		}
+		for book_match in book_matches {
+			let field = match book_match.field.as_str() {
+				"title" => SearchField::Title,
+				"authors" => SearchField::Authors,
+				_ => SearchField::Description,
+			};
+			if let Some(book_info) = books.get(&book_match.isbn_13) {
+				hits.push(SearchHit {
+					field,
+					score: score(field, &book_match.matched_text, terms),
+					snippet: build_snippet(&book_match.matched_text, terms),
+					memo: None,
+					book: book_info.clone(),
+				});
+			}
+		}
+
+		hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+		hits.truncate(limit);
+
+		Ok(hits)
+	}
+}
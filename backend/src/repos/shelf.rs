@@ -0,0 +1,714 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, FromRow, PgConnection, PgPool, Postgres, Transaction};
+use std::{borrow::BorrowMut, sync::Arc};
+use validator::Validate;
+
+use super::book::{BookFilter, BookInfo};
+use crate::entity::isbn::Isbn13;
+use super::{Cursor, Page, PageRequest, RepositoryError};
+
+#[derive(Serialize, Debug, FromRow, PartialEq)]
+pub struct Shelf {
+	pub id: String,
+	pub name: String,
+	pub description: String,
+	// スマート本棚の条件式。手で並べる本棚ではNone
+	pub query: Option<String>,
+	pub created_at: DateTime<Utc>,
+	pub updated_at: DateTime<Utc>,
+	pub book_count: i64,
+}
+
+// 本棚に並んだ本と、その本に添えたメモ
+#[derive(Serialize, Debug, FromRow, PartialEq)]
+pub struct ShelfEntry {
+	pub position: i32,
+	pub note: String,
+	pub added_at: DateTime<Utc>,
+	#[serde(flatten)]
+	#[sqlx(flatten)]
+	pub book: BookInfo,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ShelfDetail {
+	#[serde(flatten)]
+	pub shelf: Shelf,
+	pub books: Vec<ShelfEntry>,
+}
+
+// 本がどの本棚の何番目に並んでいるか
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow, PartialEq)]
+pub struct ShelfMembership {
+	pub shelf_id: String,
+	pub name: String,
+	pub position: i32,
+	pub note: String,
+}
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct CreateShelf {
+	#[validate(length(min = 1, max = 100))]
+	pub name: String,
+	#[serde(default)]
+	pub description: String,
+	// 指定した場合はスマート本棚になる
+	#[validate(length(min = 1, max = 1000))]
+	pub query: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct UpdateShelf {
+	#[validate(length(min = 1, max = 100))]
+	pub name: Option<String>,
+	pub description: Option<String>,
+	#[validate(length(min = 1, max = 1000))]
+	pub query: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct AddShelfBook {
+	pub isbn_13: Isbn13,
+	#[serde(default)]
+	pub note: String,
+	// 省略した場合は末尾に追加する
+	pub position: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateShelfBook {
+	pub note: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReorderShelf {
+	// 並べたい順のISBN。本棚の全ての本を含める
+	pub isbn_13: Vec<String>,
+}
+
+#[async_trait]
+pub trait ShelfRepository: Clone + Send + Sync + 'static {
+	async fn find(&self, user_id: &str, id: &str) -> Result<ShelfDetail, RepositoryError>;
+	async fn find_all(&self, user_id: &str) -> Result<Vec<Shelf>, RepositoryError>;
+	async fn find_query(&self, user_id: &str, id: &str) -> Result<Option<String>, RepositoryError>;
+	async fn find_books(
+		&self,
+		user_id: &str,
+		id: &str,
+		filter: &BookFilter,
+		page: &PageRequest,
+	) -> Result<Page<ShelfEntry>, RepositoryError>;
+	async fn find_membership(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+	) -> Result<Vec<ShelfMembership>, RepositoryError>;
+	async fn create(&self, user_id: &str, payload: CreateShelf) -> Result<ShelfDetail, RepositoryError>;
+	async fn update(
+		&self,
+		user_id: &str,
+		id: &str,
+		payload: UpdateShelf,
+	) -> Result<ShelfDetail, RepositoryError>;
+	async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError>;
+	async fn add_book(
+		&self,
+		user_id: &str,
+		id: &str,
+		payload: AddShelfBook,
+	) -> Result<ShelfDetail, RepositoryError>;
+	async fn update_book(
+		&self,
+		user_id: &str,
+		id: &str,
+		isbn_13: &str,
+		payload: UpdateShelfBook,
+	) -> Result<ShelfDetail, RepositoryError>;
+	async fn remove_book(&self, user_id: &str, id: &str, isbn_13: &str) -> Result<ShelfDetail, RepositoryError>;
+	async fn reorder(
+		&self,
+		user_id: &str,
+		id: &str,
+		payload: ReorderShelf,
+	) -> Result<ShelfDetail, RepositoryError>;
+}
+
+// 本棚の並び順で発行したカーソルの名前
+const POSITION_SORT: &str = "Position";
+
+#[derive(Clone)]
+pub struct ShelfRepositoryForPg {
+	pool: Arc<PgPool>,
+}
+
+impl ShelfRepositoryForPg {
+	pub fn new(pool: PgPool) -> Self {
+		ShelfRepositoryForPg {
+			pool: Arc::new(pool),
+		}
+	}
+
+	async fn start_transaction(&self) -> Result<Transaction<'_, Postgres>, RepositoryError> {
+		self
+			.pool
+			.begin()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))
+	}
+}
+
+fn handle_name_error(err: sqlx::Error, name: &str) -> RepositoryError {
+	match err.as_database_error() {
+		Some(db_err) => match db_err.is_unique_violation() {
+			true => RepositoryError::Registered(name.to_string()),
+			false => RepositoryError::Unexpected(db_err.to_string()),
+		},
+		None => RepositoryError::Unexpected(err.to_string()),
+	}
+}
+
+// 本棚が存在しているかを探し、更新日時を進める
+async fn touch_shelf(conn: &mut PgConnection, user_id: &str, id: &str) -> Result<(), RepositoryError> {
+	let result = sqlx::query(r#"UPDATE shelves SET updated_at = now() WHERE user_id = $1 AND id = $2;"#)
+		.bind(user_id)
+		.bind(id)
+		.execute(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+	if result.rows_affected() == 0 {
+		return Err(RepositoryError::NotFound(id.to_string()));
+	}
+
+	Ok(())
+}
+
+// スマート本棚の本は条件式で決まるので手では並べられない
+async fn ensure_manual_shelf(conn: &mut PgConnection, id: &str) -> Result<(), RepositoryError> {
+	let smart: bool = sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM saved_queries WHERE shelf_id = $1);"#)
+		.bind(id)
+		.fetch_one(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+	if smart {
+		return Err(RepositoryError::InvalidInput(format!(
+			"shelf {} is filled by its query",
+			id
+		)));
+	}
+
+	Ok(())
+}
+
+#[async_trait]
+impl ShelfRepository for ShelfRepositoryForPg {
+	async fn find(&self, user_id: &str, id: &str) -> Result<ShelfDetail, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let shelf = sqlx::query_as::<_, Shelf>(
+			r#"
+				SELECT id, name, description, created_at, updated_at,
+					(SELECT expression FROM saved_queries WHERE shelf_id = shelves.id) as query,
+					(SELECT count(*) FROM shelf_books WHERE shelf_id = shelves.id) as book_count
+				FROM shelves WHERE user_id = $1 AND id = $2;
+      "#,
+		)
+		.bind(user_id)
+		.bind(id)
+		.fetch_one(conn.borrow_mut())
+		.await
+		.map_err(|err| match err {
+			sqlx::Error::RowNotFound => RepositoryError::NotFound(id.to_string()),
+			_ => RepositoryError::Unexpected(err.to_string()),
+		})?;
+
+		let books = sqlx::query_as::<_, ShelfEntry>(
+			r#"
+				SELECT books.*, shelf_books.position, shelf_books.note, shelf_books.added_at, ARRAY (
+						SELECT author_name FROM authors
+						WHERE authors.user_id = books.user_id AND authors.isbn_13 = books.isbn_13
+				) as authors, ARRAY (
+						SELECT tags.name FROM book_tags JOIN tags ON tags.id = book_tags.tag_id
+						WHERE book_tags.user_id = books.user_id AND book_tags.isbn_13 = books.isbn_13
+						ORDER BY tags.name
+				) as tags FROM shelf_books
+				JOIN books ON books.user_id = shelf_books.user_id AND books.isbn_13 = shelf_books.isbn_13
+				WHERE shelf_books.shelf_id = $1
+				ORDER BY shelf_books.position;
+      "#,
+		)
+		.bind(id)
+		.fetch_all(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(ShelfDetail { shelf, books })
+	}
+
+	async fn find_all(&self, user_id: &str) -> Result<Vec<Shelf>, RepositoryError> {
+		let shelves = sqlx::query_as::<_, Shelf>(
+			r#"
+				SELECT id, name, description, created_at, updated_at,
+					(SELECT expression FROM saved_queries WHERE shelf_id = shelves.id) as query,
+					(SELECT count(*) FROM shelf_books WHERE shelf_id = shelves.id) as book_count
+				FROM shelves WHERE user_id = $1 ORDER BY name;
+      "#,
+		)
+		.bind(user_id)
+		.fetch_all(self.pool.as_ref())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(shelves)
+	}
+
+	async fn find_query(&self, user_id: &str, id: &str) -> Result<Option<String>, RepositoryError> {
+		let query: Option<String> = sqlx::query_scalar(
+			r#"
+				SELECT saved_queries.expression FROM shelves
+				LEFT JOIN saved_queries ON saved_queries.shelf_id = shelves.id
+				WHERE shelves.user_id = $1 AND shelves.id = $2;
+      "#,
+		)
+		.bind(user_id)
+		.bind(id)
+		.fetch_one(self.pool.as_ref())
+		.await
+		.map_err(|err| match err {
+			sqlx::Error::RowNotFound => RepositoryError::NotFound(id.to_string()),
+			_ => RepositoryError::Unexpected(err.to_string()),
+		})?;
+
+		Ok(query)
+	}
+
+	// 手で並べた本棚の本を並べた順に返す。並び順は指定できない
+	async fn find_books(
+		&self,
+		user_id: &str,
+		id: &str,
+		filter: &BookFilter,
+		page: &PageRequest,
+	) -> Result<Page<ShelfEntry>, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let cursor = page
+			.cursor
+			.as_deref()
+			.map(|cursor| Cursor::decode(cursor, POSITION_SORT))
+			.transpose()?;
+		let position = cursor
+			.as_ref()
+			.map(|cursor| cursor.key.parse::<i32>())
+			.transpose()
+			.map_err(|_| RepositoryError::InvalidCursor(page.cursor.clone().unwrap_or_default()))?;
+		let limit = page.limit();
+
+		let mut entries = sqlx::query_as::<_, ShelfEntry>(
+			r#"
+				SELECT books.*, shelf_books.position, shelf_books.note, shelf_books.added_at, ARRAY (
+						SELECT author_name FROM authors
+						WHERE authors.user_id = books.user_id AND authors.isbn_13 = books.isbn_13
+				) as authors, ARRAY (
+						SELECT tags.name FROM book_tags JOIN tags ON tags.id = book_tags.tag_id
+						WHERE book_tags.user_id = books.user_id AND book_tags.isbn_13 = books.isbn_13
+						ORDER BY tags.name
+				) as tags FROM shelf_books
+				JOIN books ON books.user_id = shelf_books.user_id AND books.isbn_13 = shelf_books.isbn_13
+				WHERE shelf_books.user_id = $1 AND shelf_books.shelf_id = $2
+				AND ($5::text IS NULL OR books.status = $5)
+				AND ($6::text IS NULL OR EXISTS (
+					SELECT 1 FROM book_tags JOIN tags ON tags.id = book_tags.tag_id
+					WHERE book_tags.user_id = books.user_id AND book_tags.isbn_13 = books.isbn_13
+					AND tags.name = $6
+				))
+				AND ($3::integer IS NULL OR (shelf_books.position, shelf_books.isbn_13) > ($3, $4))
+				ORDER BY shelf_books.position, shelf_books.isbn_13
+				LIMIT $7;
+      "#,
+		)
+		.bind(user_id)
+		.bind(id)
+		.bind(position)
+		.bind(cursor.as_ref().map(|cursor| cursor.id.clone()))
+		.bind(filter.status)
+		.bind(&filter.tag)
+		// 次のページがあるかを知るために1件多く取る
+		.bind(limit + 1)
+		.fetch_all(conn.borrow_mut())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let next_cursor = if entries.len() as i64 > limit {
+			entries.truncate(limit as usize);
+			entries.last().map(|last| {
+				Cursor {
+					sort: POSITION_SORT.to_string(),
+					key: last.position.to_string(),
+					id: last.book.isbn_13.clone(),
+				}
+				.encode()
+			})
+		} else {
+			None
+		};
+
+		let total = if page.with_total {
+			let total: i64 = sqlx::query_scalar(
+				r#"
+					SELECT count(*) FROM shelf_books
+					JOIN books ON books.user_id = shelf_books.user_id AND books.isbn_13 = shelf_books.isbn_13
+					WHERE shelf_books.user_id = $1 AND shelf_books.shelf_id = $2
+					AND ($3::text IS NULL OR books.status = $3)
+					AND ($4::text IS NULL OR EXISTS (
+						SELECT 1 FROM book_tags JOIN tags ON tags.id = book_tags.tag_id
+						WHERE book_tags.user_id = books.user_id AND book_tags.isbn_13 = books.isbn_13
+						AND tags.name = $4
+					));
+        "#,
+			)
+			.bind(user_id)
+			.bind(id)
+			.bind(filter.status)
+			.bind(&filter.tag)
+			.fetch_one(conn)
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+			Some(total)
+		} else {
+			None
+		};
+
+		Ok(Page {
+			items: entries,
+			next_cursor,
+			total,
+		})
+	}
+
+	async fn find_membership(
+		&self,
+		user_id: &str,
+		isbn_13: &str,
+	) -> Result<Vec<ShelfMembership>, RepositoryError> {
+		let membership = sqlx::query_as::<_, ShelfMembership>(
+			r#"
+				SELECT shelves.id as shelf_id, shelves.name, shelf_books.position, shelf_books.note
+				FROM shelf_books JOIN shelves ON shelves.id = shelf_books.shelf_id
+				WHERE shelf_books.user_id = $1 AND shelf_books.isbn_13 = $2
+				ORDER BY shelves.name;
+      "#,
+		)
+		.bind(user_id)
+		.bind(isbn_13)
+		.fetch_all(self.pool.as_ref())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(membership)
+	}
+
+	async fn create(&self, user_id: &str, payload: CreateShelf) -> Result<ShelfDetail, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let id = uuid::Uuid::new_v4().to_string();
+		sqlx::query(r#"INSERT INTO shelves (id, user_id, name, description) VALUES ($1, $2, $3, $4);"#)
+			.bind(&id)
+			.bind(user_id)
+			.bind(payload.name.trim())
+			.bind(&payload.description)
+			.execute(conn.borrow_mut())
+			.await
+			.map_err(|err| handle_name_error(err, &payload.name))?;
+
+		if let Some(query) = &payload.query {
+			sqlx::query(r#"INSERT INTO saved_queries (shelf_id, expression) VALUES ($1, $2);"#)
+				.bind(&id)
+				.bind(query)
+				.execute(conn)
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		}
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		self.find(user_id, &id).await
+	}
+
+	async fn update(
+		&self,
+		user_id: &str,
+		id: &str,
+		payload: UpdateShelf,
+	) -> Result<ShelfDetail, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let name = payload.name.as_deref().map(str::trim);
+		let result = sqlx::query(
+			r#"
+				UPDATE shelves SET name = COALESCE($3, name), description = COALESCE($4, description),
+				updated_at = now() WHERE user_id = $1 AND id = $2;
+      "#,
+		)
+		.bind(user_id)
+		.bind(id)
+		.bind(name)
+		.bind(&payload.description)
+		.execute(conn.borrow_mut())
+		.await
+		.map_err(|err| handle_name_error(err, name.unwrap_or_default()))?;
+		if result.rows_affected() == 0 {
+			return Err(RepositoryError::NotFound(id.to_string()));
+		}
+
+		if let Some(query) = &payload.query {
+			// 手で並べた本が残っている本棚はスマート本棚にしない
+			let count: i64 = sqlx::query_scalar(r#"SELECT count(*) FROM shelf_books WHERE shelf_id = $1;"#)
+				.bind(id)
+				.fetch_one(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+			if count > 0 {
+				return Err(RepositoryError::InvalidInput(format!(
+					"shelf {} already has books placed by hand",
+					id
+				)));
+			}
+
+			sqlx::query(
+				r#"
+					INSERT INTO saved_queries (shelf_id, expression) VALUES ($1, $2)
+					ON CONFLICT (shelf_id) DO UPDATE SET expression = $2, updated_at = now();
+        "#,
+			)
+			.bind(id)
+			.bind(query)
+			.execute(conn)
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		}
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		self.find(user_id, id).await
+	}
+
+	async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError> {
+		let result = sqlx::query(r#"DELETE FROM shelves WHERE user_id = $1 AND id = $2;"#)
+			.bind(user_id)
+			.bind(id)
+			.execute(self.pool.as_ref())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if result.rows_affected() == 0 {
+			return Err(RepositoryError::NotFound(id.to_string()));
+		}
+
+		Ok(())
+	}
+
+	async fn add_book(
+		&self,
+		user_id: &str,
+		id: &str,
+		payload: AddShelfBook,
+	) -> Result<ShelfDetail, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		touch_shelf(conn.borrow_mut(), user_id, id).await?;
+		ensure_manual_shelf(conn.borrow_mut(), id).await?;
+
+		// 本棚に並べたい本が存在しているかを探す
+		let book_exist: bool =
+			sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM books WHERE user_id = $1 AND isbn_13 = $2);"#)
+				.bind(user_id)
+				.bind(payload.isbn_13.as_str())
+				.fetch_one(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if !book_exist {
+			return Err(RepositoryError::NotFound(payload.isbn_13.to_string()));
+		};
+
+		let count: i64 = sqlx::query_scalar(r#"SELECT count(*) FROM shelf_books WHERE shelf_id = $1;"#)
+			.bind(id)
+			.fetch_one(conn.borrow_mut())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		let position = payload
+			.position
+			.map(|position| position.clamp(0, count as i32))
+			.unwrap_or(count as i32);
+
+		// 挿入する位置より後ろの本を1つずつずらす
+		sqlx::query(r#"UPDATE shelf_books SET position = position + 1 WHERE shelf_id = $1 AND position >= $2;"#)
+			.bind(id)
+			.bind(position)
+			.execute(conn.borrow_mut())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		sqlx::query(
+			r#"INSERT INTO shelf_books (shelf_id, user_id, isbn_13, position, note) VALUES ($1, $2, $3, $4, $5);"#,
+		)
+		.bind(id)
+		.bind(user_id)
+		.bind(payload.isbn_13.as_str())
+		.bind(position)
+		.bind(&payload.note)
+		.execute(conn)
+		.await
+		.map_err(|err| handle_name_error(err, &payload.isbn_13))?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		self.find(user_id, id).await
+	}
+
+	async fn update_book(
+		&self,
+		user_id: &str,
+		id: &str,
+		isbn_13: &str,
+		payload: UpdateShelfBook,
+	) -> Result<ShelfDetail, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		touch_shelf(conn.borrow_mut(), user_id, id).await?;
+
+		let result = sqlx::query(r#"UPDATE shelf_books SET note = $3 WHERE shelf_id = $1 AND isbn_13 = $2;"#)
+			.bind(id)
+			.bind(isbn_13)
+			.bind(&payload.note)
+			.execute(conn)
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if result.rows_affected() == 0 {
+			return Err(RepositoryError::NotFound(isbn_13.to_string()));
+		}
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		self.find(user_id, id).await
+	}
+
+	async fn remove_book(&self, user_id: &str, id: &str, isbn_13: &str) -> Result<ShelfDetail, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		touch_shelf(conn.borrow_mut(), user_id, id).await?;
+
+		let position: i32 = sqlx::query_scalar(
+			r#"DELETE FROM shelf_books WHERE shelf_id = $1 AND isbn_13 = $2 RETURNING position;"#,
+		)
+		.bind(id)
+		.bind(isbn_13)
+		.fetch_one(conn.borrow_mut())
+		.await
+		.map_err(|err| match err {
+			sqlx::Error::RowNotFound => RepositoryError::NotFound(isbn_13.to_string()),
+			_ => RepositoryError::Unexpected(err.to_string()),
+		})?;
+
+		// 空いた位置を詰める
+		sqlx::query(r#"UPDATE shelf_books SET position = position - 1 WHERE shelf_id = $1 AND position > $2;"#)
+			.bind(id)
+			.bind(position)
+			.execute(conn)
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		self.find(user_id, id).await
+	}
+
+	async fn reorder(
+		&self,
+		user_id: &str,
+		id: &str,
+		payload: ReorderShelf,
+	) -> Result<ShelfDetail, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		touch_shelf(conn.borrow_mut(), user_id, id).await?;
+		ensure_manual_shelf(conn.borrow_mut(), id).await?;
+
+		// 並べ替えは本棚の本をちょうど一度ずつ含む場合のみ受け付ける
+		let mut current: Vec<String> =
+			sqlx::query_scalar(r#"SELECT isbn_13 FROM shelf_books WHERE shelf_id = $1;"#)
+				.bind(id)
+				.fetch_all(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		let mut requested = payload.isbn_13.clone();
+		current.sort();
+		requested.sort();
+		if current != requested {
+			return Err(RepositoryError::InvalidInput(
+				"isbn_13 must list every book on the shelf exactly once".to_string(),
+			));
+		}
+
+		sqlx::query(
+			r#"
+				UPDATE shelf_books SET position = ordered.ord - 1
+				FROM unnest($2::text[]) WITH ORDINALITY AS ordered(isbn_13, ord)
+				WHERE shelf_books.shelf_id = $1 AND shelf_books.isbn_13 = ordered.isbn_13;
+      "#,
+		)
+		.bind(id)
+		.bind(&payload.isbn_13)
+		.execute(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		self.find(user_id, id).await
+	}
+}
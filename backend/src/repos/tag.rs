@@ -0,0 +1,378 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, FromRow, PgPool, Postgres, Transaction};
+use std::{borrow::BorrowMut, sync::Arc};
+use validator::{Validate, ValidationError};
+
+use super::RepositoryError;
+
+#[derive(Serialize, Debug, FromRow, PartialEq)]
+pub struct Tag {
+	pub id: String,
+	pub name: String,
+	pub created_at: DateTime<Utc>,
+	pub book_count: i64,
+	pub memo_count: i64,
+}
+
+// タグ名の長さの上限（文字数）
+const TAG_NAME_LIMIT: usize = 64;
+
+#[derive(Deserialize, Debug, Validate)]
+pub struct TagName {
+	#[validate(custom(function = "validate_tag_name"))]
+	pub name: String,
+}
+
+// 保存するのは前後の空白を除いた名前なので、除いた後の長さで確かめる
+fn validate_tag_name(name: &str) -> Result<(), ValidationError> {
+	match name.trim().chars().count() {
+		0 => Err(ValidationError::new("tag name must not be blank")),
+		len if len > TAG_NAME_LIMIT => Err(ValidationError::new("tag name is too long")),
+		_ => Ok(()),
+	}
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MergeTag {
+	// 統合先のタグ
+	pub into: String,
+}
+
+#[async_trait]
+pub trait TagRepository: Clone + Send + Sync + 'static {
+	async fn find_all(&self, user_id: &str) -> Result<Vec<Tag>, RepositoryError>;
+	async fn create(&self, user_id: &str, payload: TagName) -> Result<Tag, RepositoryError>;
+	async fn rename(&self, user_id: &str, id: &str, payload: TagName) -> Result<Tag, RepositoryError>;
+	async fn merge(&self, user_id: &str, id: &str, payload: MergeTag) -> Result<Tag, RepositoryError>;
+	async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError>;
+	async fn tag_book(&self, user_id: &str, id: &str, isbn_13: &str) -> Result<(), RepositoryError>;
+	async fn untag_book(&self, user_id: &str, id: &str, isbn_13: &str) -> Result<(), RepositoryError>;
+	async fn tag_memo(&self, user_id: &str, id: &str, memo_id: &str) -> Result<(), RepositoryError>;
+	async fn untag_memo(&self, user_id: &str, id: &str, memo_id: &str) -> Result<(), RepositoryError>;
+}
+
+#[derive(Clone)]
+pub struct TagRepositoryForPg {
+	pool: Arc<PgPool>,
+}
+
+impl TagRepositoryForPg {
+	pub fn new(pool: PgPool) -> Self {
+		TagRepositoryForPg {
+			pool: Arc::new(pool),
+		}
+	}
+
+	async fn start_transaction(&self) -> Result<Transaction<'_, Postgres>, RepositoryError> {
+		self
+			.pool
+			.begin()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))
+	}
+
+	async fn find(&self, user_id: &str, id: &str) -> Result<Tag, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let tag = sqlx::query_as::<_, Tag>(
+			r#"
+				SELECT id, name, created_at,
+					(SELECT count(*) FROM book_tags WHERE tag_id = tags.id) as book_count,
+					(SELECT count(*) FROM memo_tags WHERE tag_id = tags.id) as memo_count
+				FROM tags WHERE user_id = $1 AND id = $2;
+      "#,
+		)
+		.bind(user_id)
+		.bind(id)
+		.fetch_one(conn)
+		.await
+		.map_err(|err| match err {
+			sqlx::Error::RowNotFound => RepositoryError::NotFound(id.to_string()),
+			_ => RepositoryError::Unexpected(err.to_string()),
+		})?;
+
+		Ok(tag)
+	}
+}
+
+fn handle_name_error(err: sqlx::Error, name: &str) -> RepositoryError {
+	match err.as_database_error() {
+		Some(db_err) => match db_err.is_unique_violation() {
+			true => RepositoryError::Registered(name.to_string()),
+			false => RepositoryError::Unexpected(db_err.to_string()),
+		},
+		None => match err {
+			sqlx::Error::RowNotFound => RepositoryError::NotFound(name.to_string()),
+			_ => RepositoryError::Unexpected(err.to_string()),
+		},
+	}
+}
+
+// タグが存在しているかを探す
+async fn ensure_tag_exist(
+	conn: &mut sqlx::PgConnection,
+	user_id: &str,
+	id: &str,
+) -> Result<(), RepositoryError> {
+	let tag_exist: bool =
+		sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM tags WHERE user_id = $1 AND id = $2);"#)
+			.bind(user_id)
+			.bind(id)
+			.fetch_one(conn)
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+	if !tag_exist {
+		return Err(RepositoryError::NotFound(id.to_string()));
+	};
+
+	Ok(())
+}
+
+#[async_trait]
+impl TagRepository for TagRepositoryForPg {
+	async fn find_all(&self, user_id: &str) -> Result<Vec<Tag>, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		let tags = sqlx::query_as::<_, Tag>(
+			r#"
+				SELECT id, name, created_at,
+					(SELECT count(*) FROM book_tags WHERE tag_id = tags.id) as book_count,
+					(SELECT count(*) FROM memo_tags WHERE tag_id = tags.id) as memo_count
+				FROM tags WHERE user_id = $1 ORDER BY name;
+      "#,
+		)
+		.bind(user_id)
+		.fetch_all(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(tags)
+	}
+
+	async fn create(&self, user_id: &str, payload: TagName) -> Result<Tag, RepositoryError> {
+		let id = uuid::Uuid::new_v4().to_string();
+		sqlx::query(r#"INSERT INTO tags (id, user_id, name) VALUES ($1, $2, $3);"#)
+			.bind(&id)
+			.bind(user_id)
+			.bind(payload.name.trim())
+			.execute(self.pool.as_ref())
+			.await
+			.map_err(|err| handle_name_error(err, &payload.name))?;
+
+		self.find(user_id, &id).await
+	}
+
+	async fn rename(&self, user_id: &str, id: &str, payload: TagName) -> Result<Tag, RepositoryError> {
+		// 既にある名前への変更は統合で行う
+		let result = sqlx::query(r#"UPDATE tags SET name = $3 WHERE user_id = $1 AND id = $2;"#)
+			.bind(user_id)
+			.bind(id)
+			.bind(payload.name.trim())
+			.execute(self.pool.as_ref())
+			.await
+			.map_err(|err| handle_name_error(err, &payload.name))?;
+		if result.rows_affected() == 0 {
+			return Err(RepositoryError::NotFound(id.to_string()));
+		}
+
+		self.find(user_id, id).await
+	}
+
+	async fn merge(&self, user_id: &str, id: &str, payload: MergeTag) -> Result<Tag, RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		ensure_tag_exist(conn.borrow_mut(), user_id, id).await?;
+		ensure_tag_exist(conn.borrow_mut(), user_id, &payload.into).await?;
+		if id == payload.into {
+			return Err(RepositoryError::Registered(id.to_string()));
+		}
+
+		// 統合先に既に付いている本やメモは重複させない
+		sqlx::query(
+			r#"
+				INSERT INTO book_tags (tag_id, user_id, isbn_13)
+				SELECT $2, user_id, isbn_13 FROM book_tags WHERE tag_id = $1
+				ON CONFLICT DO NOTHING;
+      "#,
+		)
+		.bind(id)
+		.bind(&payload.into)
+		.execute(conn.borrow_mut())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		sqlx::query(
+			r#"
+				INSERT INTO memo_tags (tag_id, memo_id)
+				SELECT $2, memo_id FROM memo_tags WHERE tag_id = $1
+				ON CONFLICT DO NOTHING;
+      "#,
+		)
+		.bind(id)
+		.bind(&payload.into)
+		.execute(conn.borrow_mut())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		sqlx::query(r#"DELETE FROM tags WHERE user_id = $1 AND id = $2;"#)
+			.bind(user_id)
+			.bind(id)
+			.execute(conn)
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		self.find(user_id, &payload.into).await
+	}
+
+	async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError> {
+		let result = sqlx::query(r#"DELETE FROM tags WHERE user_id = $1 AND id = $2;"#)
+			.bind(user_id)
+			.bind(id)
+			.execute(self.pool.as_ref())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if result.rows_affected() == 0 {
+			return Err(RepositoryError::NotFound(id.to_string()));
+		}
+
+		Ok(())
+	}
+
+	async fn tag_book(&self, user_id: &str, id: &str, isbn_13: &str) -> Result<(), RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		ensure_tag_exist(conn.borrow_mut(), user_id, id).await?;
+
+		// タグを付けたい本が存在しているかを探す
+		let book_exist: bool =
+			sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM books WHERE user_id = $1 AND isbn_13 = $2);"#)
+				.bind(user_id)
+				.bind(isbn_13)
+				.fetch_one(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if !book_exist {
+			return Err(RepositoryError::NotFound(isbn_13.to_string()));
+		};
+
+		sqlx::query(
+			r#"INSERT INTO book_tags (tag_id, user_id, isbn_13) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING;"#,
+		)
+		.bind(id)
+		.bind(user_id)
+		.bind(isbn_13)
+		.execute(conn)
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(())
+	}
+
+	async fn untag_book(&self, user_id: &str, id: &str, isbn_13: &str) -> Result<(), RepositoryError> {
+		sqlx::query(r#"DELETE FROM book_tags WHERE user_id = $1 AND tag_id = $2 AND isbn_13 = $3;"#)
+			.bind(user_id)
+			.bind(id)
+			.bind(isbn_13)
+			.execute(self.pool.as_ref())
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(())
+	}
+
+	async fn tag_memo(&self, user_id: &str, id: &str, memo_id: &str) -> Result<(), RepositoryError> {
+		let mut tx = self.start_transaction().await?;
+		let conn = tx
+			.acquire()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		ensure_tag_exist(conn.borrow_mut(), user_id, id).await?;
+
+		// タグを付けたいメモが存在しているかを探す
+		let memo_exist: bool =
+			sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM memo WHERE user_id = $1 AND id = $2);"#)
+				.bind(user_id)
+				.bind(memo_id)
+				.fetch_one(conn.borrow_mut())
+				.await
+				.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+		if !memo_exist {
+			return Err(RepositoryError::NotFound(memo_id.to_string()));
+		};
+
+		sqlx::query(r#"INSERT INTO memo_tags (tag_id, memo_id) VALUES ($1, $2) ON CONFLICT DO NOTHING;"#)
+			.bind(id)
+			.bind(memo_id)
+			.execute(conn)
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		tx.commit()
+			.await
+			.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(())
+	}
+
+	async fn untag_memo(&self, user_id: &str, id: &str, memo_id: &str) -> Result<(), RepositoryError> {
+		sqlx::query(
+			r#"
+				DELETE FROM memo_tags WHERE tag_id = $2 AND memo_id = $3
+				AND EXISTS (SELECT 1 FROM tags WHERE tags.id = memo_tags.tag_id AND tags.user_id = $1);
+      "#,
+		)
+		.bind(user_id)
+		.bind(id)
+		.bind(memo_id)
+		.execute(self.pool.as_ref())
+		.await
+		.map_err(|err| RepositoryError::Unexpected(err.to_string()))?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tag_name(name: &str) -> TagName {
+		TagName { name: name.to_string() }
+	}
+
+	#[test]
+	fn tag_name_is_validated_after_trimming() {
+		assert!(tag_name("  哲学  ").validate().is_ok());
+		assert!(tag_name("").validate().is_err());
+		assert!(tag_name(" \t\n").validate().is_err());
+		assert!(tag_name(&format!(" {} ", "あ".repeat(64))).validate().is_ok());
+		assert!(tag_name(&"あ".repeat(65)).validate().is_err());
+	}
+}
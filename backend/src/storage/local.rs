@@ -0,0 +1,63 @@
+use axum::async_trait;
+use std::path::{Component, Path, PathBuf};
+
+use super::{BlobStore, StorageError};
+
+// 手元のディレクトリにキーをパスとして保存する
+#[derive(Clone)]
+pub struct LocalBlobStore {
+	root: PathBuf,
+}
+
+impl LocalBlobStore {
+	pub fn new(root: impl Into<PathBuf>) -> Self {
+		LocalBlobStore { root: root.into() }
+	}
+
+	// ルートの外を指すキーは受け付けない
+	fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+		let relative = Path::new(key);
+		if key.is_empty() || !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+			return Err(StorageError::Unexpected(format!("invalid key: {}", key)));
+		}
+
+		Ok(self.root.join(relative))
+	}
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+	async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), StorageError> {
+		let path = self.path(key)?;
+		if let Some(parent) = path.parent() {
+			tokio::fs::create_dir_all(parent)
+				.await
+				.map_err(|err| StorageError::Unexpected(err.to_string()))?;
+		}
+		// 書きかけのファイルを読まれないように、別名で書いてから置き換える
+		let temporary = path.with_extension("tmp");
+		tokio::fs::write(&temporary, bytes)
+			.await
+			.map_err(|err| StorageError::Unexpected(err.to_string()))?;
+		tokio::fs::rename(&temporary, &path)
+			.await
+			.map_err(|err| StorageError::Unexpected(err.to_string()))
+	}
+
+	async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+		tokio::fs::read(self.path(key)?)
+			.await
+			.map_err(|err| match err.kind() {
+				std::io::ErrorKind::NotFound => StorageError::NotFound(key.to_string()),
+				_ => StorageError::Unexpected(err.to_string()),
+			})
+	}
+
+	async fn delete(&self, key: &str) -> Result<(), StorageError> {
+		match tokio::fs::remove_file(self.path(key)?).await {
+			Ok(()) => Ok(()),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+			Err(err) => Err(StorageError::Unexpected(err.to_string())),
+		}
+	}
+}
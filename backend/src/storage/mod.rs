@@ -0,0 +1,24 @@
+pub mod local;
+
+use axum::async_trait;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+	#[error("Unexpected Error: [{0}]")]
+	Unexpected(String),
+	#[error("NotFound, key is {0}")]
+	NotFound(String),
+}
+
+// 表紙の画像などをキーで出し入れする保存先
+#[async_trait]
+pub trait BlobStore: Send + Sync + 'static {
+	async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), StorageError>;
+	async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+	// 無いキーを消してもエラーにしない
+	async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+pub type SharedBlobStore = Arc<dyn BlobStore>;
@@ -4,6 +4,7 @@ import Book from "~/types/book";
 import {useEffect, useState, FormEventHandler} from "react";
 
 import Memo from "~/types/memo";
+import Page from "~/types/page";
 import MemoList from "~/components/MemoList/MemoList";
 import ConfirmDialog from "~/components/ConfirmDialog/ConfirmDialog";
 import myFetch from "~/utility/fetch/my-fetch";
@@ -27,11 +28,19 @@ export default function BookDetailModal({...props}: props) {
 	const handleConfirmDialogClose = () => setShowConfirmDialog(false);
 
 	const getMemoList = async () => {
-		const res = await myFetch(memoUrl);
-		if (!res.ok) {
-			return;
-		}
-		const resMemoList: Memo[] = await res.json();
+		// 全てのページを順に取得する
+		let resMemoList: Memo[] = [];
+		let cursor: string | null = null;
+		do {
+			const url: string = cursor === null ? memoUrl : memoUrl + "?cursor=" + encodeURIComponent(cursor);
+			const res = await myFetch(url);
+			if (!res.ok) {
+				return;
+			}
+			const page: Page<Memo> = await res.json();
+			resMemoList = [...resMemoList, ...page.items];
+			cursor = page.next_cursor;
+		} while (cursor !== null);
 		setMemoList(resMemoList);
 	};
 
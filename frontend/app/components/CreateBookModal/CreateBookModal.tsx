@@ -1,15 +1,31 @@
 import {Modal, Button, Form, Spinner} from "react-bootstrap";
 import {useState, ChangeEventHandler} from "react";
 import myFetch from "~/utility/fetch/my-fetch";
+import Job from "~/types/job";
+
+// 書誌情報の取得ジョブが終わるまで待つ間隔
+const POLL_INTERVAL_MS = 1000;
 
 interface props {
 	show: boolean,
 	handleClose: () => void,
 	createApi: string,
+	jobApi: string,
 	afterCreateHandler: () => void,
 }
 
-export default function CreateBookModal({show, handleClose, createApi, afterCreateHandler}: props) {
+// ジョブが成功か失敗で終わるまで状態を問い合わせる
+const waitForJob = async (jobApi: string, job: Job): Promise<Job | null> => {
+	while(job.status === "pending" || job.status === "running") {
+		await new Promise((resolve) => setTimeout(resolve, POLL_INTERVAL_MS));
+		const res = await myFetch(jobApi + "/" + job.id);
+		if(!res.ok) return null;
+		job = await res.json();
+	}
+	return job;
+}
+
+export default function CreateBookModal({show, handleClose, createApi, jobApi, afterCreateHandler}: props) {
 	const [isbn13, setIsbn13] = useState<string>("");
 	const [enableInput, setEnableInput] = useState<boolean>(true);
 	const [enableSubmit, setEnableSubmit] = useState<boolean>(false);
@@ -31,19 +47,29 @@ export default function CreateBookModal({show, handleClose, createApi, afterCrea
 					headers: {
 						"Content-Type": "application/json",
 					},
+					// 取得元の応答を待たずにジョブとして受け付けてもらう
 					body: JSON.stringify({
 						"isbn_13": isbn13,
+						"background": true,
 					})
 				});
+			const job = res.ok ? await waitForJob(jobApi, await res.json()) : null;
 			setEnableInput(true);
 			setEnableSubmit(true);
 			setIsLoading(false);
 			if(!res.ok) {
 				if(res.statusText == "Bad Request") alert("登録済みです");
-				else if(res.statusText == "Not Found") alert("見つかりません");
 				else alert("err!");
 				return;
 			}
+			if(job === null) {
+				alert("err!");
+				return;
+			}
+			if(job.status === "failed") {
+				alert("見つかりません");
+				return;
+			}
 			afterCreateHandler();
 			setIsbn13("");
 		})();
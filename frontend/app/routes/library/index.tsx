@@ -3,6 +3,7 @@ import {useEffect, useState} from "react";
 import {Button} from "react-bootstrap";
 
 import Book from "~/types/book";
+import Page from "~/types/page";
 import BookCard from "~/components/BookCard/BookCard";
 import CreateBookModal from "~/components/CreateBookModal/CreateBookModal";
 import myFetch from "~/utility/fetch/my-fetch";
@@ -12,8 +13,10 @@ export default function Index() {
 
     const baseURL = "http://localhost:8000"
     const bookUrl = baseURL + '/book';
+    const jobUrl = baseURL + '/job';
 
     const [books, setBooks] = useState<Book[]>([]);
+    const [nextCursor, setNextCursor] = useState<string | null>(null);
     const [show, setShow] = useState(false);
     const handleClose = () => setShow(false);
     const handleShow = () => setShow(true);
@@ -22,11 +25,27 @@ export default function Index() {
         let res = await myFetch(bookUrl);
         if (!res.ok) {
             setBooks([]);
+            setNextCursor(null);
             return;
         }
 
-        let books: Book[] = await res.json();
-        setBooks(books);
+        let page: Page<Book> = await res.json();
+        setBooks(page.items);
+        setNextCursor(page.next_cursor);
+    };
+
+    const getMoreBooksInfo = async () => {
+        if (nextCursor === null) {
+            return;
+        }
+        let res = await myFetch(bookUrl + '?cursor=' + encodeURIComponent(nextCursor));
+        if (!res.ok) {
+            return;
+        }
+
+        let page: Page<Book> = await res.json();
+        setBooks([...books, ...page.items]);
+        setNextCursor(page.next_cursor);
     };
 
     const afterCreateHandler = () => {
@@ -47,11 +66,16 @@ export default function Index() {
                 {books.map((book) =>
                         <BookCard book={book} baseUrl={baseURL} key={book.isbn_13} handleAfterDelete={getBooksInfo}/>)}
             </div>
+            {nextCursor !== null &&
+                <div className="flex justify-content-center pb-4">
+                    <Button variant="outline-primary" onClick={getMoreBooksInfo}>もっと見る</Button>
+                </div>}
             <Button className="fixed bottom-[12px] right-[12px]" variant="primary" onClick={handleShow}>本を追加</Button>
             <CreateBookModal
                 show={show}
                 handleClose={handleClose}
                 createApi={bookUrl}
+                jobApi={jobUrl}
                 afterCreateHandler={afterCreateHandler}
             />
         </>
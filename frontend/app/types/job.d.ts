@@ -0,0 +1,9 @@
+export default interface Job {
+	id: string,
+	kind: "fetch_metadata" | "fetch_cover",
+	isbn_13: string,
+	status: "pending" | "running" | "succeeded" | "failed",
+	attempts: number,
+	max_attempts: number,
+	last_error: string | null,
+};
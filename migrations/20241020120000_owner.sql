@@ -0,0 +1,47 @@
+-- 本とメモをユーザーごとに分ける
+ALTER TABLE books DROP CONSTRAINT IF EXISTS books_pkey;
+ALTER TABLE books ADD COLUMN IF NOT EXISTS user_id CHAR(36);
+ALTER TABLE authors ADD COLUMN IF NOT EXISTS user_id CHAR(36);
+ALTER TABLE memo ADD COLUMN IF NOT EXISTS user_id CHAR(36);
+
+-- これまで共有していたデータは1人のユーザーの持ち物にする。複製すると他人のメモまで見えてしまう
+-- 持ち主はPGOPTIONS="-c app.legacy_owner=<ユーザーID>"で指定でき、指定が無ければ唯一のユーザーにする
+-- 持ち主を決められない場合はデータを消さないように移行を止める
+DO $$
+DECLARE
+	owner CHAR(36) := NULLIF(current_setting('app.legacy_owner', true), '');
+	user_count BIGINT;
+BEGIN
+	IF NOT EXISTS (SELECT 1 FROM books WHERE user_id IS NULL)
+		AND NOT EXISTS (SELECT 1 FROM memo WHERE user_id IS NULL) THEN
+		RETURN;
+	END IF;
+
+	IF owner IS NULL THEN
+		SELECT count(*) INTO user_count FROM users;
+		IF user_count <> 1 THEN
+			RAISE EXCEPTION 'cannot choose an owner for existing books among % users; set app.legacy_owner', user_count;
+		END IF;
+		SELECT id INTO owner FROM users;
+	ELSIF NOT EXISTS (SELECT 1 FROM users WHERE id = owner) THEN
+		RAISE EXCEPTION 'app.legacy_owner % is not a user', owner;
+	END IF;
+
+	UPDATE books SET user_id = owner WHERE user_id IS NULL;
+	UPDATE authors SET user_id = owner WHERE user_id IS NULL;
+	UPDATE memo SET user_id = owner WHERE user_id IS NULL;
+END
+$$;
+
+ALTER TABLE books
+	ALTER COLUMN user_id SET NOT NULL,
+	ADD PRIMARY KEY (user_id, isbn_13),
+	ADD FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
+
+ALTER TABLE authors
+	ALTER COLUMN user_id SET NOT NULL,
+	ADD FOREIGN KEY (user_id, isbn_13) REFERENCES books(user_id, isbn_13) ON DELETE CASCADE;
+
+ALTER TABLE memo
+	ALTER COLUMN user_id SET NOT NULL,
+	ADD FOREIGN KEY (user_id, isbn_13) REFERENCES books(user_id, isbn_13) ON DELETE CASCADE;
@@ -0,0 +1,9 @@
+-- メモを更新したときに以前の本文を残す
+CREATE TABLE IF NOT EXISTS memo_revisions (
+    id          CHAR(36) PRIMARY KEY,
+    memo_id     CHAR(36) NOT NULL REFERENCES memo(id) ON DELETE CASCADE,
+    text        TEXT NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS memo_revisions_memo_id_idx ON memo_revisions (memo_id, created_at);
@@ -0,0 +1,21 @@
+-- 本とメモに登録日時と更新日時を追加する
+ALTER TABLE books
+	ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+	ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+ALTER TABLE memo
+	ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+	ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- 編集履歴が残っているメモは、その日時から作成日時と更新日時を推定する
+UPDATE memo SET
+	created_at = revisions.first_revised_at,
+	updated_at = revisions.last_revised_at
+FROM (
+	SELECT memo_id, min(created_at) AS first_revised_at, max(created_at) AS last_revised_at
+	FROM memo_revisions GROUP BY memo_id
+) AS revisions
+WHERE memo.id = revisions.memo_id;
+
+CREATE INDEX IF NOT EXISTS books_user_id_created_at_idx ON books (user_id, created_at);
+CREATE INDEX IF NOT EXISTS memo_user_id_isbn_13_created_at_idx ON memo (user_id, isbn_13, created_at);
@@ -0,0 +1,7 @@
+-- 日本語は単語の区切りが無いため、トライグラムの部分一致で検索する
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS memo_text_trgm_idx ON memo USING gin (text gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS books_title_trgm_idx ON books USING gin (title gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS books_description_trgm_idx ON books USING gin (description gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS authors_author_name_trgm_idx ON authors USING gin (author_name gin_trgm_ops);
@@ -0,0 +1,36 @@
+-- 読書状態。現在の状態は本に持たせ、読み始めから読み終わり（または中断）までを1サイクルとして残す
+ALTER TABLE books
+	ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'want_to_read'
+		CHECK (status IN ('want_to_read', 'reading', 'finished', 'abandoned')),
+	ADD COLUMN IF NOT EXISTS started_on DATE,
+	ADD COLUMN IF NOT EXISTS finished_on DATE;
+
+CREATE INDEX IF NOT EXISTS books_user_id_status_idx ON books (user_id, status);
+
+CREATE TABLE IF NOT EXISTS reading_cycles (
+    id          CHAR(36) PRIMARY KEY,
+    user_id     CHAR(36) NOT NULL,
+    isbn_13     CHAR(13) NOT NULL,
+    started_on  DATE,
+    finished_on DATE,
+    outcome     TEXT CHECK (outcome IN ('finished', 'abandoned')),
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (user_id, isbn_13) REFERENCES books(user_id, isbn_13) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS reading_cycles_book_idx ON reading_cycles (user_id, isbn_13, created_at);
+
+CREATE TABLE IF NOT EXISTS reading_status_events (
+    id          CHAR(36) PRIMARY KEY,
+    user_id     CHAR(36) NOT NULL,
+    isbn_13     CHAR(13) NOT NULL,
+    cycle_id    CHAR(36) REFERENCES reading_cycles(id) ON DELETE SET NULL,
+    from_status TEXT NOT NULL,
+    to_status   TEXT NOT NULL,
+    occurred_on DATE NOT NULL,
+    note        TEXT,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    FOREIGN KEY (user_id, isbn_13) REFERENCES books(user_id, isbn_13) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS reading_status_events_book_idx ON reading_status_events (user_id, isbn_13, created_at);
@@ -0,0 +1,16 @@
+-- 本のページ数と読書の進み具合
+ALTER TABLE books ADD COLUMN IF NOT EXISTS page_count INTEGER;
+
+CREATE TABLE IF NOT EXISTS reading_progress (
+    id          CHAR(36) PRIMARY KEY,
+    user_id     CHAR(36) NOT NULL,
+    isbn_13     CHAR(13) NOT NULL,
+    page        INTEGER CHECK (page >= 0),
+    percent     DOUBLE PRECISION CHECK (percent >= 0 AND percent <= 100),
+    note        TEXT,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (page IS NOT NULL OR percent IS NOT NULL),
+    FOREIGN KEY (user_id, isbn_13) REFERENCES books(user_id, isbn_13) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS reading_progress_book_idx ON reading_progress (user_id, isbn_13, recorded_at);
@@ -0,0 +1,26 @@
+-- ユーザーごとのタグと、本・メモへの付与
+CREATE TABLE IF NOT EXISTS tags (
+    id          CHAR(36) PRIMARY KEY,
+    user_id     CHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS book_tags (
+    tag_id      CHAR(36) NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    user_id     CHAR(36) NOT NULL,
+    isbn_13     CHAR(13) NOT NULL,
+    PRIMARY KEY (tag_id, isbn_13),
+    FOREIGN KEY (user_id, isbn_13) REFERENCES books(user_id, isbn_13) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS book_tags_book_idx ON book_tags (user_id, isbn_13);
+
+CREATE TABLE IF NOT EXISTS memo_tags (
+    tag_id      CHAR(36) NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    memo_id     CHAR(36) NOT NULL REFERENCES memo(id) ON DELETE CASCADE,
+    PRIMARY KEY (tag_id, memo_id)
+);
+
+CREATE INDEX IF NOT EXISTS memo_tags_memo_id_idx ON memo_tags (memo_id);
@@ -0,0 +1,23 @@
+-- 本を好きな順に並べておける本棚
+CREATE TABLE IF NOT EXISTS shelves (
+    id          CHAR(36) PRIMARY KEY,
+    user_id     CHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS shelf_books (
+    shelf_id    CHAR(36) NOT NULL REFERENCES shelves(id) ON DELETE CASCADE,
+    user_id     CHAR(36) NOT NULL,
+    isbn_13     CHAR(13) NOT NULL,
+    position    INTEGER NOT NULL,
+    note        TEXT NOT NULL DEFAULT '',
+    added_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (shelf_id, isbn_13),
+    FOREIGN KEY (user_id, isbn_13) REFERENCES books(user_id, isbn_13) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS shelf_books_book_idx ON shelf_books (user_id, isbn_13);
@@ -0,0 +1,7 @@
+-- 条件式で本を集めるスマート本棚
+CREATE TABLE IF NOT EXISTS saved_queries (
+    shelf_id    CHAR(36) PRIMARY KEY REFERENCES shelves(id) ON DELETE CASCADE,
+    expression  TEXT NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,4 @@
+-- 書誌情報の取得元。手入力した本はISBNの代わりに内部の識別子を持つ
+ALTER TABLE books
+	ADD COLUMN IF NOT EXISTS source TEXT NOT NULL DEFAULT 'provider'
+		CHECK (source IN ('provider', 'manual'));
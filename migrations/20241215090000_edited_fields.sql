@@ -0,0 +1,2 @@
+-- 利用者が書き換えた書誌情報の項目。再取得してもこれらの項目は上書きしない
+ALTER TABLE books ADD COLUMN IF NOT EXISTS edited_fields TEXT[] NOT NULL DEFAULT '{}';
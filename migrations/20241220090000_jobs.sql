@@ -0,0 +1,19 @@
+-- 書誌情報の取得などを後から行うためのジョブ
+CREATE TABLE IF NOT EXISTS jobs (
+    id           CHAR(36) PRIMARY KEY,
+    user_id      CHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind         TEXT NOT NULL CHECK (kind IN ('fetch_metadata')),
+    isbn_13      CHAR(13) NOT NULL,
+    status       TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'succeeded', 'failed')),
+    attempts     INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 5,
+    run_at       TIMESTAMPTZ NOT NULL DEFAULT now(),
+    locked_at    TIMESTAMPTZ,
+    last_error   TEXT,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS jobs_status_run_at_idx ON jobs (status, run_at);
+CREATE INDEX IF NOT EXISTS jobs_user_isbn_idx ON jobs (user_id, isbn_13);
@@ -0,0 +1,12 @@
+-- 取得元の応答をそのまま保存し、同じISBNの問い合わせを減らす
+-- ユーザーをまたいで共有する
+CREATE TABLE IF NOT EXISTS metadata_cache (
+    provider    TEXT NOT NULL,
+    isbn_13     CHAR(13) NOT NULL,
+    payload     TEXT NOT NULL,
+    fetched_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at  TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (provider, isbn_13)
+);
+
+CREATE INDEX IF NOT EXISTS metadata_cache_expires_at_idx ON metadata_cache (expires_at);
@@ -0,0 +1,6 @@
+-- 手元に保存した表紙の更新日時。NULLなら取得元の画像のURLしか無い
+ALTER TABLE books ADD COLUMN IF NOT EXISTS cover_updated_at TIMESTAMPTZ;
+
+-- 表紙の取り寄せもジョブで行う
+ALTER TABLE jobs DROP CONSTRAINT IF EXISTS jobs_kind_check;
+ALTER TABLE jobs ADD CONSTRAINT jobs_kind_check CHECK (kind IN ('fetch_metadata', 'fetch_cover'));
@@ -0,0 +1,6 @@
+-- 外部のサービスから取り込んだメモの出どころ。取り込み直しても同じメモを二重に登録しない
+ALTER TABLE memo ADD COLUMN IF NOT EXISTS source_key TEXT;
+
+-- ハイライトの本文を含むことがあるので、長さによらず索引に収まるようにハッシュで比べる
+CREATE UNIQUE INDEX IF NOT EXISTS memo_source_key_idx
+    ON memo (user_id, isbn_13, md5(source_key)) WHERE source_key IS NOT NULL;
@@ -0,0 +1,16 @@
+-- 本の5段階の評価。NULLなら評価なし
+ALTER TABLE books ADD COLUMN IF NOT EXISTS rating SMALLINT CHECK (rating BETWEEN 1 AND 5);
+
+-- これまでは取り込んだ評価を感想のメモの先頭に「評価: ★★★★☆」と書き残していたので、最初のものを評価にする
+UPDATE books SET rating = reviews.rating
+FROM (
+    SELECT DISTINCT ON (user_id, isbn_13) user_id, isbn_13,
+        (length(stars) - length(replace(stars, '★', ''))) / length('★') AS rating
+    FROM (
+        SELECT user_id, isbn_13, created_at, substring(text FROM '^評価: ((?:★|☆){5})') AS stars FROM memo
+    ) AS memo
+    WHERE stars IS NOT NULL
+    ORDER BY user_id, isbn_13, created_at
+) AS reviews
+WHERE books.user_id = reviews.user_id AND books.isbn_13 = reviews.isbn_13
+    AND books.rating IS NULL AND reviews.rating BETWEEN 1 AND 5;
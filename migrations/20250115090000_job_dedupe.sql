@@ -0,0 +1,13 @@
+-- 同じ本の終わっていないジョブは1件だけにする
+-- これまでに重複して積まれたものは古い1件を残して失敗にする
+UPDATE jobs SET status = 'failed', last_error = 'duplicate job', locked_at = NULL, updated_at = now()
+WHERE status IN ('pending', 'running') AND id IN (
+    SELECT id FROM (
+        SELECT id, row_number() OVER (PARTITION BY user_id, kind, isbn_13 ORDER BY created_at, id) AS rank
+        FROM jobs WHERE status IN ('pending', 'running')
+    ) AS queued
+    WHERE queued.rank > 1
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS jobs_active_unique_idx ON jobs (user_id, kind, isbn_13)
+    WHERE status IN ('pending', 'running');